@@ -0,0 +1,19 @@
+module Admin
+  class UsersController < ApplicationController
+    PER_PAGE = 25
+
+    def index
+      @users = User.admins.page(params[:page]).per(PER_PAGE)
+    end
+
+    def show
+      @user = User.find(params[:id])
+    end
+
+    def destroy
+      user = User.find(params[:id])
+      user.deactivate!(params[:reason], notify: true)
+      redirect_to admin_users_path
+    end
+  end
+end
@@ -0,0 +1,20 @@
+class Account < ApplicationRecord
+  STATUSES = %i[trial active suspended closed].freeze
+  TRIAL_DAYS, GRACE_DAYS = 14, 7
+
+  def self.create_trial(owner)
+    create!(owner: owner, status: :trial, trial_ends_at: TRIAL_DAYS.days.from_now)
+  end
+
+  def balance
+    invoices.sum(:amount) - payments.sum(:amount)
+  end
+
+  def suspend!(at = Time.current)
+    update!(status: :suspended, suspended_at: at)
+  end
+
+  def within_grace_period?
+    suspended_at && suspended_at > GRACE_DAYS.days.ago
+  end
+end
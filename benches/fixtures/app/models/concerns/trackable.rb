@@ -0,0 +1,11 @@
+module Trackable
+  TRACKED_EVENTS = %w[created updated destroyed].freeze
+
+  def track(event, payload = {})
+    Tracker.record(self.class.name, event, payload)
+  end
+
+  def tracked_events
+    TRACKED_EVENTS
+  end
+end
@@ -0,0 +1,36 @@
+class User < ApplicationRecord
+  include Trackable
+
+  ROLES = %w[admin member guest].freeze
+  DEFAULT_ROLE = "member"
+
+  def self.find_by_email(email)
+    where(email: email.downcase).first
+  end
+
+  def self.admins
+    where(role: "admin")
+  end
+
+  def initialize(name, email, role = DEFAULT_ROLE, active: true)
+    @name = name
+    @email = email
+    @role = role
+    @active = active
+  end
+
+  def admin?
+    role == "admin"
+  end
+
+  def display_name
+    full_name = "#{first_name} #{last_name}"
+    full_name.strip.empty? ? email : full_name
+  end
+
+  def deactivate!(reason = nil, notify: false)
+    self.active = false
+    UserMailer.deactivated(self, reason).deliver_later if notify
+    save!
+  end
+end
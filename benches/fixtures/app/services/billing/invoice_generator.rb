@@ -0,0 +1,35 @@
+module Billing
+  class InvoiceGenerator
+    TAX_RATE = 0.2
+
+    class Error < StandardError; end
+    class MissingAccountError < Error; end
+
+    def self.call(account, period)
+      new(account, period).call
+    end
+
+    def initialize(account, period)
+      @account = account
+      @period = period
+    end
+
+    def call
+      raise MissingAccountError, "account is required" unless @account
+
+      lines = line_items
+      subtotal = lines.sum(&:amount)
+      Invoice.create!(account: @account, lines: lines, total: total(subtotal))
+    end
+
+    private
+
+    def line_items
+      @account.subscriptions.map { |s| LineItem.new(s.plan.name, s.plan.price) }
+    end
+
+    def total(subtotal, tax_rate: TAX_RATE)
+      subtotal + subtotal * tax_rate
+    end
+  end
+end
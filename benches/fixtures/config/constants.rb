@@ -0,0 +1,10 @@
+$app_name = "Bench"
+$debug = false
+
+APP_VERSION = "1.0.0"
+SUPPORTED_LOCALES = %w[en de uk].freeze
+
+module Config
+  MAX_UPLOAD_SIZE = 10 * 1024 * 1024
+  TIMEOUTS = { read: 5, write: 10 }.freeze
+end
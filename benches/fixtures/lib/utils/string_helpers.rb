@@ -0,0 +1,15 @@
+module Utils
+  module StringHelpers
+    ELLIPSIS = "..."
+
+    def self.truncate(text, length = 30, omission: ELLIPSIS)
+      return text if text.length <= length
+
+      text[0, length - omission.length] + omission
+    end
+
+    def self.titleize(text)
+      text.split("_").map(&:capitalize).join(" ")
+    end
+  end
+end
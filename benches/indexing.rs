@@ -0,0 +1,22 @@
+use std::path::Path;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use rust_ruby_ls::{config::Config, indexer::Indexer};
+
+const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/fixtures");
+
+fn indexing_benchmark(c: &mut Criterion) {
+    let dir = Path::new(FIXTURES_DIR);
+    let config = Config::default();
+    let symbols_count: usize = Indexer::index_files(dir, &config).iter().map(|i| i.symbols.len()).sum();
+
+    let mut group = c.benchmark_group("indexing");
+    // reported as symbols per second
+    group.throughput(Throughput::Elements(symbols_count as u64));
+    group.bench_function("index_files", |b| b.iter(|| Indexer::index_files(black_box(dir), &config)));
+    group.finish();
+}
+
+criterion_group!(benches, indexing_benchmark);
+criterion_main!(benches);
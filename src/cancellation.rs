@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::Receiver;
+use log::info;
+use lsp_server::{Message, RequestId};
+use lsp_types::{
+    notification::{Cancel, Notification},
+    request::{Request, Shutdown},
+    CancelParams, NumberOrString,
+};
+
+/*
+ * Flag checked by long-running handlers to stop early once the client cancels the request.
+ */
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/*
+ * Tokens of the in-flight requests. Requests are handled one by one on the main thread,
+ * so $/cancelRequest is processed by the thread forwarding the messages to it.
+ */
+#[derive(Clone, Default)]
+pub struct Cancellations {
+    in_flight: Arc<Mutex<HashMap<RequestId, CancellationToken>>>,
+}
+
+impl Cancellations {
+    /*
+     * Forwards the messages to the returned receiver, tracking requests and handling cancellations on the way.
+     * Forwarding stops after the shutdown request so that the connection can receive the exit notification.
+     */
+    pub fn forward(&self, receiver: Receiver<Message>) -> (Receiver<Message>, JoinHandle<()>) {
+        let (sender, forwarded) = crossbeam_channel::unbounded();
+        let cancellations = self.clone();
+
+        let handle = thread::spawn(move || {
+            for msg in receiver {
+                let is_shutdown = match &msg {
+                    Message::Request(req) => {
+                        cancellations.start(&req.id);
+                        req.method == Shutdown::METHOD
+                    }
+
+                    Message::Notification(not) if not.method == Cancel::METHOD => {
+                        match serde_json::from_value::<CancelParams>(not.params.clone()) {
+                            Ok(params) => cancellations.cancel(&Self::to_request_id(params.id)),
+                            Err(e) => info!("Invalid cancel params: {e}"),
+                        }
+                        continue;
+                    }
+
+                    _ => false,
+                };
+
+                if sender.send(msg).is_err() || is_shutdown {
+                    break;
+                }
+            }
+        });
+
+        (forwarded, handle)
+    }
+
+    pub fn token(&self, id: &RequestId) -> CancellationToken {
+        self.in_flight.lock().unwrap().get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn finish(&self, id: &RequestId) {
+        self.in_flight.lock().unwrap().remove(id);
+    }
+
+    fn start(&self, id: &RequestId) {
+        self.in_flight.lock().unwrap().insert(id.clone(), CancellationToken::default());
+    }
+
+    fn cancel(&self, id: &RequestId) {
+        match self.in_flight.lock().unwrap().get(id) {
+            Some(token) => token.cancel(),
+            None => info!("Request #{id} is not in flight, ignoring cancellation"),
+        }
+    }
+
+    fn to_request_id(id: NumberOrString) -> RequestId {
+        match id {
+            NumberOrString::Number(n) => n.into(),
+            NumberOrString::String(s) => s.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_server::{Notification as ServerNotification, Request as ServerRequest};
+
+    use super::*;
+
+    #[test]
+    fn forward_test() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let cancellations = Cancellations::default();
+        let (forwarded, handle) = cancellations.forward(receiver);
+
+        let request = |id: i32, method: &str| Message::Request(ServerRequest::new(id.into(), method.to_string(), ()));
+        let cancel = |id: i32| {
+            let params = CancelParams {
+                id: NumberOrString::Number(id),
+            };
+            Message::Notification(ServerNotification::new(Cancel::METHOD.to_string(), params))
+        };
+
+        sender.send(request(1, "workspace/symbol")).unwrap();
+        sender.send(request(2, "workspace/symbol")).unwrap();
+        sender.send(cancel(1)).unwrap();
+        sender.send(request(3, Shutdown::METHOD)).unwrap();
+        sender.send(request(4, "workspace/symbol")).unwrap();
+        handle.join().unwrap();
+
+        let ids: Vec<RequestId> = forwarded
+            .try_iter()
+            .filter_map(|m| match m {
+                Message::Request(r) => Some(r.id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![RequestId::from(1), RequestId::from(2), RequestId::from(3)], ids);
+
+        assert!(cancellations.token(&1.into()).is_cancelled());
+        assert!(!cancellations.token(&2.into()).is_cancelled());
+
+        cancellations.finish(&1.into());
+        assert!(!cancellations.token(&1.into()).is_cancelled());
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use log::error;
+use regex::Regex;
+use serde::Deserialize;
+
+/*
+ * Server configuration, passed by the client in the initialization options.
+ */
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    // extensions of the files to index as Ruby
+    pub file_extensions: Vec<String>,
+    // names of the files without extension to index as Ruby
+    pub file_names: Vec<String>,
+    // resolve constants referenced in YARD tags of comments, e.g. `@return [Foo]`
+    pub yard_types: bool,
+    // use the nearest ancestor of the workspace root containing one of the markers as the project root
+    pub detect_project_root: bool,
+    pub project_root_markers: Vec<String>,
+    // kinds of the symbols shown in the document outline, e.g. "class" or "constant"
+    pub outline_kinds: Vec<String>,
+    // symbols searched for constants not found in the lexical scopes of the reference
+    pub global_fallback: GlobalFallback,
+    // search the constants nested in any namespace when the global one isn't found either, the closest
+    // namespace to the reference first, e.g. Billing::Settings::Config for Config in Billing::Invoice
+    pub nested_constant_fallback: bool,
+    // globs of the test files relative to the project root, e.g. "spec/**"
+    pub test_file_globs: Vec<String>,
+    // hide symbols of the test files from the workspace symbol search, goto definition still finds them
+    pub exclude_test_symbols: bool,
+    // never spawn processes (e.g. `rails runner`), the environment is detected from the files only
+    pub disable_shell_commands: bool,
+    // scope methods defined outside of classes by the file path, e.g. `def helper` in admin/user.rb is Admin::User::helper
+    pub scope_top_level_methods: bool,
+    // the most definitions returned by goto definition, the closest ones to the file are kept, 0 keeps all of them
+    pub max_definitions: usize,
+    // index the Ruby code of `.erb` templates (e.g. Rails views), the rest of the template is ignored
+    pub erb_templates: bool,
+    // workspace symbol queries shorter than that return nothing, e.g. the first typed character
+    pub min_symbol_query_length: usize,
+    // the most workspace symbols returned for a query, the best matches are kept
+    pub max_symbol_results: usize,
+    // milliseconds without changes before a changed document is reindexed, 0 reindexes on every change
+    pub reindex_debounce_ms: u64,
+    // rename methods across the workspace, experimental since the call sites are resolved on the best effort basis
+    pub experimental_rename: bool,
+    // acronyms of the file names inflection like in Zeitwerk, e.g. "HTML" for html_parser.rb defining HTMLParser
+    pub acronyms: Vec<String>,
+    // what the container of the workspace symbols shows, e.g. "Admin" or "app/models/admin/user.rb" for Admin::User
+    pub symbol_container: SymbolContainer,
+    // directories relative to the project root mapped to the top-level scope, e.g. lib/my_gem/client.rb is MyGem::Client
+    pub autoload_roots: Vec<String>,
+    // show the method signatures of the RBS files in "sig/" of the project root on hover, the files are read
+    // once on startup, so the changed signatures are shown after a restart of the server
+    pub rbs_signatures: bool,
+    // levels of the nested definitions indexed in a file, e.g. modules in modules, the deeper ones are skipped
+    // so that the generated code (e.g. thousands of nested modules) doesn't overflow the stack. The nodes wrapping
+    // the definitions take the levels as well, e.g. `if` and its branches or `BEGIN` blocks
+    pub max_nesting_depth: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GlobalFallback {
+    // project, gems and stubs
+    #[default]
+    All,
+    // only the files in the project root
+    Project,
+    Disabled,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SymbolContainer {
+    // the enclosing scope, e.g. the class of a method
+    #[default]
+    Scope,
+    // the file relative to the project root
+    Path,
+    None,
+}
+
+impl Config {
+    pub fn is_ruby_file(&self, path: &Path) -> bool {
+        let extension = path.extension().and_then(OsStr::to_str);
+        let file_name = path.file_name().and_then(OsStr::to_str);
+
+        match (extension, file_name) {
+            (Some("erb"), _) => self.erb_templates,
+            (Some(e), _) => self.file_extensions.iter().any(|fe| fe == e),
+            (None, Some(n)) => self.file_names.iter().any(|f| f == n),
+            (None, None) => false,
+        }
+    }
+
+    /*
+     * Gets the project root for the workspace root, it can be different when the editor
+     * opened a subdirectory of the project.
+     */
+    pub fn project_root(&self, root_dir: &Path) -> PathBuf {
+        if !self.detect_project_root {
+            return root_dir.to_path_buf();
+        }
+
+        root_dir
+            .ancestors()
+            .find(|dir| self.project_root_markers.iter().any(|m| dir.join(m).exists()))
+            .unwrap_or(root_dir)
+            .to_path_buf()
+    }
+
+    /*
+     * Gets the directory of the RBS signatures in the project root when they're enabled and it exists.
+     */
+    pub fn rbs_signatures_dir(&self, root_dir: &Path) -> Option<PathBuf> {
+        let sig_dir = root_dir.join("sig");
+        (self.rbs_signatures && sig_dir.is_dir()).then_some(sig_dir)
+    }
+
+    pub fn is_outline_kind(&self, kind: &str) -> bool {
+        self.outline_kinds.iter().any(|k| k == kind)
+    }
+
+    /*
+     * Combines the test file globs into a regex matching paths relative to the project root.
+     */
+    pub fn test_files_regex(&self) -> Option<Regex> {
+        if self.test_file_globs.is_empty() {
+            return None;
+        }
+
+        let pattern = self.test_file_globs.iter().map(|g| Self::glob_to_regex(g)).join("|");
+        match Regex::new(&format!("^(?:{pattern})$")) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                error!("Invalid test file globs {:?}: {e}", self.test_file_globs);
+                None
+            }
+        }
+    }
+
+    /*
+     * Converts the glob to a regex, "**" matches any number of directories, "*" and "?" match within a directory.
+     */
+    fn glob_to_regex(glob: &str) -> String {
+        let mut regex = String::new();
+        let mut chars = glob.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                _ => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+
+        regex
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file_extensions: ["rb", "rake", "gemspec", "ru", "jbuilder"].iter().map(|s| s.to_string()).collect(),
+            file_names: ["Rakefile", "Gemfile"].iter().map(|s| s.to_string()).collect(),
+            yard_types: false,
+            detect_project_root: false,
+            project_root_markers: ["Gemfile", ".ruby-version", ".git"].iter().map(|s| s.to_string()).collect(),
+            outline_kinds: ["class", "module", "method", "singleton_method", "constant"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            global_fallback: GlobalFallback::All,
+            nested_constant_fallback: false,
+            test_file_globs: ["spec/**", "test/**"].iter().map(|s| s.to_string()).collect(),
+            exclude_test_symbols: false,
+            disable_shell_commands: false,
+            scope_top_level_methods: false,
+            max_definitions: 50,
+            erb_templates: false,
+            min_symbol_query_length: 2,
+            max_symbol_results: 500,
+            reindex_debounce_ms: 300,
+            experimental_rename: false,
+            acronyms: vec![],
+            symbol_container: SymbolContainer::Scope,
+            autoload_roots: vec!["lib".to_string()],
+            rbs_signatures: false,
+            max_nesting_depth: 200,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ruby_file() {
+        let config = Config::default();
+
+        assert!(config.is_ruby_file(Path::new("/a/app/models/user.rb")));
+        assert!(config.is_ruby_file(Path::new("/a/lib/tasks/db.rake")));
+        assert!(config.is_ruby_file(Path::new("/a/Rakefile")));
+        assert!(config.is_ruby_file(Path::new("/a/Gemfile")));
+        assert!(!config.is_ruby_file(Path::new("/a/Gemfile.lock")));
+        assert!(!config.is_ruby_file(Path::new("/a/README.md")));
+        assert!(!config.is_ruby_file(Path::new("/a/Makefile")));
+        assert!(!config.is_ruby_file(Path::new("/a/app/views/users/show.html.erb")));
+
+        let config = Config {
+            erb_templates: true,
+            ..Default::default()
+        };
+        assert!(config.is_ruby_file(Path::new("/a/app/views/users/show.html.erb")));
+    }
+
+    #[test]
+    fn test_project_root() {
+        let fixture_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/project_root");
+        let models_dir = fixture_dir.join("app/models");
+        let mut config = Config {
+            detect_project_root: true,
+            project_root_markers: vec!["Gemfile".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(fixture_dir.join("app"), config.project_root(&models_dir));
+        assert_eq!(fixture_dir.join("app"), config.project_root(&fixture_dir.join("app")));
+
+        config.project_root_markers = vec!["unknown.marker".to_string()];
+        assert_eq!(models_dir, config.project_root(&models_dir));
+
+        config.detect_project_root = false;
+        config.project_root_markers = vec!["Gemfile".to_string()];
+        assert_eq!(models_dir, config.project_root(&models_dir));
+    }
+
+    #[test]
+    fn test_rbs_signatures_dir() {
+        let fixture_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rbs");
+        let mut config = Config::default();
+        assert_eq!(None, config.rbs_signatures_dir(&fixture_dir));
+
+        config.rbs_signatures = true;
+        assert_eq!(Some(fixture_dir.join("sig")), config.rbs_signatures_dir(&fixture_dir));
+        assert_eq!(None, config.rbs_signatures_dir(&fixture_dir.join("sig")));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let config: Config = serde_json::from_str(r#"{ "fileExtensions": ["rb"] }"#).unwrap();
+
+        assert_eq!(vec!["rb"], config.file_extensions);
+        assert_eq!(Config::default().file_names, config.file_names);
+        assert_eq!(GlobalFallback::All, config.global_fallback);
+
+        let config: Config = serde_json::from_str(r#"{ "globalFallback": "project" }"#).unwrap();
+        assert_eq!(GlobalFallback::Project, config.global_fallback);
+        assert_eq!(SymbolContainer::Scope, config.symbol_container);
+
+        let config: Config = serde_json::from_str(r#"{ "symbolContainer": "path" }"#).unwrap();
+        assert_eq!(SymbolContainer::Path, config.symbol_container);
+    }
+
+    #[test]
+    fn test_test_files_regex() {
+        let regex = Config::default().test_files_regex().unwrap();
+
+        assert!(regex.is_match("spec/models/user_spec.rb"));
+        assert!(regex.is_match("test/test_helper.rb"));
+        assert!(!regex.is_match("app/models/spec.rb"));
+        assert!(!regex.is_match("specification.rb"));
+
+        let config = Config {
+            test_file_globs: vec!["**/*_spec.rb".to_string(), "t?st/*.rb".to_string()],
+            ..Default::default()
+        };
+        let regex = config.test_files_regex().unwrap();
+
+        assert!(regex.is_match("engines/billing/spec/invoice_spec.rb"));
+        assert!(regex.is_match("test/a.rb"));
+        assert!(!regex.is_match("test/unit/a.rb"));
+        assert!(!regex.is_match("app/invoice.rb"));
+
+        let config = Config {
+            test_file_globs: vec![],
+            ..Default::default()
+        };
+        assert!(config.test_files_regex().is_none());
+    }
+
+    #[test]
+    fn test_outline_kinds() {
+        let config = Config::default();
+        assert!(config.is_outline_kind("class"));
+        assert!(config.is_outline_kind("constant"));
+        assert!(!config.is_outline_kind("class_variable"));
+
+        let config: Config = serde_json::from_str(r#"{ "outlineKinds": ["class", "module"] }"#).unwrap();
+        assert!(config.is_outline_kind("module"));
+        assert!(!config.is_outline_kind("method"));
+    }
+}
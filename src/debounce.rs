@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/*
+ * Files changed in the editor waiting to be reindexed. A file is ready once it hasn't changed for the delay,
+ * so a burst of changes while typing is reindexed once.
+ */
+pub struct Debouncer {
+    delay: Duration,
+    changed_at: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration) -> Debouncer {
+        Debouncer {
+            delay,
+            changed_at: HashMap::new(),
+        }
+    }
+
+    pub fn touch(&mut self, file: PathBuf, now: Instant) {
+        self.changed_at.insert(file, now);
+    }
+
+    pub fn remove(&mut self, file: &Path) {
+        self.changed_at.remove(file);
+    }
+
+    /*
+     * Gets the time left until the earliest pending file is ready, None when nothing is pending.
+     */
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        self.changed_at.values().min().map(|changed_at| (*changed_at + self.delay).saturating_duration_since(now))
+    }
+
+    /*
+     * Removes the files which haven't changed for the delay and returns them.
+     */
+    pub fn take_ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let ready: Vec<PathBuf> = self
+            .changed_at
+            .iter()
+            .filter(|(_, changed_at)| now.saturating_duration_since(**changed_at) >= self.delay)
+            .map(|(file, _)| file.clone())
+            .collect();
+
+        for file in &ready {
+            self.changed_at.remove(file);
+        }
+
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_of_changes_test() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(Duration::from_millis(300));
+        let file = PathBuf::from("app/models/user.rb");
+
+        assert_eq!(None, debouncer.timeout(start));
+
+        debouncer.touch(file.clone(), start);
+        debouncer.touch(file.clone(), start + Duration::from_millis(200));
+
+        // the second change restarts the delay
+        let now = start + Duration::from_millis(400);
+        assert_eq!(Some(Duration::from_millis(100)), debouncer.timeout(now));
+        assert!(debouncer.take_ready(now).is_empty());
+
+        let now = start + Duration::from_millis(500);
+        assert_eq!(Some(Duration::ZERO), debouncer.timeout(now));
+        assert_eq!(vec![file], debouncer.take_ready(now));
+        assert_eq!(None, debouncer.timeout(now));
+    }
+
+    #[test]
+    fn files_are_independent_test() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(Duration::from_millis(300));
+
+        debouncer.touch(PathBuf::from("a.rb"), start);
+        debouncer.touch(PathBuf::from("b.rb"), start + Duration::from_millis(200));
+        debouncer.touch(PathBuf::from("c.rb"), start);
+        debouncer.remove(Path::new("c.rb"));
+
+        let now = start + Duration::from_millis(300);
+        assert_eq!(vec![PathBuf::from("a.rb")], debouncer.take_ready(now));
+        assert_eq!(Some(Duration::from_millis(200)), debouncer.timeout(now));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{cell::RefCell, collections::HashMap, path::PathBuf, rc::Rc};
+
+use anyhow::Result;
+use lsp_types::{Position, TextDocumentContentChangeEvent};
+use tree_sitter::{InputEdit, Parser, Point, Tree};
+use tree_sitter_ruby::language;
+
+use crate::parsers::erb::erb_to_ruby;
+
+// documents opened in the editor by their paths, shared by the server with the finder
+pub type Documents = Rc<RefCell<HashMap<PathBuf, Document>>>;
+
+/*
+ * Document opened in the editor, its tree is kept in sync with the changes by editing it
+ * and reparsing incrementally.
+ */
+pub struct Document {
+    pub source: Vec<u8>,
+    pub tree: Tree,
+    // the source is an ERB template, its tree is parsed from the Ruby code of the tags
+    erb: bool,
+}
+
+impl Document {
+    pub fn new(source: String) -> Result<Document> {
+        Self::with_source(source, false)
+    }
+
+    /*
+     * Creates the document of an ERB template, its source is kept as is since the converted Ruby code
+     * has the same offsets, but every change reparses the whole template.
+     */
+    pub fn new_erb(source: String) -> Result<Document> {
+        Self::with_source(source, true)
+    }
+
+    fn with_source(source: String, erb: bool) -> Result<Document> {
+        let source = source.into_bytes();
+        let tree = if erb { parse(&erb_to_ruby(&source), None)? } else { parse(&source, None)? };
+
+        Ok(Document {
+            source,
+            tree,
+            erb,
+        })
+    }
+
+    pub fn apply_change(&mut self, change: TextDocumentContentChangeEvent) -> Result<()> {
+        let range = match change.range {
+            Some(r) => r,
+            None => {
+                *self = Document::with_source(change.text, self.erb)?;
+                return Ok(());
+            }
+        };
+
+        let (start_byte, start_position) = self.position_to_offset(range.start)?;
+        let (old_end_byte, old_end_position) = self.position_to_offset(range.end)?;
+        let new_end_byte = start_byte + change.text.len();
+        let new_end_position = match change.text.rfind('\n') {
+            Some(i) => Point {
+                row: start_position.row + change.text.matches('\n').count(),
+                column: change.text.len() - i - 1,
+            },
+            None => Point {
+                row: start_position.row,
+                column: start_position.column + change.text.len(),
+            },
+        };
+
+        self.source.splice(start_byte..old_end_byte, change.text.into_bytes());
+        self.tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position,
+        });
+        // a change of a tag affects the Ruby code outside of the edited range, e.g. `<%` turns the text after it into code
+        self.tree =
+            if self.erb { parse(&erb_to_ruby(&self.source), None)? } else { parse(&self.source, Some(&self.tree))? };
+
+        Ok(())
+    }
+
+    /*
+     * Converts the LSP position (UTF-16 based) to the byte offset and the tree-sitter point (byte based).
+     */
+    fn position_to_offset(&self, position: Position) -> Result<(usize, Point)> {
+        let row = position.line as usize;
+        let line_start = if row == 0 {
+            0
+        } else {
+            self.source
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| **b == b'\n')
+                .nth(row - 1)
+                .map(|(i, _)| i + 1)
+                .ok_or(anyhow!("Line {row} is out of the document"))?
+        };
+
+        let line_end = self.source[line_start..].iter().position(|b| *b == b'\n').map(|i| line_start + i);
+        let line = std::str::from_utf8(&self.source[line_start..line_end.unwrap_or(self.source.len())])?;
+
+        let mut utf16_column = 0;
+        let mut column = line.len();
+        for (i, c) in line.char_indices() {
+            if utf16_column >= position.character as usize {
+                column = i;
+                break;
+            }
+            utf16_column += c.len_utf16();
+        }
+
+        Ok((
+            line_start + column,
+            Point {
+                row,
+                column,
+            },
+        ))
+    }
+}
+
+fn parse(source: &[u8], old_tree: Option<&Tree>) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(language())?;
+
+    parser.parse(source, old_tree).ok_or(anyhow!("Failed to parse the document"))
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Range;
+
+    use super::*;
+
+    const SOURCE: &str = "class Foo\n  def bar\n  end\nend\n";
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position::new(start.0, start.1),
+                end: Position::new(end.0, end.1),
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn assert_same_as_full_parse(document: &Document) {
+        let full = parse(&document.source, None).unwrap();
+        assert_eq!(full.root_node().to_sexp(), document.tree.root_node().to_sexp());
+    }
+
+    #[test]
+    fn apply_change_single_line_test() {
+        let mut document = Document::new(SOURCE.to_string()).unwrap();
+        let old_tree = document.tree.clone();
+
+        document.apply_change(change((1, 6), (1, 9), "baz")).unwrap();
+
+        assert_eq!("class Foo\n  def baz\n  end\nend\n", String::from_utf8_lossy(&document.source));
+        assert_same_as_full_parse(&document);
+
+        // the tree is reused, only the edited line has changed
+        let mut edited_tree = old_tree;
+        edited_tree.edit(&InputEdit {
+            start_byte: 16,
+            old_end_byte: 19,
+            new_end_byte: 19,
+            start_position: Point::new(1, 6),
+            old_end_position: Point::new(1, 9),
+            new_end_position: Point::new(1, 9),
+        });
+        let changed_rows: Vec<(usize, usize)> =
+            edited_tree.changed_ranges(&document.tree).map(|r| (r.start_point.row, r.end_point.row)).collect();
+        assert!(changed_rows.iter().all(|(start, end)| *start == 1 && *end == 1), "{changed_rows:?}");
+    }
+
+    #[test]
+    fn apply_change_multiline_test() {
+        let mut document = Document::new(SOURCE.to_string()).unwrap();
+
+        document.apply_change(change((2, 5), (2, 5), "\n\n  def qux\n  end")).unwrap();
+        document.apply_change(change((4, 6), (4, 9), "quux")).unwrap();
+
+        assert_eq!(
+            "class Foo\n  def bar\n  end\n\n  def quux\n  end\nend\n",
+            String::from_utf8_lossy(&document.source)
+        );
+        assert_same_as_full_parse(&document);
+    }
+
+    #[test]
+    fn apply_change_utf16_test() {
+        let mut document = Document::new("A = \"ü😀\"; B = 1\n".to_string()).unwrap();
+
+        // "😀" takes two UTF-16 code units
+        document.apply_change(change((0, 11), (0, 12), "C")).unwrap();
+
+        assert_eq!("A = \"ü😀\"; C = 1\n", String::from_utf8_lossy(&document.source));
+        assert_same_as_full_parse(&document);
+    }
+
+    #[test]
+    fn apply_full_change_test() {
+        let mut document = Document::new(SOURCE.to_string()).unwrap();
+
+        document
+            .apply_change(TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "module Bar\nend\n".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!("module Bar\nend\n", String::from_utf8_lossy(&document.source));
+        assert_same_as_full_parse(&document);
+    }
+
+    #[test]
+    fn apply_change_erb_test() {
+        let mut document = Document::new_erb("<p>Foo</p>\n".to_string()).unwrap();
+        assert_eq!("(program)", document.tree.root_node().to_sexp());
+
+        document.apply_change(change((0, 3), (0, 3), "<%= ")).unwrap();
+        document.apply_change(change((0, 10), (0, 10), " %>")).unwrap();
+
+        assert_eq!("<p><%= Foo %></p>\n", String::from_utf8_lossy(&document.source));
+        assert_eq!("(program (constant))", document.tree.root_node().to_sexp());
+    }
+}
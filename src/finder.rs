@@ -1,58 +1,346 @@
 use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    iter,
     path::{Path, PathBuf},
     rc::Rc,
-    sync::Arc,
+    sync::{Arc, OnceLock},
     time::Instant,
 };
 
-use log::{debug, info};
+use log::{debug, info, warn};
 
 use anyhow::{Context, Result};
-use tree_sitter::{Node, Point};
+use itertools::{Either, Itertools};
+use regex::Regex;
+use tree_sitter::{Node, Point, Query, QueryCursor, Tree};
 
-use crate::parsers::methods::get_method_variable_definition;
-use crate::parsers::scopes::{get_context_scope, get_parent_scope_resolution};
+use crate::parsers::calls::{get_alias_keyword_name, get_literal_key};
+use crate::parsers::comments::get_yard_type_at;
+use crate::parsers::methods::{
+    get_block_variable_definition, get_method_variable_definition, parse_instance_variable_readers,
+};
+use crate::parsers::scopes::{get_context_scope, get_parent_scope_resolution, is_constant_path};
 use crate::{
+    cancellation::CancellationToken,
+    config::{Config, GlobalFallback},
+    documents::{Document, Documents},
+    indexer::{FileIndex, Indexer},
     parsers::{
         general::read_file_tree,
         identifiers::get_identifier_context,
-        types::{NodeKind, NodeName, Scope},
+        types::{NodeKind, NodeName, Scope, KERNEL_SCOPE_VALUE, OBJECT_SCOPE_VALUE, SCOPE_DELIMITER},
     },
+    rbs::RbsSignatures,
     ruby_filename_converter::RubyFilenameConverter,
-    symbols_matcher::SymbolsMatcher,
-    types::{RSymbol, RVariable},
+    symbols_matcher::{SymbolMatch, SymbolsMatcher},
+    types::{RSymbol, RSymbolIndex, RSymbols, RVariable, SourceKind},
 };
 
+// builtins parsed as identifiers, they don't have a definition
+const PSEUDO_IDENTIFIERS: [&str; 6] = ["__method__", "__callee__", "__dir__", "__FILE__", "__LINE__", "__ENCODING__"];
+
+// methods calling the method named by the first argument, e.g. `send(:process)`
+const SEND_METHODS: [&str; 3] = ["send", "public_send", "__send__"];
+
+// operators which can't be defined as methods
+const NON_METHOD_OPERATORS: [&str; 4] = ["&&", "||", "and", "or"];
+
+// links of a call chain followed to infer the receiver type, bounded so that recursive methods don't loop
+const MAX_INFERENCE_DEPTH: usize = 6;
+
+// resolved positions kept for the requests repeated on the same position, e.g. definition and highlight
+const DEFINITIONS_CACHE_SIZE: usize = 32;
+
+// the least recently used definitions are first, they are evicted when the cache is full
+type DefinitionsCache = VecDeque<((PathBuf, Point), Vec<Arc<RSymbol>>)>;
+
+// the trees of the files read to infer the return types of their methods, the least recently used first
+const TREES_CACHE_SIZE: usize = 16;
+
+type TreesCache = VecDeque<(PathBuf, Rc<(Tree, Vec<u8>)>)>;
+
+// assignments of the instance variables returned by the methods, see `infer_return_types`
+static INSTANCE_VARIABLE_ASSIGNMENTS: OnceLock<Query> = OnceLock::new();
+
+/*
+ * Locations of the method name to rename: the definitions and the call sites resolved to them,
+ * and the call sites which can't be resolved confidently, they're left for the user to check.
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MethodRename {
+    pub name: String,
+    pub locations: Vec<(PathBuf, Point)>,
+    pub ambiguous: Vec<(PathBuf, Point)>,
+}
+
+/*
+ * Inferred type of a value: either an instance of the class or the class itself, e.g. `self` in a class method.
+ */
+#[derive(Debug, Clone)]
+enum InferredType {
+    Instance(Arc<RSymbol>),
+    Singleton(Arc<RSymbol>),
+}
+
+impl InferredType {
+    fn symbol(&self) -> &Arc<RSymbol> {
+        match self {
+            InferredType::Instance(symbol) | InferredType::Singleton(symbol) => symbol,
+        }
+    }
+
+    fn is_singleton(&self) -> bool {
+        matches!(self, InferredType::Singleton(_))
+    }
+}
+
 pub struct Finder {
     root_dir: PathBuf,
-    symbols: Rc<Vec<Arc<RSymbol>>>,
+    config: Rc<Config>,
+    symbols: RSymbols,
     ruby_filename_converter: Rc<RubyFilenameConverter>,
+    test_files_regex: Option<Regex>,
+    stubs_dir: Option<PathBuf>,
+    definitions_cache: RefCell<DefinitionsCache>,
+    trees_cache: RefCell<TreesCache>,
+    signatures: RbsSignatures,
+    documents: Documents,
 }
 
 impl Finder {
     pub fn new(
         root_dir: &Path,
-        symbols: Rc<Vec<Arc<RSymbol>>>,
+        config: Rc<Config>,
+        symbols: RSymbols,
         ruby_filename_converter: Rc<RubyFilenameConverter>,
     ) -> Finder {
+        let test_files_regex = config.test_files_regex();
+
         Finder {
             root_dir: root_dir.to_path_buf(),
+            config,
             symbols,
             ruby_filename_converter,
+            test_files_regex,
+            stubs_dir: None,
+            definitions_cache: RefCell::new(DefinitionsCache::new()),
+            trees_cache: RefCell::new(TreesCache::new()),
+            signatures: RbsSignatures::default(),
+            documents: Documents::default(),
+        }
+    }
+
+    /*
+     * Sets the directory of the core classes stubs, the rest of the files outside the root are gems.
+     */
+    pub fn with_stubs_dir(mut self, stubs_dir: Option<PathBuf>) -> Finder {
+        self.stubs_dir = stubs_dir;
+        self
+    }
+
+    /*
+     * Sets the method signatures of the RBS files, they're matched to the methods by the fully qualified names.
+     */
+    pub fn with_signatures(mut self, signatures: RbsSignatures) -> Finder {
+        self.signatures = signatures;
+        self
+    }
+
+    /*
+     * Shares the documents opened in the editor, their trees are used instead of the files on disk.
+     */
+    pub fn with_documents(mut self, documents: Documents) -> Finder {
+        self.documents = documents;
+        self
+    }
+
+    /*
+     * Gets the RBS signatures of the method, every overload is a separate one, e.g. "(Float rate) -> Float".
+     */
+    pub fn method_signatures(&self, symbol: &RSymbol) -> &[String] {
+        let separator = match symbol {
+            RSymbol::Method(_) => "#",
+            RSymbol::SingletonMethod(_) => ".",
+            _ => return &[],
+        };
+        let scope = symbol.full_scope();
+        let Some(name) = scope.last() else {
+            return &[];
+        };
+
+        self.signatures.get(&format!("{}{separator}{name}", scope.prefix(scope.len() - 1)))
+    }
+
+    /*
+     * Gets where the symbol comes from, as recorded when its file was indexed. The symbols which aren't
+     * indexed (e.g. local variables) are in the files being edited, so they're in the project.
+     */
+    pub fn source_kind(&self, symbol: &RSymbol) -> SourceKind {
+        self.symbols.load().source(symbol.file()).unwrap_or(SourceKind::Project)
+    }
+
+    fn file_source_kind(&self, file: &Path) -> SourceKind {
+        if self.stubs_dir.as_ref().is_some_and(|d| file.starts_with(d)) {
+            return SourceKind::Stub;
+        }
+
+        let relative_path = match file.strip_prefix(&self.root_dir) {
+            Ok(p) => p.to_str(),
+            Err(_) => return SourceKind::Gem,
+        };
+
+        match (relative_path, &self.test_files_regex) {
+            (Some(path), Some(regex)) if regex.is_match(path) => SourceKind::Test,
+            _ => SourceKind::Project,
+        }
+    }
+
+    /*
+     * Atomically replaces the symbols, requests in progress keep using the previous set.
+     */
+    pub fn publish_symbols(&self, symbols: RSymbolIndex) {
+        self.symbols.store(Arc::new(symbols));
+        // definitions in other files can point to the reindexed one, so none of the cached ones can be trusted
+        self.definitions_cache.borrow_mut().clear();
+        self.trees_cache.borrow_mut().clear();
+    }
+
+    /*
+     * Drops the cached definitions of the positions in the file, e.g. when its document is edited before reindexing.
+     */
+    pub fn invalidate_definitions(&self, file: &Path) {
+        self.trees_cache.borrow_mut().retain(|(f, _)| f != file);
+        self.definitions_cache.borrow_mut().retain(|((f, _), _)| f != file);
+    }
+
+    /*
+     * The definitions are cached by the path the server gets from the document URI, it's the same for all
+     * the requests of the document, so it isn't canonicalized on the hot path.
+     */
+    fn cached_definitions(&self, file: &Path, position: Point) -> Option<Vec<Arc<RSymbol>>> {
+        let mut cache = self.definitions_cache.borrow_mut();
+        let index = cache.iter().position(|((f, p), _)| f == file && *p == position)?;
+        let entry = cache.remove(index)?;
+        let definitions = entry.1.clone();
+        cache.push_back(entry);
+
+        debug!("Using cached definitions in {file:?} at {position:?}");
+        Some(definitions)
+    }
+
+    fn cache_definitions(&self, file: &Path, position: Point, definitions: &[Arc<RSymbol>]) {
+        let mut cache = self.definitions_cache.borrow_mut();
+        if cache.len() >= DEFINITIONS_CACHE_SIZE {
+            cache.pop_front();
+        }
+        cache.push_back(((file.to_path_buf(), position), definitions.to_vec()));
+    }
+
+    /*
+     * Replaces symbols and references of the reindexed file.
+     */
+    pub fn publish_file_index(&self, file_index: FileIndex) {
+        self.publish_file_indexes(vec![file_index]);
+    }
+
+    pub fn publish_file_indexes(&self, file_indexes: Vec<FileIndex>) {
+        let mut symbols = RSymbolIndex::clone(&self.symbols.load());
+        for file_index in file_indexes {
+            self.insert_file_index(&mut symbols, file_index);
+        }
+
+        self.publish_symbols(symbols);
+    }
+
+    /*
+     * Drops the symbols and references of the file, e.g. the closed document in the single file mode.
+     */
+    pub fn remove_file(&self, file: &Path) {
+        let mut symbols = RSymbolIndex::clone(&self.symbols.load());
+        symbols.retain(|f| f != file);
+
+        self.publish_symbols(symbols);
+    }
+
+    /*
+     * Moves the index of the renamed file or directory: the symbols of the old path are dropped
+     * and the new one is indexed from the disk, both are published at once.
+     */
+    pub fn rename_path(&self, old_path: &Path, new_path: &Path) -> Result<()> {
+        let file_indexes = if new_path.is_dir() {
+            Indexer::index_files(new_path, &self.config)
+        } else if self.config.is_ruby_file(new_path) {
+            vec![Indexer::index_file(new_path.to_path_buf(), &self.config)?]
+        } else {
+            vec![]
+        };
+        info!("Reindexing {} files renamed from {old_path:?} to {new_path:?}", file_indexes.len());
+
+        let mut symbols = RSymbolIndex::clone(&self.symbols.load());
+        symbols.retain(|file| !file.starts_with(old_path));
+        for file_index in file_indexes {
+            self.insert_file_index(&mut symbols, file_index);
         }
+
+        self.publish_symbols(symbols);
+
+        Ok(())
+    }
+
+    fn insert_file_index(&self, symbols: &mut RSymbolIndex, file_index: FileIndex) {
+        let file_index = if self.config.scope_top_level_methods {
+            Indexer::scope_top_level_methods(file_index, &self.ruby_filename_converter)
+        } else {
+            file_index
+        };
+
+        let source = self.file_source_kind(&file_index.file);
+        // only the project is renamed, the references of gems and stubs would take memory for nothing
+        let references = match source {
+            SourceKind::Project | SourceKind::Test => file_index.references,
+            SourceKind::Gem | SourceKind::Stub => Default::default(),
+        };
+        symbols.insert(file_index.file, source, file_index.symbols, references);
     }
 
     pub fn find_by_path(&self, path: &Path) -> Vec<Arc<RSymbol>> {
-        self.symbols.iter().filter(|s| s.file() == path).cloned().collect()
+        self.symbols.load().get(path).to_vec()
+    }
+
+    /*
+     * Finds the symbols by the exact fully qualified name, e.g. "ActiveRecord::Base". Methods are qualified
+     * by "#" for the instance ones or "." for the singleton ones (e.g. "Foo::Bar#baz"), "::" matches both.
+     */
+    pub fn find_by_fqn(&self, fqn: &str) -> Vec<Arc<RSymbol>> {
+        let (scope, is_kind): (String, fn(&RSymbol) -> bool) = if let Some((owner, method)) = fqn.rsplit_once('#') {
+            (format!("{owner}{SCOPE_DELIMITER}{method}"), |s| matches!(s, RSymbol::Method(_)))
+        } else if let Some((owner, method)) = fqn.rsplit_once('.') {
+            (format!("{owner}{SCOPE_DELIMITER}{method}"), |s| matches!(s, RSymbol::SingletonMethod(_)))
+        } else {
+            (fqn.to_string(), |_| true)
+        };
+
+        self.symbols.load().iter().filter(|s| is_kind(s) && s.full_scope().to_string() == scope).cloned().collect()
     }
 
-    pub fn fuzzy_find_symbol(&self, query: &str) -> Vec<Arc<RSymbol>> {
+    pub fn fuzzy_find_symbol(&self, query: &str, token: &CancellationToken) -> Option<Vec<SymbolMatch>> {
         let start = Instant::now();
-        let result = if query.is_empty() {
-            // optimization to not overload telescope on request without a query
-            vec![]
+        let result = if query.is_empty() || query.chars().count() < self.config.min_symbol_query_length {
+            // optimization to not overload telescope on request without a query, short ones match almost everything
+            Some(vec![])
         } else {
-            SymbolsMatcher::new(&self.root_dir).match_rsymbols(query, &self.symbols)
+            let symbols = self.symbols.load();
+            let symbols = symbols
+                .iter_with_source()
+                .filter(|(_, source)| !self.config.exclude_test_symbols || *source != SourceKind::Test);
+            let matches = SymbolsMatcher::new().match_rsymbols(query, symbols, token);
+
+            matches.map(|mut m| {
+                m.truncate(self.config.max_symbol_results);
+                m
+            })
         };
 
         info!("Finding symbol by {} took {:?}", query, start.elapsed());
@@ -60,151 +348,4076 @@ impl Finder {
         result
     }
 
+    /*
+     * Finds positions of all method calls and constants with the name, they still have to be
+     * resolved to filter out the ones referencing other symbols with the same name.
+     */
+    pub fn find_reference_candidates(&self, name: &str) -> Vec<(PathBuf, Point)> {
+        self.symbols.load().references(name).map(|(file, point)| (file.to_path_buf(), point)).sorted().collect()
+    }
+
+    /*
+     * Finds the locations of the method under the position (its definition or a call) to rename across the workspace.
+     * Only the call sites with the method name which resolve to the renamed definitions exclusively are renamed,
+     * the ones resolving to other definitions as well (e.g. an untyped receiver) or to nothing are ambiguous.
+     * The opened documents are used instead of the files, and only the files of the project are renamed.
+     */
+    pub fn find_method_rename(
+        &self,
+        documents: &HashMap<PathBuf, Document>,
+        file: &Path,
+        position: Point,
+    ) -> Result<MethodRename> {
+        let mut trees: HashMap<PathBuf, (Tree, Vec<u8>)> = HashMap::new();
+        let (tree, source) = Self::cached_file_tree(&mut trees, documents, file)?;
+        let node = tree
+            .root_node()
+            .descendant_for_point_range(position, position)
+            .filter(|n| n.kind() == NodeKind::Identifier)
+            .ok_or(anyhow!("No method name to rename at {position:?}"))?;
+        let name = node.utf8_text(source)?.to_string();
+
+        let is_definition_name = node
+            .parent()
+            .filter(|p| matches!(p.kind().try_into(), Ok(NodeKind::Method | NodeKind::SingletonMethod)))
+            .and_then(|p| p.child_by_field_name(NodeName::Name))
+            == Some(node);
+        let definitions: Vec<Arc<RSymbol>> = if is_definition_name {
+            self.find_by_path(file).into_iter().filter(|s| s.location() == &node.start_position()).collect()
+        } else {
+            self.find_definition_in_tree(file, tree, source, position)?
+        };
+        let definitions: Vec<Arc<RSymbol>> = definitions
+            .into_iter()
+            .filter(|s| matches!(**s, RSymbol::Method(_) | RSymbol::SingletonMethod(_)))
+            .filter(|s| s.full_scope().last() == Some(name.as_str()))
+            .collect();
+        if definitions.is_empty() {
+            bail!("No method definition of {name} to rename at {position:?}");
+        }
+        // the call sites in the project would break with a gem or the core method renamed only there
+        if let Some(definition) = definitions.iter().find(|d| !d.file().starts_with(&self.root_dir)) {
+            bail!("{name} is defined outside of the project in {:?}", definition.file());
+        }
+
+        let mut rename = MethodRename {
+            name: name.clone(),
+            ..Default::default()
+        };
+
+        // definitions created dynamically (e.g. `define_method :name`) don't have the name at their location
+        for definition in &definitions {
+            let location = (definition.file().to_path_buf(), *definition.location());
+            let (tree, source) = match Self::cached_file_tree(&mut trees, documents, definition.file()) {
+                Ok(tree) => tree,
+                Err(e) => {
+                    warn!("Failed to read the definition of {name} in {:?}: {e}", definition.file());
+                    rename.ambiguous.push(location);
+                    continue;
+                }
+            };
+            let text = tree
+                .root_node()
+                .descendant_for_point_range(location.1, location.1)
+                .and_then(|n| n.utf8_text(source).ok());
+
+            if text == Some(name.as_str()) {
+                rename.locations.push(location);
+            } else {
+                rename.ambiguous.push(location);
+            }
+        }
+
+        // the references are indexed in the project files only
+        for (file, point) in self.find_reference_candidates(&name) {
+            let (tree, source) = match Self::cached_file_tree(&mut trees, documents, &file) {
+                Ok(tree) => tree,
+                Err(e) => {
+                    warn!("Skipping the calls of {name} in {file:?}: {e}");
+                    continue;
+                }
+            };
+            let resolved = self.find_definition_in_tree(&file, tree, source, point).unwrap_or_default();
+
+            if !resolved.is_empty() && resolved.iter().all(|s| definitions.contains(s)) {
+                rename.locations.push((file, point));
+            } else if resolved.is_empty() || resolved.iter().any(|s| definitions.contains(s)) {
+                rename.ambiguous.push((file, point));
+            }
+        }
+
+        info!(
+            "Renaming {} locations of {name}, {} ambiguous ones are left",
+            rename.locations.len(),
+            rename.ambiguous.len()
+        );
+
+        Ok(rename)
+    }
+
+    /*
+     * Gets the tree of the opened document or reads the file once for all its locations.
+     */
+    fn cached_file_tree<'b>(
+        trees: &'b mut HashMap<PathBuf, (Tree, Vec<u8>)>,
+        documents: &'b HashMap<PathBuf, Document>,
+        file: &Path,
+    ) -> Result<(&'b Tree, &'b [u8])> {
+        if let Some(document) = documents.get(file) {
+            return Ok((&document.tree, &document.source));
+        }
+
+        if !trees.contains_key(file) {
+            trees.insert(file.to_path_buf(), read_file_tree(file)?);
+        }
+        let (tree, source) = &trees[file];
+
+        Ok((tree, source))
+    }
+
     pub fn find_definition(&self, file: &Path, position: Point) -> Result<Vec<Arc<RSymbol>>> {
+        if let Some(definitions) = self.cached_definitions(file, position) {
+            return Ok(definitions);
+        }
+
         let (tree, source) = read_file_tree(file)?;
 
+        self.find_definition_in_tree(file, &tree, &source, position)
+    }
+
+    /*
+     * Finds the definition using the given tree of the file instead of reading it,
+     * e.g. the tree of the document opened in the editor with unsaved changes.
+     * The results are cached by the position until the file is reindexed or its cached definitions are invalidated.
+     */
+    pub fn find_definition_in_tree(
+        &self,
+        file: &Path,
+        tree: &Tree,
+        source: &[u8],
+        position: Point,
+    ) -> Result<Vec<Arc<RSymbol>>> {
+        if let Some(definitions) = self.cached_definitions(file, position) {
+            return Ok(definitions);
+        }
+
         let node = tree
             .root_node()
             .descendant_for_point_range(position, position)
             .ok_or(anyhow!("Failed to find node of definition"))?;
 
-        let node_kind = node.kind().try_into().with_context(|| format!("Unknown node kind: {}", node.kind()))?;
+        let definitions = self.find_node_definition(node, file, source, position)?;
+        self.cache_definitions(file, position, &definitions);
+
+        Ok(definitions)
+    }
+
+    fn find_node_definition(
+        &self,
+        node: Node,
+        file: &Path,
+        source: &[u8],
+        position: Point,
+    ) -> Result<Vec<Arc<RSymbol>>> {
+        if let Some((method_name, receiver)) = Self::get_operator_call(&node, source) {
+            return self.find_operator_method(&method_name, receiver, file, source);
+        }
+
+        // the boolean operators aren't methods, nothing defines them
+        if NON_METHOD_OPERATORS.contains(&node.kind()) {
+            return Ok(vec![]);
+        }
+
+        let node = Self::get_resolvable_node(node);
 
-        match node_kind {
-            NodeKind::Constant => Ok(self.find_constant(&node, file, &source)),
-            NodeKind::Identifier => self.find_identifier(&node, file, &source),
-            NodeKind::GlobalVariable => self.find_global_variable(&node, &source),
-            _ => Err(anyhow!("Find definition of {} node kind is not supported", node.kind())),
+        // classes known at runtime only are expected to be unresolved unless something defines the method
+        let definitions = self.find_resolvable_node_definition(node, file, source, position);
+        if Self::is_dynamic_constant(&node, source) && definitions.as_ref().map_or(true, |d| d.is_empty()) {
+            debug!("{:?} refers to a class known at runtime only, skipping", node.utf8_text(source));
+            return Ok(vec![]);
         }
+
+        definitions
     }
 
-    fn find_identifier(&self, node: &Node, file: &Path, source: &[u8]) -> Result<Vec<Arc<RSymbol>>> {
-        info!("Trying to find an identifier in {:?} at {:?}", file, node.start_position());
-        let identifier = node.utf8_text(source).unwrap();
+    fn find_resolvable_node_definition(
+        &self,
+        node: Node,
+        file: &Path,
+        source: &[u8],
+        position: Point,
+    ) -> Result<Vec<Arc<RSymbol>>> {
+        if let Some((method_name, call)) = Self::get_sent_method_name(&node, source) {
+            let method = call.child_by_field_name(NodeName::Method).unwrap_or(node);
+            return self.find_method_definition(
+                method_name,
+                file,
+                source,
+                call.child_by_field_name(NodeName::Receiver),
+                &method,
+            );
+        }
 
-        let parent = node.parent().with_context(|| {
-            format!("Failed to find parent for identifier in {:?} at {:?}", file, node.start_position())
-        })?;
+        if let Some(method_name) = Self::get_aliased_method_name(&node, source) {
+            return Ok(self.find_alias_method(method_name, &node, source));
+        }
 
-        let context_node = get_identifier_context(node).ok_or(anyhow!(
-            "Failed to determine context of node in {:?} at {:?}",
-            file,
-            node.start_position()
-        ))?;
+        if let Some((variable, constant_scope)) = Self::get_variable_qualified_constant(&node, source) {
+            return Ok(self.find_variable_qualified_constant(&variable, &constant_scope, file, source));
+        }
 
-        match context_node.kind().try_into()? {
-            NodeKind::Call => {
-                let receiver = parent.child_by_field_name(NodeName::Receiver);
-                self.find_method_definition(identifier, file, receiver)
+        // a constant called with arguments is a method, e.g. the Kernel conversion method `Array(x)`
+        if let Some(call) = node.parent().filter(|p| Self::is_constant_method_call(p, &node)) {
+            let method_name = node.utf8_text(source)?;
+            return self.find_method_definition(
+                method_name,
+                file,
+                source,
+                call.child_by_field_name(NodeName::Receiver),
+                &node,
+            );
+        }
+
+        // nothing to resolve for the other nodes, e.g. keywords or literals
+        match node.kind().try_into() {
+            Ok(NodeKind::Constant) => Ok(self.find_constant(&node, file, source)),
+            Ok(NodeKind::Identifier) => self.find_identifier(&node, file, source),
+            Ok(NodeKind::GlobalVariable) => self.find_global_variable(&node, source),
+            Ok(NodeKind::Comment) => Ok(self.find_yard_type(&node, position, source)),
+            _ => {
+                debug!("Find definition of {} node kind is not supported", node.kind());
+                Ok(vec![])
             }
+        }
+    }
 
-            NodeKind::Method | NodeKind::SingletonMethod => {
-                let variable_def = get_method_variable_definition(node, &context_node, file, source).ok_or(anyhow!(
-                    "Failed to find variable definition in {:?} at {:?}",
-                    file,
-                    node.start_position()
-                ))?;
-                let symbol = Arc::new(RSymbol::Variable(RVariable {
-                    file: file.to_path_buf(),
-                    name: variable_def.utf8_text(source).unwrap().to_string(),
-                    scope: Scope::new(vec![]),
-                    location: variable_def.start_position(),
-                    parent: None,
-                }));
-                Ok(vec![symbol])
+    /*
+     * Caps the number of definitions by the configured maximum, keeping the ones in the same file
+     * and the project over gems and stubs.
+     */
+    pub fn limit_definitions(&self, file: &Path, mut symbols: Vec<Arc<RSymbol>>) -> Vec<Arc<RSymbol>> {
+        if self.config.max_definitions == 0 || symbols.len() <= self.config.max_definitions {
+            return symbols;
+        }
+
+        info!("Truncating {} definitions to {}", symbols.len(), self.config.max_definitions);
+        self.sort_by_proximity(file, &mut symbols);
+        symbols.truncate(self.config.max_definitions);
+
+        symbols
+    }
+
+    /*
+     * Checks if the constant is the method name of the call, e.g. `Array` in `Array(value)`.
+     */
+    fn is_constant_method_call(call: &Node, node: &Node) -> bool {
+        node.kind() == NodeKind::Constant
+            && call.kind() == NodeKind::Call
+            && call.child_by_field_name(NodeName::Method).as_ref() == Some(node)
+    }
+
+    /*
+     * Checks if the node is a call of a method returning a class which can't be resolved statically,
+     * e.g. `described_class` or `const_get(name)` with a non-literal argument.
+     */
+    fn is_dynamic_constant(node: &Node, source: &[u8]) -> bool {
+        if node.kind() != NodeKind::Identifier {
+            return false;
+        }
+
+        let call = node.parent().filter(|p| p.kind() == NodeKind::Call);
+        let is_call_method = call.and_then(|c| c.child_by_field_name(NodeName::Method)).as_ref() == Some(node);
+        match node.utf8_text(source) {
+            Ok("described_class") => {
+                !is_call_method || call.and_then(|c| c.child_by_field_name(NodeName::Receiver)).is_none()
             }
 
-            _ => Ok(vec![]),
+            Ok("const_get") if is_call_method => {
+                let argument =
+                    call.and_then(|c| c.child_by_field_name(NodeName::Arguments)).and_then(|a| a.named_child(0));
+                argument.map(|a| get_literal_key(&a, source).is_none()).unwrap_or(true)
+            }
+
+            _ => false,
         }
     }
 
-    fn find_method_definition(
+    /*
+     * Gets the node to resolve, in a region with syntax errors the cursor can be on a token of an unfinished
+     * expression (e.g. "." in "foo."), then the closest named node before it is resolved instead.
+     */
+    fn get_resolvable_node(node: Node) -> Node {
+        let is_resolvable = |n: &Node| {
+            matches!(
+                n.kind().try_into(),
+                Ok(NodeKind::Constant | NodeKind::Identifier | NodeKind::GlobalVariable | NodeKind::Comment)
+            )
+        };
+
+        let in_error = iter::successors(Some(node), |n| n.parent()).any(|n| n.is_error());
+        if is_resolvable(&node) || !in_error {
+            return node;
+        }
+
+        node.prev_named_sibling().filter(is_resolvable).unwrap_or(node)
+    }
+
+    /*
+     * Gets the variable qualifying the constant and the constant path after it,
+     * e.g. `klass` and A::B for B in `klass::A::B`.
+     */
+    fn get_variable_qualified_constant<'b>(node: &Node<'b>, source: &[u8]) -> Option<(Node<'b>, Scope)> {
+        if node.kind() != NodeKind::Constant {
+            return None;
+        }
+
+        let mut names = vec![node.utf8_text(source).ok()?];
+        let mut resolution = node.parent().filter(|p| {
+            p.kind() == NodeKind::ScopeResolution && p.child_by_field_name(NodeName::Name) == Some(*node)
+        })?;
+
+        loop {
+            let scope = resolution.child_by_field_name(NodeName::Scope)?;
+            match scope.kind().try_into().ok()? {
+                NodeKind::Identifier => {
+                    names.reverse();
+                    return Some((scope, Scope::from(names)));
+                }
+
+                NodeKind::ScopeResolution => {
+                    names.push(scope.child_by_field_name(NodeName::Name)?.utf8_text(source).ok()?);
+                    resolution = scope;
+                }
+
+                _ => return None,
+            }
+        }
+    }
+
+    /*
+     * Finds the constant qualified by a local variable holding a class, e.g. CONST in `klass = Foo; klass::CONST`.
+     * Unlike the lexical lookup it's searched in the class and its ancestors only.
+     */
+    fn find_variable_qualified_constant(
         &self,
-        method_name: &str,
+        variable: &Node,
+        constant_scope: &Scope,
         file: &Path,
-        receiver: Option<Node>,
-    ) -> Result<Vec<Arc<RSymbol>>> {
-        let receiver_kind = receiver.map(|n| n.kind());
-        info!("Trying to find method: {method_name}, receiver kind = {receiver_kind:?}");
+        source: &[u8],
+    ) -> Vec<Arc<RSymbol>> {
+        info!("Trying to find {constant_scope} qualified by a variable");
 
-        let receiver_definitions = receiver.map(|r| self.find_definition(file, r.start_position())).transpose()?;
+        let classes = match self.infer_variable_classes(variable, file, source) {
+            Some(classes) => classes,
+            None => {
+                info!("Couldn't infer the class of {:?}", variable.utf8_text(source));
+                return vec![];
+            }
+        };
 
-        Ok(self
-            .symbols
-            .iter()
-            // TODO: depends on the type of receiver, change after adding more definition types
-            .filter(|s| matches!(***s, RSymbol::SingletonMethod(_)))
-            .filter(|s| {
-                let defs = if let Some(rd) = &receiver_definitions { rd } else { return true };
-                let parent = if let Some(p) = s.parent() { p } else { return true };
+        let all_symbols = self.symbols.load();
+        let mut visited: Vec<Arc<RSymbol>> = Vec::new();
+        let mut queue = classes;
+        while !queue.is_empty() {
+            let class = queue.remove(0);
+            if visited.iter().any(|v| Arc::ptr_eq(v, &class)) {
+                continue;
+            }
 
-                defs.contains(parent)
-            })
-            .filter(|s| s.full_scope().last().map(|l| l == method_name).unwrap_or(false))
-            .cloned()
-            .collect())
+            let scope = class.full_scope().join(constant_scope);
+            let found: Vec<Arc<RSymbol>> = all_symbols
+                .iter()
+                .filter(|s| matches!(***s, RSymbol::Class(_) | RSymbol::Module(_) | RSymbol::Constant(_)))
+                .filter(|s| s.full_scope() == &scope)
+                .cloned()
+                .collect();
+            if !found.is_empty() {
+                return found;
+            }
+
+            queue.extend(self.find_supertypes(&class));
+            visited.push(class);
+        }
+
+        vec![]
     }
 
-    fn find_global_variable(&self, node: &Node, source: &[u8]) -> Result<Vec<Arc<RSymbol>>> {
-        info!("Trying to find a global variable");
+    /*
+     * Infers classes held by the local variable from the constant assigned to it, e.g. Foo in `klass = Foo`.
+     */
+    fn infer_variable_classes(&self, variable: &Node, file: &Path, source: &[u8]) -> Option<Vec<Arc<RSymbol>>> {
+        let context = get_identifier_context(variable)?;
+        let definition = get_method_variable_definition(variable, &context, file, source)?;
+        let assignment = definition.parent().filter(|p| p.kind() == NodeKind::Assignment)?;
+        let value = assignment.child_by_field_name(NodeName::Right).filter(is_constant_path)?;
+        let name =
+            if value.kind() == NodeKind::ScopeResolution { value.child_by_field_name(NodeName::Name)? } else { value };
 
-        let node_kind: NodeKind = node.kind().try_into()?;
-        if node_kind != NodeKind::GlobalVariable {
-            bail!("Node kind is not global variable")
+        let classes: Vec<Arc<RSymbol>> = self
+            .find_constant(&name, file, source)
+            .into_iter()
+            .filter(|s| matches!(**s, RSymbol::Class(_) | RSymbol::Module(_)))
+            .collect();
+
+        Some(classes).filter(|c| !c.is_empty())
+    }
+
+    /*
+     * Finds the class referenced in a YARD tag of the comment, e.g. `# @return [Foo::Bar]`.
+     */
+    fn find_yard_type(&self, node: &Node, position: Point, source: &[u8]) -> Vec<Arc<RSymbol>> {
+        if !self.config.yard_types {
+            return vec![];
         }
 
-        let name = node.utf8_text(source).unwrap();
+        info!("Trying to find a YARD type");
+        let comment = match node.utf8_text(source) {
+            Ok(comment) => comment,
+            Err(_) => return vec![],
+        };
+
+        // convert the position to the offset in the comment text
+        let start = node.start_position();
+        let row = position.row - start.row;
+        let column = if row == 0 { position.column - start.column } else { position.column };
+        let offset = comment.split('\n').take(row).map(|l| l.len() + 1).sum::<usize>() + column;
+
+        match get_yard_type_at(comment, offset) {
+            Some(constant_scope) => self.resolve_constant_in_context(&constant_scope, &get_context_scope(node, source)),
+            None => vec![],
+        }
+    }
 
+    /*
+     * Finds classes and modules referenced at the position.
+     */
+    pub fn find_types(&self, file: &Path, position: Point) -> Result<Vec<Arc<RSymbol>>> {
         Ok(self
-            .symbols
-            .iter()
-            .filter(|s| matches!(***s, RSymbol::GlobalVariable(_) if s.name() == name))
-            .cloned()
+            .find_definition(file, position)?
+            .into_iter()
+            .filter(|s| matches!(**s, RSymbol::Class(_) | RSymbol::Module(_)))
             .collect())
     }
 
-    fn find_constant(&self, node: &Node, file: &Path, source: &[u8]) -> Vec<Arc<RSymbol>> {
-        info!("Trying to find a constant");
-        // traverse down till we hit the whole symbol name
-        let constant_scope = get_parent_scope_resolution(node, source);
+    /*
+     * Finds the superclass and included modules of the class or module.
+     */
+    pub fn find_supertypes(&self, symbol: &RSymbol) -> Vec<Arc<RSymbol>> {
+        let mut supertypes = self.find_superclass(symbol);
+        supertypes.extend(self.find_prepended_modules(symbol));
+        supertypes.extend(self.find_included_modules(symbol));
 
-        let context_scope = get_context_scope(node, source).join(&constant_scope);
+        supertypes
+    }
 
-        let mut file_scope = self.ruby_filename_converter.path_to_scope(file).unwrap_or(Scope::new(vec![]));
-        file_scope.remove_last();
-        let file_scope = file_scope.join(&constant_scope);
+    fn find_superclass(&self, symbol: &RSymbol) -> Vec<Arc<RSymbol>> {
+        let class = match symbol {
+            RSymbol::Class(c) if !c.superclass_scopes.is_empty() => c,
+            _ => return vec![],
+        };
 
-        let symbols = self
-            .symbols
-            .iter()
-            .filter(|s| matches!(***s, RSymbol::Class(_) | RSymbol::Module(_) | RSymbol::Constant(_)));
+        // superclass is referenced from outside of the class body
+        let outer_scope = class.scope.prefix(class.scope.len().saturating_sub(1));
 
-        let results = if constant_scope.is_global() {
-            info!("Global scope, searching for {constant_scope}");
-            symbols.filter(|s| s.full_scope() == &constant_scope).cloned().collect()
-        } else {
-            info!("Searching for {context_scope} or {file_scope} or {context_scope} in the same file");
-            // search in contexts first
-            let found_symbols: Vec<Arc<RSymbol>> = symbols
-                .clone()
-                .filter(|s| {
-                    let name = s.full_scope();
-                    name == &context_scope || name == &file_scope || (name == &constant_scope && s.file() == file)
-                })
-                .cloned()
-                .collect();
+        self.resolve_constant_in_context(&class.superclass_scopes, &outer_scope)
+            .into_iter()
+            .filter(|s| matches!(**s, RSymbol::Class(_)))
+            .collect()
+    }
 
-            // then global
-            if found_symbols.is_empty() {
-                info!("Haven't found anything, searching for global {constant_scope}");
-                symbols.clone().filter(|s| s.full_scope() == &constant_scope).cloned().collect()
-            } else {
-                found_symbols
-            }
+    fn find_included_modules(&self, symbol: &RSymbol) -> Vec<Arc<RSymbol>> {
+        match symbol {
+            RSymbol::Class(c) | RSymbol::Module(c) => self.resolve_modules(&c.include_scopes, &c.scope),
+            _ => vec![],
+        }
+    }
+
+    fn find_prepended_modules(&self, symbol: &RSymbol) -> Vec<Arc<RSymbol>> {
+        match symbol {
+            RSymbol::Class(c) | RSymbol::Module(c) => self.resolve_modules(&c.prepend_scopes, &c.scope),
+            _ => vec![],
+        }
+    }
+
+    fn resolve_modules(&self, module_scopes: &[Scope], class_scope: &Scope) -> Vec<Arc<RSymbol>> {
+        module_scopes
+            .iter()
+            .flat_map(|s| self.resolve_constant_in_context(s, class_scope))
+            .filter(|s| matches!(**s, RSymbol::Class(_) | RSymbol::Module(_)))
+            .collect()
+    }
+
+    /*
+     * Finds instance methods of the class or module and its ancestors in the method resolution order:
+     * prepended modules (the last prepended first), the class itself, included modules (the last included first),
+     * then the superclass.
+     */
+    pub fn methods_of(&self, symbol: &Arc<RSymbol>) -> Vec<Arc<RSymbol>> {
+        let mut visited = Vec::new();
+        let mut ancestors = Vec::new();
+        self.collect_ancestors(symbol, &mut visited, &mut ancestors);
+
+        // the reopened classes are separate symbols with the same scope, e.g. a monkey-patch in another file
+        let mut positions: HashMap<&Scope, usize> = HashMap::new();
+        for (position, ancestor) in ancestors.iter().enumerate() {
+            positions.entry(ancestor.full_scope()).or_insert(position);
+        }
+
+        // the methods are overridden by the closest ancestor, so only its definitions are kept
+        let mut owners: HashMap<&str, usize> = HashMap::new();
+        let all_symbols = self.symbols.load();
+        all_symbols
+            .iter()
+            .filter(|s| matches!(***s, RSymbol::Method(_)))
+            .filter_map(|s| Some((*positions.get(s.parent().as_ref()?.full_scope())?, s)))
+            .sorted_by_key(|(position, _)| *position)
+            .filter(|(position, method)| {
+                let name = method.full_scope().last().unwrap_or_default();
+                *owners.entry(name).or_insert(*position) == *position
+            })
+            .map(|(_, method)| method.clone())
+            .collect()
+    }
+
+    fn collect_ancestors(
+        &self,
+        symbol: &Arc<RSymbol>,
+        visited: &mut Vec<Arc<RSymbol>>,
+        ancestors: &mut Vec<Arc<RSymbol>>,
+    ) {
+        // modules can be included several times (diamonds) or include each other (cycles)
+        if visited.iter().any(|v| Arc::ptr_eq(v, symbol)) {
+            return;
+        }
+        visited.push(symbol.clone());
+
+        for module in self.find_prepended_modules(symbol).iter().rev() {
+            self.collect_ancestors(module, visited, ancestors);
+        }
+        ancestors.push(symbol.clone());
+
+        for module in self.find_included_modules(symbol).iter().rev() {
+            self.collect_ancestors(module, visited, ancestors);
+        }
+        for superclass in self.find_superclass(symbol) {
+            self.collect_ancestors(&superclass, visited, ancestors);
+        }
+    }
+
+    /*
+     * Finds classes inheriting from the class and classes or modules including the module.
+     */
+    pub fn find_subtypes(&self, symbol: &RSymbol) -> Vec<Arc<RSymbol>> {
+        let name = match symbol.full_scope().last() {
+            Some(name) => name,
+            None => return vec![],
         };
 
-        debug!("Found {} results", results.len());
+        self.symbols
+            .load()
+            .iter()
+            .filter(|s| match &***s {
+                RSymbol::Class(c) | RSymbol::Module(c) => {
+                    c.superclass_scopes.last() == Some(name)
+                        || c.include_scopes.iter().chain(&c.prepend_scopes).any(|i| i.last() == Some(name))
+                }
+                _ => false,
+            })
+            .filter(|s| self.find_supertypes(s).iter().any(|t| **t == *symbol))
+            .cloned()
+            .collect()
+    }
 
-        results
+    /*
+     * Resolves the constant the same way Ruby does it lexically: starting from the innermost
+     * context scope and going outwards till the top level.
+     */
+    pub fn resolve_constant_in_context(&self, constant_scope: &Scope, context_scope: &Scope) -> Vec<Arc<RSymbol>> {
+        let all_symbols = self.symbols.load();
+        let symbols = all_symbols
+            .iter()
+            .filter(|s| matches!(***s, RSymbol::Class(_) | RSymbol::Module(_) | RSymbol::Constant(_)));
+
+        if constant_scope.is_global() {
+            let global_scope = Scope::default().join(constant_scope);
+            return symbols.filter(|s| s.full_scope() == &global_scope).cloned().collect();
+        }
+
+        for len in (0..=context_scope.len()).rev() {
+            let candidate = context_scope.prefix(len).join(constant_scope);
+            let found: Vec<Arc<RSymbol>> = symbols.clone().filter(|s| s.full_scope() == &candidate).cloned().collect();
+
+            if !found.is_empty() {
+                return found;
+            }
+        }
+
+        vec![]
+    }
+
+    fn find_identifier(&self, node: &Node, file: &Path, source: &[u8]) -> Result<Vec<Arc<RSymbol>>> {
+        info!("Trying to find an identifier in {:?} at {:?}", file, node.start_position());
+        let identifier = node.utf8_text(source)?;
+
+        if PSEUDO_IDENTIFIERS.contains(&identifier) {
+            info!("{identifier} is a builtin, skipping");
+            return Ok(vec![]);
+        }
+
+        let parent = node.parent().with_context(|| {
+            format!("Failed to find parent for identifier in {:?} at {:?}", file, node.start_position())
+        })?;
+
+        // method name of the call (e.g. "b" in "a.b"), the receiver is a variable or a method call on its own
+        if parent.kind() == NodeKind::Call && parent.child_by_field_name(NodeName::Method) == Some(*node) {
+            let receiver = parent.child_by_field_name(NodeName::Receiver);
+            return self.find_method_definition(identifier, file, source, receiver, node);
+        }
+
+        if let Some(param_node) = get_block_variable_definition(node, file, source) {
+            return Ok(vec![Self::new_variable(file, identifier, &param_node)]);
+        }
+
+        // code with syntax errors may be missing the enclosing method, the error region is used instead
+        let error_region = || iter::successors(node.parent(), |p| p.parent()).find(|p| p.is_error());
+        let context_node = get_identifier_context(node).or_else(error_region).ok_or(anyhow!(
+            "Failed to determine context of node in {:?} at {:?}",
+            file,
+            node.start_position()
+        ))?;
+
+        match context_node.kind().try_into()? {
+            NodeKind::Method | NodeKind::SingletonMethod | NodeKind::Program | NodeKind::Error => {
+                match get_method_variable_definition(node, &context_node, file, source) {
+                    Some(variable_def) => Ok(vec![Self::new_variable(file, identifier, &variable_def)]),
+
+                    // a bare identifier that isn't a variable is a method call without a receiver and parentheses
+                    None => {
+                        info!("Variable definition for '{identifier}' wasn't found, treating it as a method call");
+                        self.find_method_definition(identifier, file, source, None, node)
+                    }
+                }
+            }
+
+            // self of the class or module body is the class itself, so the calls are its singleton methods first
+            NodeKind::Class | NodeKind::Module => {
+                if let Some(variable_def) = get_method_variable_definition(node, &context_node, file, source) {
+                    return Ok(vec![Self::new_variable(file, identifier, &variable_def)]);
+                }
+
+                let enclosing_classes = self.find_enclosing_classes(node, source);
+                let methods = self.find_singleton_methods(identifier, Some(&enclosing_classes));
+                if !methods.is_empty() {
+                    return Ok(methods);
+                }
+
+                self.find_method_definition(identifier, file, source, None, node)
+            }
+
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn new_variable(file: &Path, name: &str, definition: &Node) -> Arc<RSymbol> {
+        Arc::new(RSymbol::Variable(RVariable {
+            file: file.to_path_buf(),
+            name: name.to_string(),
+            scope: Scope::new(vec![]),
+            location: definition.start_position(),
+            parent: None,
+        }))
+    }
+
+    fn find_method_definition(
+        &self,
+        method_name: &str,
+        file: &Path,
+        source: &[u8],
+        receiver: Option<Node>,
+        node: &Node,
+    ) -> Result<Vec<Arc<RSymbol>>> {
+        let receiver_kind = receiver.map(|n| n.kind());
+        info!("Trying to find method: {method_name}, receiver kind = {receiver_kind:?}");
+
+        // calls on self in instance methods are resolved in the class and its ancestors
+        let is_self = receiver.map(|r| r.kind() == NodeKind::SelfNode).unwrap_or(true);
+        if is_self && get_identifier_context(node).map(|c| c.kind() == NodeKind::Method).unwrap_or(false) {
+            let methods = self.find_methods_of_enclosing_classes(method_name, node, source);
+            if !methods.is_empty() {
+                return Ok(methods);
+            }
+        }
+
+        // calls without a receiver which aren't methods of the class are the Kernel methods, e.g. `puts`
+        if receiver.is_none() {
+            let methods = self.find_kernel_methods(method_name);
+            if !methods.is_empty() {
+                return Ok(methods);
+            }
+        }
+
+        if let Some(r) = receiver.filter(|r| Self::is_self_class(r, source)) {
+            let receiver_definitions = self.find_enclosing_classes(&r, source);
+            let mut methods = self.find_singleton_methods(method_name, Some(&receiver_definitions));
+            self.sort_by_proximity(file, &mut methods);
+
+            return Ok(methods);
+        }
+
+        // OpenStruct readers of instance variables are scoped by the variable, e.g. Foo::@options::verbose
+        if let Some(r) = receiver.filter(|r| r.kind() == NodeKind::InstanceVariable) {
+            return Ok(Self::find_instance_variable_readers(method_name, &r, file, source));
+        }
+
+        // receivers of an inferred type (e.g. "user.account" after "user = User.new") are resolved in their classes,
+        // otherwise any method with the name can be called
+        if let Some(r) = receiver
+            .filter(|r| matches!(r.kind().try_into(), Ok(NodeKind::Identifier | NodeKind::Call | NodeKind::SelfNode)))
+        {
+            let types = self.infer_types(&r, file, source, MAX_INFERENCE_DEPTH).unwrap_or_default();
+            let mut methods = self.find_methods_of_inferred_types(method_name, &types);
+            if methods.is_empty() && r.kind() == NodeKind::Call {
+                methods = self.find_instance_methods(method_name, None);
+            }
+
+            if !methods.is_empty() {
+                self.sort_by_proximity(file, &mut methods);
+                return Ok(methods);
+            }
+        }
+
+        // for scoped receivers (e.g. Foo::Bar) the last constant has to be resolved, not the first one
+        let receiver = receiver.map(|r| {
+            if r.kind() == NodeKind::ScopeResolution {
+                r.child_by_field_name(NodeName::Name).unwrap_or(r)
+            } else {
+                r
+            }
+        });
+        let receiver_definitions = receiver
+            .map(|r| {
+                let position = r.start_position();
+                let node = r.descendant_for_point_range(position, position).unwrap_or(r);
+                self.find_node_definition(node, file, source, position)
+            })
+            .transpose()?;
+
+        let mut methods = self.find_singleton_methods(method_name, receiver_definitions.as_ref());
+        self.sort_by_proximity(file, &mut methods);
+
+        Ok(methods)
+    }
+
+    /*
+     * Finds the methods of Object and Kernel (usually from the stubs), they are available everywhere.
+     */
+    fn find_kernel_methods(&self, method_name: &str) -> Vec<Arc<RSymbol>> {
+        let global_scopes = [Scope::from(OBJECT_SCOPE_VALUE), Scope::from(KERNEL_SCOPE_VALUE)];
+        let types: Vec<Arc<RSymbol>> = self
+            .symbols
+            .load()
+            .iter()
+            .filter(|s| matches!(***s, RSymbol::Class(_) | RSymbol::Module(_)))
+            .filter(|s| global_scopes.contains(s.full_scope()))
+            .cloned()
+            .collect();
+
+        self.find_methods_of_types(method_name, &types)
+    }
+
+    fn find_methods_of_types(&self, method_name: &str, types: &[Arc<RSymbol>]) -> Vec<Arc<RSymbol>> {
+        types
+            .iter()
+            .flat_map(|t| self.methods_of(t))
+            .filter(|m| m.full_scope().last() == Some(method_name))
+            .unique_by(Arc::as_ptr)
+            .collect()
+    }
+
+    /*
+     * Finds the instance methods of the instances and the singleton methods of the classes.
+     */
+    fn find_methods_of_inferred_types(&self, method_name: &str, types: &[InferredType]) -> Vec<Arc<RSymbol>> {
+        let (singletons, instances): (Vec<Arc<RSymbol>>, Vec<Arc<RSymbol>>) = types.iter().partition_map(|t| match t {
+            InferredType::Singleton(s) => Either::Left(s.clone()),
+            InferredType::Instance(s) => Either::Right(s.clone()),
+        });
+
+        let mut methods = self.find_methods_of_types(method_name, &instances);
+        if !singletons.is_empty() {
+            methods.extend(self.find_singleton_methods(method_name, Some(&singletons)));
+        }
+
+        methods
+    }
+
+    /*
+     * Infers classes of the value of the expression with simple heuristics instead of a full type inference:
+     * - `Foo.new` is an instance of Foo
+     * - a class method call (e.g. `Current.user`) has the type of the last expression of the method body
+     * - a local variable has the type of the closest value assigned to it
+     * - a method call has the type of the last expression of the method body, which is either one of the above
+     *   or an instance variable assigned in the same class body, e.g. `@account = Account.new` in `initialize`
+     * - a call without a receiver in an instance method is a call on an instance of the enclosing class,
+     *   while in a class method it's a call on the class itself, e.g. `new` is an instance of the class
+     * - `self` is an instance of the enclosing class in instance methods (e.g. returned by a fluent builder method)
+     *   and the class itself elsewhere, e.g. in `def self.build`
+     * Every link of the call chain decreases the depth, None is returned when the type is unknown.
+     */
+    fn infer_types(&self, node: &Node, file: &Path, source: &[u8], depth: usize) -> Option<Vec<InferredType>> {
+        if depth == 0 {
+            info!("Inference depth is exhausted at {:?} in {file:?}", node.start_position());
+            return None;
+        }
+
+        let types = match node.kind().try_into().ok()? {
+            NodeKind::Identifier => {
+                let context = get_identifier_context(node)?;
+                if !matches!(
+                    context.kind().try_into(),
+                    Ok(NodeKind::Method | NodeKind::SingletonMethod | NodeKind::Program)
+                ) {
+                    return None;
+                }
+
+                match get_method_variable_definition(node, &context, file, source) {
+                    Some(variable) => {
+                        let assignment = variable.parent().filter(|p| p.kind() == NodeKind::Assignment)?;
+                        let value = assignment.child_by_field_name(NodeName::Right)?;
+                        self.infer_types(&value, file, source, depth - 1)?
+                    }
+
+                    None => self.infer_call_types(node.utf8_text(source).ok()?, None, node, file, source, depth)?,
+                }
+            }
+
+            NodeKind::Call => {
+                let method = node.child_by_field_name(NodeName::Method)?;
+                let method_name = method.utf8_text(source).ok()?;
+                let receiver = node.child_by_field_name(NodeName::Receiver);
+
+                self.infer_call_types(method_name, receiver, &method, file, source, depth)?
+            }
+
+            NodeKind::SelfNode => {
+                let classes = self.find_enclosing_classes(node, source);
+                if Self::is_in_instance_method(node) {
+                    classes.into_iter().map(InferredType::Instance).collect()
+                } else {
+                    classes.into_iter().map(InferredType::Singleton).collect()
+                }
+            }
+
+            _ => return None,
+        };
+
+        Some(types).filter(|t| !t.is_empty())
+    }
+
+    fn infer_call_types(
+        &self,
+        method_name: &str,
+        receiver: Option<Node>,
+        method: &Node,
+        file: &Path,
+        source: &[u8],
+        depth: usize,
+    ) -> Option<Vec<InferredType>> {
+        let receiver_types = match receiver {
+            Some(r) if is_constant_path(&r) => {
+                let name =
+                    if r.kind() == NodeKind::ScopeResolution { r.child_by_field_name(NodeName::Name)? } else { r };
+
+                self.find_constant(&name, file, source)
+                    .into_iter()
+                    .filter(|s| matches!(**s, RSymbol::Class(_) | RSymbol::Module(_)))
+                    .map(InferredType::Singleton)
+                    .collect()
+            }
+
+            Some(r) => self.infer_types(&r, file, source, depth - 1)?,
+
+            None if method.kind() == NodeKind::Identifier => {
+                let classes = self.find_enclosing_classes(method, source);
+                match get_identifier_context(method)?.kind().try_into().ok()? {
+                    NodeKind::Method => classes.into_iter().map(InferredType::Instance).collect(),
+                    NodeKind::SingletonMethod => classes.into_iter().map(InferredType::Singleton).collect(),
+                    _ => return None,
+                }
+            }
+
+            None => return None,
+        };
+
+        if method_name == "new" {
+            let instances = receiver_types
+                .into_iter()
+                .filter(|t| t.is_singleton() && matches!(**t.symbol(), RSymbol::Class(_)))
+                .map(|t| InferredType::Instance(t.symbol().clone()))
+                .collect();
+            return Some(instances);
+        }
+
+        // class methods returning an instance, e.g. `Current.user`
+        let types = self
+            .find_methods_of_inferred_types(method_name, &receiver_types)
+            .iter()
+            .flat_map(|m| self.infer_return_types(m, depth - 1))
+            .flatten()
+            .unique_by(|t| (t.is_singleton(), Arc::as_ptr(t.symbol())))
+            .collect();
+
+        Some(types)
+    }
+
+    /*
+     * Finds the OpenStruct readers of the instance variable assigned in the methods of the enclosing class,
+     * they're parsed on demand since indexing them would add every reader to the workspace symbols.
+     */
+    fn find_instance_variable_readers(
+        method_name: &str,
+        variable: &Node,
+        file: &Path,
+        source: &[u8],
+    ) -> Vec<Arc<RSymbol>> {
+        let Some(class_node) = iter::successors(variable.parent(), |p| p.parent())
+            .find(|p| matches!(p.kind().try_into(), Ok(NodeKind::Class | NodeKind::Module)))
+        else {
+            return vec![];
+        };
+        let Some(body) = class_node.child_by_field_name(NodeName::Body) else {
+            return vec![];
+        };
+        let class_scope = get_context_scope(variable, source);
+        let Ok(variable_name) = variable.utf8_text(source) else {
+            return vec![];
+        };
+        let variable_scope = class_scope.join(&variable_name.into());
+
+        let mut cursor = body.walk();
+        body.named_children(&mut cursor)
+            .filter_map(|n| match n.kind().try_into() {
+                Ok(NodeKind::Method) => Some(n),
+                // methods passed to the visibility modifiers, e.g. `private def setup`
+                Ok(NodeKind::Call) => n
+                    .child_by_field_name(NodeName::Arguments)
+                    .and_then(|a| a.named_child(0))
+                    .filter(|a| a.kind() == NodeKind::Method),
+                _ => None,
+            })
+            .flat_map(|method| parse_instance_variable_readers(file, source, method, &class_scope))
+            .filter(|reader| {
+                let scope = reader.full_scope();
+                scope.last() == Some(method_name) && scope.prefix(scope.len() - 1) == variable_scope
+            })
+            .map(Arc::new)
+            .collect()
+    }
+
+    /*
+     * Gets the tree of the file, the opened document is preferred over the file on disk,
+     * which is parsed once and kept until the file or the symbols change.
+     */
+    fn file_tree(&self, file: &Path) -> Option<Rc<(Tree, Vec<u8>)>> {
+        if let Some(document) = self.documents.borrow().get(file) {
+            return Some(Rc::new((document.tree.clone(), document.source.clone())));
+        }
+
+        let mut trees = self.trees_cache.borrow_mut();
+        if let Some(index) = trees.iter().position(|(f, _)| f == file) {
+            let entry = trees.remove(index)?;
+            let tree = entry.1.clone();
+            trees.push_back(entry);
+            return Some(tree);
+        }
+
+        let tree = Rc::new(read_file_tree(file).ok()?);
+        if trees.len() >= TREES_CACHE_SIZE {
+            trees.pop_front();
+        }
+        trees.push_back((file.to_path_buf(), tree.clone()));
+
+        Some(tree)
+    }
+
+    /*
+     * Infers the type of the last expression of the method body, see `infer_types`.
+     */
+    fn infer_return_types(&self, method: &RSymbol, depth: usize) -> Option<Vec<InferredType>> {
+        let file_tree = self.file_tree(method.file())?;
+        let (tree, source) = file_tree.as_ref();
+        let location = method.location();
+        let name_node = tree.root_node().descendant_for_point_range(*location, *location)?;
+        let method_node = name_node
+            .parent()
+            .filter(|p| matches!(p.kind().try_into(), Ok(NodeKind::Method | NodeKind::SingletonMethod)))?;
+
+        let body = method_node.child_by_field_name(NodeName::Body)?;
+        let last_expression = if body.kind() == NodeKind::BodyStatement {
+            body.named_child(body.named_child_count().checked_sub(1)?)?
+        } else {
+            body
+        };
+
+        if last_expression.kind() != NodeKind::InstanceVariable {
+            return self.infer_types(&last_expression, method.file(), source, depth);
+        }
+
+        // instance variables are looked up in the class body the method is defined in
+        let class_node = iter::successors(method_node.parent(), |p| p.parent())
+            .find(|p| matches!(p.kind().try_into(), Ok(NodeKind::Class | NodeKind::Module)))?;
+        let variable_name = last_expression.utf8_text(source).ok()?;
+        let query = INSTANCE_VARIABLE_ASSIGNMENTS.get_or_init(|| {
+            Query::new(
+                tree_sitter_ruby::language(),
+                r#"(assignment left: (instance_variable) @variable right: (_) @value)"#,
+            )
+            .unwrap()
+        });
+        let variable_index = query.capture_index_for_name("variable")?;
+        let value_index = query.capture_index_for_name("value")?;
+
+        let types = QueryCursor::new()
+            .matches(query, class_node, &source[..])
+            .filter(|m| {
+                m.captures
+                    .iter()
+                    .any(|c| c.index == variable_index && c.node.utf8_text(source).ok() == Some(variable_name))
+            })
+            .flat_map(|m| m.captures.iter().filter(|c| c.index == value_index).map(|c| c.node).collect::<Vec<_>>())
+            .flat_map(|value| self.infer_types(&value, method.file(), source, depth))
+            .flatten()
+            .unique_by(|t| (t.is_singleton(), Arc::as_ptr(t.symbol())))
+            .collect();
+
+        Some(types)
+    }
+
+    /*
+     * Orders symbols by proximity to the file: the same file first, then the project, then gems and stubs.
+     */
+    fn sort_by_proximity(&self, file: &Path, symbols: &mut [Arc<RSymbol>]) {
+        let index = self.symbols.load();
+        symbols.sort_by_key(|s| (s.file() != file, Self::source_priority(&index, s)));
+    }
+
+    fn source_priority(index: &RSymbolIndex, symbol: &RSymbol) -> u8 {
+        index.source(symbol.file()).unwrap_or(SourceKind::Project).priority()
+    }
+
+    /*
+     * Finds definitions of the operator method called with the receiver, e.g. `+` in `a + b`.
+     */
+    fn find_operator_method(
+        &self,
+        method_name: &str,
+        receiver: Node,
+        file: &Path,
+        source: &[u8],
+    ) -> Result<Vec<Arc<RSymbol>>> {
+        info!("Trying to find operator method: {method_name}, receiver kind = {}", receiver.kind());
+
+        let receiver_kind = receiver.kind().try_into().ok();
+        let mut methods = match receiver_kind {
+            Some(NodeKind::Constant) | Some(NodeKind::ScopeResolution) => {
+                return self.find_method_definition(method_name, file, source, Some(receiver), &receiver)
+            }
+            Some(NodeKind::SelfNode) => {
+                let receiver_definitions = self.find_enclosing_classes(&receiver, source);
+                self.find_methods_of_types(method_name, &receiver_definitions)
+            }
+            // operators of included modules are found in the ancestors, e.g. `<` of Comparable
+            Some(NodeKind::Identifier | NodeKind::Call) => {
+                let types = self.infer_types(&receiver, file, source, MAX_INFERENCE_DEPTH).unwrap_or_default();
+                match self.find_methods_of_inferred_types(method_name, &types) {
+                    methods if methods.is_empty() => self.find_instance_methods(method_name, None),
+                    methods => methods,
+                }
+            }
+            // the type of other receivers is unknown, so any definition of the operator can be called
+            _ => self.find_instance_methods(method_name, None),
+        };
+        self.sort_by_proximity(file, &mut methods);
+
+        Ok(methods)
+    }
+
+    /*
+     * Maps the operator call at the node to the called method name and its receiver.
+     */
+    fn get_operator_call<'b>(node: &Node<'b>, source: &[u8]) -> Option<(String, Node<'b>)> {
+        // the cursor is either on the operator token or between the operands
+        let call = if node.is_named() { *node } else { node.parent()? };
+        let call_kind: NodeKind = call.kind().try_into().ok()?;
+
+        match call_kind {
+            NodeKind::Binary | NodeKind::Unary => {
+                let operator_node = call.child_by_field_name(NodeName::Operator)?;
+                if !node.is_named() && *node != operator_node {
+                    return None;
+                }
+
+                let operator = operator_node.utf8_text(source).ok()?;
+                let (method_name, receiver) = if call_kind == NodeKind::Binary {
+                    (operator.to_string(), call.child_by_field_name(NodeName::Left)?)
+                } else {
+                    (Self::unary_method_name(operator)?.to_string(), call.child_by_field_name(NodeName::Operand)?)
+                };
+
+                if NON_METHOD_OPERATORS.contains(&method_name.as_str()) {
+                    return None;
+                }
+
+                Some((method_name, receiver))
+            }
+            NodeKind::ElementReference => {
+                let receiver = call.child_by_field_name(NodeName::Object)?;
+                let is_assigned = call
+                    .parent()
+                    .filter(|p| p.kind() == NodeKind::Assignment)
+                    .and_then(|p| p.child_by_field_name(NodeName::Left))
+                    .map(|l| l == call)
+                    .unwrap_or(false);
+                let method_name = if is_assigned { "[]=" } else { "[]" };
+
+                Some((method_name.to_string(), receiver))
+            }
+            _ => None,
+        }
+    }
+
+    fn unary_method_name(operator: &str) -> Option<&'static str> {
+        match operator {
+            "-" => Some("-@"),
+            "+" => Some("+@"),
+            "!" => Some("!"),
+            "~" => Some("~"),
+            _ => None,
+        }
+    }
+
+    fn find_instance_methods(
+        &self,
+        method_name: &str,
+        receiver_definitions: Option<&Vec<Arc<RSymbol>>>,
+    ) -> Vec<Arc<RSymbol>> {
+        self.find_methods(method_name, receiver_definitions, |s| matches!(s, RSymbol::Method(_)))
+    }
+
+    fn find_singleton_methods(
+        &self,
+        method_name: &str,
+        receiver_definitions: Option<&Vec<Arc<RSymbol>>>,
+    ) -> Vec<Arc<RSymbol>> {
+        // TODO: depends on the type of receiver, change after adding more definition types
+        self.find_methods(method_name, receiver_definitions, |s| matches!(s, RSymbol::SingletonMethod(_)))
+    }
+
+    fn find_methods(
+        &self,
+        method_name: &str,
+        receiver_definitions: Option<&Vec<Arc<RSymbol>>>,
+        is_method: impl Fn(&RSymbol) -> bool,
+    ) -> Vec<Arc<RSymbol>> {
+        self.symbols
+            .load()
+            .iter()
+            .filter(|s| is_method(s))
+            .filter(|s| {
+                let defs = if let Some(rd) = receiver_definitions { rd } else { return true };
+                let parent = if let Some(p) = s.parent() { p } else { return true };
+
+                defs.contains(parent)
+            })
+            .filter(|s| s.full_scope().last().map(|l| l == method_name).unwrap_or(false))
+            .cloned()
+            .collect()
+    }
+
+    /*
+     * Checks if the node is a `self.class` call.
+     */
+    fn is_self_class(node: &Node, source: &[u8]) -> bool {
+        if node.kind() != NodeKind::Call {
+            return false;
+        }
+
+        let receiver = node.child_by_field_name(NodeName::Receiver);
+        let method = node.child_by_field_name(NodeName::Method);
+
+        receiver.map(|r| r.kind() == NodeKind::SelfNode).unwrap_or(false)
+            && method.and_then(|m| m.utf8_text(source).ok()) == Some("class")
+    }
+
+    /*
+     * Gets the literal method name of a dynamic call and the call, e.g. "process" in `send(:process)`
+     * for both the argument and the `send` itself.
+     */
+    fn get_sent_method_name<'b, 'c>(node: &Node<'c>, source: &'b [u8]) -> Option<(&'b str, Node<'c>)> {
+        let argument = if node.kind() == NodeKind::StringContent { node.parent()? } else { *node };
+        let (call, is_method) = match argument.parent() {
+            Some(p) if p.kind() == NodeKind::ArgumentList => (p.parent()?, false),
+            Some(p) if p.child_by_field_name(NodeName::Method) == Some(*node) => (p, true),
+            _ => return None,
+        };
+        if call.kind() != NodeKind::Call {
+            return None;
+        }
+
+        let method = call.child_by_field_name(NodeName::Method)?.utf8_text(source).ok()?;
+        let name_node = call.child_by_field_name(NodeName::Arguments)?.named_child(0)?;
+        if !SEND_METHODS.contains(&method) || (!is_method && name_node != argument) {
+            return None;
+        }
+
+        Some((get_literal_key(&name_node, source)?, call))
+    }
+
+    /*
+     * Gets the method name of an alias argument, e.g. "a" or "b" in "alias_method :b, :a" or "alias b a".
+     */
+    fn get_aliased_method_name<'b>(node: &Node, source: &'b [u8]) -> Option<&'b str> {
+        let argument = if node.kind() == NodeKind::StringContent { node.parent()? } else { *node };
+        let parent = argument.parent()?;
+
+        match parent.kind().try_into().ok()? {
+            NodeKind::Alias => get_alias_keyword_name(&argument, source),
+
+            NodeKind::ArgumentList => {
+                let call = parent.parent().filter(|c| c.kind() == NodeKind::Call)?;
+                let method = call.child_by_field_name(NodeName::Method)?.utf8_text(source).ok()?;
+                if method != "alias_method" || call.child_by_field_name(NodeName::Receiver).is_some() {
+                    return None;
+                }
+
+                get_literal_key(&argument, source)
+            }
+
+            _ => None,
+        }
+    }
+
+    /*
+     * Finds the method named in an alias, both the original method and the alias are methods of the class.
+     */
+    fn find_alias_method(&self, method_name: &str, node: &Node, source: &[u8]) -> Vec<Arc<RSymbol>> {
+        info!("Trying to find aliased method: {method_name}");
+
+        let methods = self.find_methods_of_enclosing_classes(method_name, node, source);
+        if methods.is_empty() {
+            self.find_instance_methods(method_name, None)
+        } else {
+            methods
+        }
+    }
+
+    /*
+     * Finds the methods of the classes enclosing the node, including the inherited ones.
+     */
+    fn find_methods_of_enclosing_classes(&self, method_name: &str, node: &Node, source: &[u8]) -> Vec<Arc<RSymbol>> {
+        self.find_enclosing_classes(node, source)
+            .iter()
+            .flat_map(|c| self.methods_of(c))
+            .filter(|m| m.full_scope().last() == Some(method_name))
+            .collect()
+    }
+
+    /*
+     * Checks if the closest definition enclosing the node is an instance method, e.g. not `def self.build`
+     * or the class body.
+     */
+    fn is_in_instance_method(node: &Node) -> bool {
+        iter::successors(node.parent(), |p| p.parent())
+            .find(|p| {
+                matches!(
+                    p.kind().try_into(),
+                    Ok(NodeKind::Method | NodeKind::SingletonMethod | NodeKind::Class | NodeKind::Module)
+                )
+            })
+            .map(|p| p.kind() == NodeKind::Method)
+            .unwrap_or(false)
+    }
+
+    fn find_enclosing_classes(&self, node: &Node, source: &[u8]) -> Vec<Arc<RSymbol>> {
+        let context_scope = get_context_scope(node, source);
+
+        self.symbols
+            .load()
+            .iter()
+            .filter(|s| matches!(***s, RSymbol::Class(_) | RSymbol::Module(_)))
+            .filter(|s| s.full_scope() == &context_scope)
+            .cloned()
+            .collect()
+    }
+
+    fn find_global_variable(&self, node: &Node, source: &[u8]) -> Result<Vec<Arc<RSymbol>>> {
+        info!("Trying to find a global variable");
+
+        let node_kind: NodeKind = node.kind().try_into()?;
+        if node_kind != NodeKind::GlobalVariable {
+            bail!("Node kind is not global variable")
+        }
+
+        let name = node.utf8_text(source)?;
+
+        Ok(self
+            .symbols
+            .load()
+            .iter()
+            .filter(|s| matches!(***s, RSymbol::GlobalVariable(_) if s.name() == name))
+            .cloned()
+            .collect())
+    }
+
+    /*
+     * Checks if the symbol can be found by the global search for constants, depending on the configuration.
+     */
+    fn is_global_fallback_symbol(&self, symbol: &RSymbol) -> bool {
+        match self.config.global_fallback {
+            GlobalFallback::All => true,
+            GlobalFallback::Project => symbol.file().starts_with(&self.root_dir),
+            GlobalFallback::Disabled => false,
+        }
+    }
+
+    fn find_constant(&self, node: &Node, file: &Path, source: &[u8]) -> Vec<Arc<RSymbol>> {
+        info!("Trying to find a constant");
+        // traverse down till we hit the whole symbol name
+        let constant_scope = get_parent_scope_resolution(node, source);
+        if constant_scope.is_empty() {
+            info!("Constant scope can't be determined");
+            return vec![];
+        }
+
+        let context = get_context_scope(node, source);
+        let context_scope = context.join(&constant_scope);
+
+        let mut file_scope = self.ruby_filename_converter.path_to_scope(file).unwrap_or(Scope::new(vec![]));
+        file_scope.remove_last();
+        // for files at the top of the autoload paths it's a global search, so it's limited like the fallback below
+        let is_file_scope_global = file_scope.is_empty();
+        let file_scope = file_scope.join(&constant_scope);
+
+        let all_symbols = self.symbols.load();
+        let symbols = all_symbols
+            .iter()
+            .filter(|s| matches!(***s, RSymbol::Class(_) | RSymbol::Module(_) | RSymbol::Constant(_)));
+
+        // reopened classes are found in every file, e.g. a monkey-patch of String has to come before its stub
+        let mut results = if constant_scope.is_global() {
+            info!("Global scope, searching for {constant_scope}");
+            let global_scope = Scope::default().join(&constant_scope);
+            symbols.filter(|s| s.full_scope() == &global_scope).cloned().collect()
+        } else {
+            info!("Searching for {context_scope} or {file_scope} or {constant_scope} in the same file");
+            // search in contexts first
+            let found_symbols: Vec<Arc<RSymbol>> = symbols
+                .clone()
+                .filter(|s| {
+                    let name = s.full_scope();
+                    name == &context_scope
+                        // the acronyms of the file name are inflected by the converter, e.g. html_parser.rb is HTMLParser
+                        || (name == &file_scope && (!is_file_scope_global || self.is_global_fallback_symbol(s)))
+                        || (name == &constant_scope && s.file() == file)
+                })
+                .cloned()
+                .collect();
+
+            // then the outer lexical scopes from the innermost, e.g. A::B::C then A::C for C in "module A; class B"
+            let found_symbols = if found_symbols.is_empty() {
+                (1..context.len())
+                    .rev()
+                    .map(|n| context.prefix(n).join(&constant_scope))
+                    .map(|scope| symbols.clone().filter(|s| s.full_scope() == &scope).cloned().collect::<Vec<_>>())
+                    .find(|found| !found.is_empty())
+                    .unwrap_or_default()
+            } else {
+                found_symbols
+            };
+
+            // then global, and then nested in any namespace when it's enabled
+            if found_symbols.is_empty() {
+                info!(
+                    "Haven't found anything, searching for global {constant_scope} in {:?}",
+                    self.config.global_fallback
+                );
+                let fallback_symbols = symbols.clone().filter(|s| self.is_global_fallback_symbol(s));
+                let global_symbols: Vec<Arc<RSymbol>> =
+                    fallback_symbols.clone().filter(|s| s.full_scope() == &constant_scope).cloned().collect();
+
+                if global_symbols.is_empty() && self.config.nested_constant_fallback {
+                    let mut nested_symbols: Vec<Arc<RSymbol>> =
+                        fallback_symbols.filter(|s| s.full_scope().ends_with(&constant_scope)).cloned().collect();
+                    // the closest namespace to the reference first, e.g. Shipping::Settings::Config in Shipping::Rates
+                    nested_symbols.sort_by_key(|s| {
+                        (Reverse(s.full_scope().common_prefix_len(&context)), Self::source_priority(&all_symbols, s))
+                    });
+
+                    return nested_symbols;
+                } else {
+                    global_symbols
+                }
+            } else {
+                found_symbols
+            }
+        };
+
+        results.sort_by_key(|s| Self::source_priority(&all_symbols, s));
+        debug!("Found {} results", results.len());
+
+        results
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs;
+
+    use arc_swap::ArcSwap;
+    use walkdir::WalkDir;
+
+    use crate::{documents::Document, indexer::Indexer};
+
+    use super::*;
+
+    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    pub fn fixture_finder(name: &str) -> (PathBuf, Finder) {
+        fixture_finder_with_root(name, "")
+    }
+
+    /*
+     * Indexes the whole fixture but uses its subdirectory as the project root, the rest of the
+     * fixture is treated as gems and stubs.
+     */
+    pub fn fixture_finder_with_root(name: &str, root_dir: &str) -> (PathBuf, Finder) {
+        fixture_finder_with_config(name, root_dir, Config::default())
+    }
+
+    pub fn fixture_finder_with_config(name: &str, root_dir: &str, config: Config) -> (PathBuf, Finder) {
+        let fixture_dir = Path::new(FIXTURES_DIR).join(name);
+        let root = fixture_dir.join(root_dir);
+        let finder = empty_finder(&root, config);
+        finder.publish_file_indexes(index_fixture_files(&fixture_dir));
+
+        (root, finder)
+    }
+
+    /*
+     * Creates the finder without any symbols, the indexed files are published to it.
+     */
+    pub fn empty_finder(root: &Path, config: Config) -> Finder {
+        let ruby_filename_converter = Rc::new(
+            RubyFilenameConverter::with_autoload_paths(root, vec![])
+                .with_acronyms(&config.acronyms)
+                .with_autoload_roots(&config.autoload_roots),
+        );
+        let symbols = Arc::new(ArcSwap::from_pointee(RSymbolIndex::default()));
+
+        Finder::new(root, Rc::new(config), symbols, ruby_filename_converter)
+    }
+
+    /*
+     * Temporary project directory for the tests changing the files, it's removed when dropped even if the test panics.
+     */
+    pub struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("rust-ruby-ls-{name}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /*
+     * Creates the finder of a temporary project with a single user.rb file of the source.
+     */
+    pub fn temp_finder(name: &str, source: &str) -> (TempDir, PathBuf, Finder) {
+        let root = TempDir::new(name);
+        let file = root.path().join("user.rb");
+        fs::write(&file, source).unwrap();
+
+        let finder = empty_finder(root.path(), Config::default());
+        finder.publish_file_index(Indexer::index_file(file.clone(), &Config::default()).unwrap());
+
+        (root, file, finder)
+    }
+
+    fn index_fixture_files(fixture_dir: &Path) -> Vec<FileIndex> {
+        WalkDir::new(fixture_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .map(|e| Indexer::index_file(e.into_path(), &Config::default()).unwrap())
+            .collect()
+    }
+
+    pub fn assert_definitions(finder: &Finder, file: &Path, point: Point, expected: &[(&str, &str)]) {
+        let actual: Vec<(String, String)> = finder
+            .find_definition(file, point)
+            .unwrap()
+            .iter()
+            .map(|s| (s.kind().to_string(), s.name().to_string()))
+            .collect();
+        let expected: Vec<(String, String)> = expected.iter().map(|(k, n)| (k.to_string(), n.to_string())).collect();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[cfg(test)]
+    mod find_constant_tests {
+        use super::*;
+
+        #[test]
+        fn include_argument_test() {
+            let (root, finder) = fixture_finder("include_extend");
+            let file = root.join("post.rb");
+
+            for column in [12, 22] {
+                let point = Point {
+                    row: 1,
+                    column,
+                };
+                let expected = if column == 12 { "Concerns" } else { "Concerns::Trackable" };
+
+                assert_definitions(&finder, &file, point, &[("module", expected)]);
+            }
+        }
+
+        #[test]
+        fn extend_argument_test() {
+            let (root, finder) = fixture_finder("include_extend");
+            let point = Point {
+                row: 2,
+                column: 10,
+            };
+
+            assert_definitions(&finder, &root.join("post.rb"), point, &[("module", "Searchable")]);
+        }
+
+        #[test]
+        fn top_level_include_argument_test() {
+            let (root, finder) = fixture_finder("include_extend");
+            let file = root.join("post.rb");
+            let point = Point {
+                row: 3,
+                column: 24,
+            };
+
+            assert_definitions(&finder, &file, point, &[("module", "Concerns::Trackable")]);
+
+            let point = Point {
+                row: 4,
+                column: 11,
+            };
+
+            assert_definitions(&finder, &file, point, &[("module", "Searchable")]);
+        }
+
+        #[test]
+        fn object_prefix_test() {
+            let (root, finder) = fixture_finder("object_prefix");
+            let file = root.join("nested.rb");
+
+            let point = Point {
+                row: 5,
+                column: 16,
+            };
+            assert_definitions(&finder, &file, point, &[("class", "Foo")]);
+
+            let point = Point {
+                row: 6,
+                column: 18,
+            };
+            assert_definitions(&finder, &file, point, &[("class", "Foo")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod compact_class_tests {
+        use super::*;
+
+        #[test]
+        fn method_scope_test() {
+            let (_, finder) = fixture_finder("compact_class");
+
+            let scopes: Vec<String> = finder
+                .symbols
+                .load()
+                .iter()
+                .filter(|s| matches!(***s, RSymbol::Method(_) | RSymbol::SingletonMethod(_)))
+                .map(|s| s.full_scope().to_string())
+                .sorted()
+                .collect();
+
+            assert_eq!(vec!["Caller::call", "Foo::Bar::baz", "Foo::Bar::qux"], scopes);
+        }
+
+        #[test]
+        fn namespace_across_files_test() {
+            let (root, finder) = fixture_finder("compact_class");
+            let point = Point {
+                row: 0,
+                column: 6,
+            };
+
+            assert_definitions(&finder, &root.join("foo/bar.rb"), point, &[("class", "Foo")]);
+        }
+
+        #[test]
+        fn singleton_method_across_files_test() {
+            let (root, finder) = fixture_finder("compact_class");
+            let point = Point {
+                row: 2,
+                column: 14,
+            };
+
+            assert_definitions(&finder, &root.join("caller.rb"), point, &[("singleton_method", "Foo::Bar::qux")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod find_method_definition_tests {
+        use super::*;
+
+        #[test]
+        fn self_class_receiver_test() {
+            let (root, finder) = fixture_finder("self_class");
+            let point = Point {
+                row: 4,
+                column: 15,
+            };
+
+            assert_definitions(&finder, &root.join("user.rb"), point, &[("singleton_method", "User::create")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod prepend_tests {
+        use super::*;
+
+        #[test]
+        fn prepended_method_first_test() {
+            let (root, finder) = fixture_finder("prepend");
+            let file = root.join("record.rb");
+            // the prepended method overrides the one of the class, which is reachable with `super` only
+            let expected = [("method", "Auditing::save")];
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 12,
+                    column: 4,
+                },
+                &expected,
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 18,
+                    column: 9,
+                },
+                &expected,
+            );
+        }
+
+        #[test]
+        fn prepended_supertype_test() {
+            let (root, finder) = fixture_finder("prepend");
+            let symbols = finder.find_by_path(&root.join("record.rb"));
+            let record = symbols.iter().find(|s| s.name() == "Record").unwrap();
+
+            let supertypes = finder.find_supertypes(record);
+            assert_eq!(vec!["Auditing"], supertypes.iter().map(|s| s.name()).collect::<Vec<_>>());
+        }
+    }
+
+    #[cfg(test)]
+    mod closest_scope_tests {
+        use super::*;
+
+        #[test]
+        fn closest_namespace_first_test() {
+            let config = Config {
+                nested_constant_fallback: true,
+                ..Default::default()
+            };
+            let (root, finder) = fixture_finder_with_config("closest_scope", "", config);
+
+            assert_definitions(
+                &finder,
+                &root.join("shipping/calculator.rb"),
+                Point {
+                    row: 3,
+                    column: 6,
+                },
+                &[("constant", "Shipping::Settings::Config"), ("constant", "Billing::Settings::Config")],
+            );
+            assert_definitions(
+                &finder,
+                &root.join("billing/invoice.rb"),
+                Point {
+                    row: 3,
+                    column: 6,
+                },
+                &[("constant", "Billing::Settings::Config"), ("constant", "Shipping::Settings::Config")],
+            );
+        }
+
+        #[test]
+        fn nested_fallback_disabled_test() {
+            let (root, finder) = fixture_finder("closest_scope");
+
+            assert_definitions(
+                &finder,
+                &root.join("billing/invoice.rb"),
+                Point {
+                    row: 3,
+                    column: 6,
+                },
+                &[],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod refinements_tests {
+        use super::*;
+
+        #[test]
+        fn refined_class_methods_test() {
+            let (root, finder) = fixture_finder("refinements");
+            let symbols = finder.find_by_path(&root.join("string_ext.rb"));
+
+            let string = symbols.iter().find(|s| s.name() == "String").unwrap();
+            let methods = finder.methods_of(string);
+            assert_eq!(vec!["String::whisper"], methods.iter().map(|m| m.name()).collect::<Vec<_>>());
+
+            let module = symbols.iter().find(|s| s.name() == "StringExt").unwrap();
+            assert!(finder.methods_of(module).is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod max_definitions_tests {
+        use super::*;
+
+        fn names(symbols: &[Arc<RSymbol>]) -> Vec<&str> {
+            symbols.iter().map(|s| s.name()).collect()
+        }
+
+        #[test]
+        fn limit_definitions_test() {
+            let config = Config {
+                max_definitions: 2,
+                ..Default::default()
+            };
+            let (root, finder) = fixture_finder_with_config("max_definitions", "project", config);
+            let file = root.join("app.rb");
+
+            let symbols = finder
+                .find_definition(
+                    &file,
+                    Point {
+                        row: 4,
+                        column: 19,
+                    },
+                )
+                .unwrap();
+            assert_eq!(5, symbols.len());
+
+            let symbols = finder.limit_definitions(&file, symbols);
+            assert_eq!(vec!["App::call", "Job::call"], names(&symbols));
+        }
+
+        #[test]
+        fn default_limit_test() {
+            let (root, finder) = fixture_finder_with_root("max_definitions", "project");
+            let file = root.join("app.rb");
+
+            let symbols = finder
+                .find_definition(
+                    &file,
+                    Point {
+                        row: 4,
+                        column: 19,
+                    },
+                )
+                .unwrap();
+            let symbols = finder.limit_definitions(&file, symbols);
+
+            assert_eq!(
+                vec!["App::call", "Job::call", "Rack::Builder::call", "Rack::Static::call", "Rack::Lint::call"],
+                names(&symbols)
+            );
+        }
+
+        #[test]
+        fn unlimited_test() {
+            let config = Config {
+                max_definitions: 0,
+                ..Default::default()
+            };
+            let (root, finder) = fixture_finder_with_config("max_definitions", "project", config);
+            let file = root.join("app.rb");
+
+            let symbols = finder
+                .find_definition(
+                    &file,
+                    Point {
+                        row: 4,
+                        column: 19,
+                    },
+                )
+                .unwrap();
+            let symbols = finder.limit_definitions(&file, symbols);
+
+            assert_eq!(5, symbols.len());
+        }
+    }
+
+    #[cfg(test)]
+    mod kernel_tests {
+        use super::*;
+
+        #[test]
+        fn kernel_method_test() {
+            let (root, finder) = fixture_finder("kernel");
+            let file = root.join("greeter.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 4,
+                },
+                &[("method", "Kernel::puts")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 7,
+                    column: 4,
+                },
+                &[("method", "Kernel::sleep")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 11,
+                    column: 0,
+                },
+                &[("method", "Kernel::puts")],
+            );
+        }
+
+        #[test]
+        fn object_method_test() {
+            let (root, finder) = fixture_finder("kernel");
+            let file = root.join("greeter.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 3,
+                    column: 4,
+                },
+                &[("method", "Object::freeze")],
+            );
+        }
+
+        #[test]
+        fn conversion_method_test() {
+            let (root, finder) = fixture_finder("kernel");
+            let file = root.join("converter.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 4,
+                },
+                &[("method", "Kernel::Array")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 4,
+                },
+                &[("class", "Array")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod dynamic_scopes_tests {
+        use super::*;
+
+        #[test]
+        fn call_scope_test() {
+            let (root, finder) = fixture_finder("dynamic_scopes");
+            let file = root.join("limits.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 4,
+                    column: 16,
+                },
+                &[],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 8,
+                    column: 14,
+                },
+                &[],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod variable_qualified_constants_tests {
+        use super::*;
+
+        #[test]
+        fn variable_class_constant_test() {
+            let (root, finder) = fixture_finder("variable_qualified_constants");
+            let file = root.join("serializers.rb");
+
+            let point = Point {
+                row: 16,
+                column: 9,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Serializers::User::FIELDS")]);
+
+            let point = Point {
+                row: 18,
+                column: 9,
+            };
+            assert_definitions(&finder, &file, point, &[("class", "Serializers::User::Options")]);
+        }
+
+        #[test]
+        fn inherited_constant_test() {
+            let (root, finder) = fixture_finder("variable_qualified_constants");
+            let file = root.join("serializers.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 17,
+                    column: 9,
+                },
+                &[("constant", "BaseSerializer::VERSION")],
+            );
+        }
+
+        #[test]
+        fn unknown_variable_test() {
+            let (root, finder) = fixture_finder("variable_qualified_constants");
+            let file = root.join("serializers.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 19,
+                    column: 10,
+                },
+                &[],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod erb_tests {
+        use super::*;
+
+        #[test]
+        fn erb_constant_test() {
+            let (root, finder) = fixture_finder("erb");
+            let file = root.join("app/views/users/show.html.erb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 1,
+                    column: 20,
+                },
+                &[("class", "UserPresenter")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 22,
+                },
+                &[("method", "UserPresenter::display_name")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod fluent_tests {
+        use super::*;
+
+        #[test]
+        fn self_returning_chain_test() {
+            let (root, finder) = fixture_finder("fluent");
+            let file = root.join("query_builder.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 16,
+                    column: 32,
+                },
+                &[("method", "QueryBuilder::order")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 16,
+                    column: 43,
+                },
+                &[("method", "QueryBuilder::to_sql")],
+            );
+        }
+
+        #[test]
+        fn singleton_self_chain_test() {
+            let (root, finder) = fixture_finder("fluent");
+            let file = root.join("invoice.rb");
+            let point = |row, column| Point {
+                row,
+                column,
+            };
+
+            // `self` in a class method is the class, and `new` in it is an instance
+            assert_definitions(&finder, &file, point(22, 18), &[("singleton_method", "Invoice::build")]);
+            assert_definitions(&finder, &file, point(22, 24), &[("method", "Invoice::total")]);
+            assert_definitions(&finder, &file, point(10, 10), &[("singleton_method", "Invoice::build")]);
+            assert_definitions(&finder, &file, point(10, 16), &[("method", "Invoice::total")]);
+            assert_definitions(&finder, &file, point(14, 15), &[("method", "Invoice::total")]);
+        }
+
+        #[test]
+        fn opened_document_return_type_test() {
+            let source = "class Report\n  def build\n    self\n  end\n\n  def render; end\nend\n\nclass Draft\n  def render; end\nend\n\nReport.new.build.render\n";
+            let (_root, file, finder) = temp_finder("opened_document_return_type", source);
+            let documents = Documents::default();
+            let finder = finder.with_documents(documents.clone());
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 12,
+                    column: 18,
+                },
+                &[("method", "Report::render")],
+            );
+
+            // the unsaved changes of the opened document are used instead of the file on disk
+            let document = Document::new(source.replace("    self\n", "    Draft.new\n")).unwrap();
+            documents.borrow_mut().insert(file.clone(), document);
+            finder.invalidate_definitions(&file);
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 12,
+                    column: 18,
+                },
+                &[("method", "Draft::render")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod chained_calls_tests {
+        use super::*;
+
+        #[test]
+        fn class_method_chain_test() {
+            let (root, finder) = fixture_finder("chained_calls");
+            let file = root.join("current.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 14,
+                    column: 20,
+                },
+                &[("method", "Profile::name")],
+            );
+
+            // the class method returns nothing known, any method with the name is a candidate
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 18,
+                    column: 20,
+                },
+                &[("method", "Session::expires_at")],
+            );
+        }
+
+        #[test]
+        fn local_variable_chain_test() {
+            let (root, finder) = fixture_finder("chained_calls");
+            let file = root.join("bank.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 33,
+                    column: 12,
+                },
+                &[("method", "User::account")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 33,
+                    column: 20,
+                },
+                &[("method", "Account::balance")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 33,
+                    column: 28,
+                },
+                &[("method", "Balance::amount")],
+            );
+        }
+
+        #[test]
+        fn implicit_self_chain_test() {
+            let (root, finder) = fixture_finder("chained_calls");
+            let file = root.join("bank.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 20,
+                    column: 12,
+                },
+                &[("method", "Account::balance")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 20,
+                    column: 20,
+                },
+                &[("method", "Balance::amount")],
+            );
+        }
+
+        #[test]
+        fn unknown_receiver_fallback_test() {
+            let (root, finder) = fixture_finder("chained_calls");
+            let file = root.join("bank.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 34,
+                    column: 17,
+                },
+                &[("method", "Account::balance"), ("method", "Ledger::balance")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod heredoc_tests {
+        use super::*;
+
+        #[test]
+        fn heredoc_content_symbols_test() {
+            let (root, finder) = fixture_finder("heredoc");
+
+            let symbols = finder.find_by_path(&root.join("report.rb"));
+            let names: Vec<&str> = symbols.iter().map(|s| s.name()).sorted().collect();
+
+            assert_eq!(vec!["Report", "Report::QUERY", "Report::title"], names);
+        }
+
+        #[test]
+        fn heredoc_interpolation_constant_test() {
+            let (root, finder) = fixture_finder("heredoc");
+            let point = Point {
+                row: 4,
+                column: 48,
+            };
+
+            assert_definitions(&finder, &root.join("report.rb"), point, &[("constant", "Formatter::ID")]);
+        }
+
+        #[test]
+        fn string_interpolation_test() {
+            let (root, finder) = fixture_finder("heredoc");
+            let file = root.join("report.rb");
+            let point = Point {
+                row: 8,
+                column: 7,
+            };
+
+            assert_definitions(&finder, &file, point, &[("class", "Formatter")]);
+
+            let point = Point {
+                row: 8,
+                column: 17,
+            };
+
+            assert_definitions(&finder, &file, point, &[("singleton_method", "Formatter::bold")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod type_hierarchy_tests {
+        use super::*;
+
+        fn names(symbols: &[Arc<RSymbol>]) -> Vec<&str> {
+            symbols.iter().map(|s| s.name()).sorted().collect()
+        }
+
+        fn find_class(finder: &Finder, name: &str) -> Arc<RSymbol> {
+            finder.symbols.load().iter().find(|s| s.name() == name).unwrap().clone()
+        }
+
+        #[test]
+        fn find_types_test() {
+            let (root, finder) = fixture_finder("type_hierarchy");
+            let point = Point {
+                row: 5,
+                column: 16,
+            };
+
+            let types = finder.find_types(&root.join("models/user.rb"), point).unwrap();
+
+            assert_eq!(vec!["Models::User"], names(&types));
+        }
+
+        #[test]
+        fn find_supertypes_test() {
+            let (_, finder) = fixture_finder("type_hierarchy");
+
+            let supertypes = finder.find_supertypes(&find_class(&finder, "Models::User"));
+            assert_eq!(vec!["Auditable", "Base"], names(&supertypes));
+
+            let supertypes = finder.find_supertypes(&find_class(&finder, "Models::Admin"));
+            assert_eq!(vec!["Models::User"], names(&supertypes));
+
+            let supertypes = finder.find_supertypes(&find_class(&finder, "Base"));
+            assert!(supertypes.is_empty());
+        }
+
+        #[test]
+        fn find_subtypes_test() {
+            let (_, finder) = fixture_finder("type_hierarchy");
+
+            let subtypes = finder.find_subtypes(&find_class(&finder, "Base"));
+            assert_eq!(vec!["Models::User"], names(&subtypes));
+
+            let subtypes = finder.find_subtypes(&find_class(&finder, "Models::User"));
+            assert_eq!(vec!["Models::Admin"], names(&subtypes));
+
+            let subtypes = finder.find_subtypes(&find_class(&finder, "Auditable"));
+            assert_eq!(vec!["Models::User"], names(&subtypes));
+        }
+    }
+
+    #[cfg(test)]
+    mod find_identifier_tests {
+        use super::*;
+
+        fn find_location(finder: &Finder, file: &Path, point: Point) -> Vec<(String, Point)> {
+            let symbols = finder.find_definition(file, point).unwrap();
+
+            symbols.iter().map(|s| (s.name().to_string(), *s.location())).collect()
+        }
+
+        #[test]
+        fn local_variable_assignment_test() {
+            let (root, finder) = fixture_finder("local_variables");
+            let file = root.join("calculator.rb");
+
+            let point = Point {
+                row: 4,
+                column: 4,
+            };
+            let expected_point = Point {
+                row: 3,
+                column: 4,
+            };
+            assert_eq!(vec![("total".to_string(), expected_point)], find_location(&finder, &file, point));
+
+            let point = Point {
+                row: 3,
+                column: 12,
+            };
+            let expected_point = Point {
+                row: 2,
+                column: 4,
+            };
+            assert_eq!(vec![("total".to_string(), expected_point)], find_location(&finder, &file, point));
+        }
+
+        #[test]
+        fn method_param_test() {
+            let (root, finder) = fixture_finder("local_variables");
+            let file = root.join("calculator.rb");
+
+            let point = Point {
+                row: 2,
+                column: 12,
+            };
+            let expected_point = Point {
+                row: 1,
+                column: 10,
+            };
+            assert_eq!(vec![("a".to_string(), expected_point)], find_location(&finder, &file, point));
+
+            let point = Point {
+                row: 3,
+                column: 20,
+            };
+            let expected_point = Point {
+                row: 1,
+                column: 20,
+            };
+            assert_eq!(vec![("c".to_string(), expected_point)], find_location(&finder, &file, point));
+        }
+
+        #[test]
+        fn pseudo_identifiers_test() {
+            let (root, finder) = fixture_finder("local_variables");
+            let file = root.join("tracer.rb");
+
+            let point = Point {
+                row: 2,
+                column: 11,
+            };
+            assert!(find_location(&finder, &file, point).is_empty());
+
+            let point = Point {
+                row: 3,
+                column: 15,
+            };
+            assert!(find_location(&finder, &file, point).is_empty());
+
+            let point = Point {
+                row: 3,
+                column: 24,
+            };
+            assert!(find_location(&finder, &file, point).is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod proximity_tests {
+        use super::*;
+
+        #[test]
+        fn reopened_method_order_test() {
+            let (root, finder) = fixture_finder_with_root("reopened_method", "project");
+            let point = Point {
+                row: 6,
+                column: 8,
+            };
+
+            let files: Vec<PathBuf> = finder
+                .find_definition(&root.join("caller.rb"), point)
+                .unwrap()
+                .iter()
+                .map(|s| s.file().strip_prefix(Path::new(FIXTURES_DIR).join("reopened_method")).unwrap().to_path_buf())
+                .collect();
+
+            let expected_files: Vec<PathBuf> =
+                ["project/caller.rb", "project/lib/foo.rb", "gems/foo.rb"].iter().map(PathBuf::from).collect();
+            assert_eq!(expected_files, files);
+        }
+    }
+
+    #[cfg(test)]
+    mod test_symbols_tests {
+        use super::*;
+
+        fn search(finder: &Finder, query: &str) -> Vec<String> {
+            let matches = finder.fuzzy_find_symbol(query, &CancellationToken::default()).unwrap();
+            matches.iter().map(|m| m.symbol.name().to_string()).sorted().collect()
+        }
+
+        #[test]
+        fn source_kind_test() {
+            let (_, finder) = fixture_finder("test_symbols");
+            let symbols = finder.symbols.load();
+            let kind_of = |name: &str| finder.source_kind(symbols.iter().find(|s| s.name() == name).unwrap());
+
+            assert_eq!(SourceKind::Project, kind_of("User"));
+            assert_eq!(SourceKind::Test, kind_of("FakeUser"));
+            assert_eq!(SourceKind::Test, kind_of("UserHelper"));
+        }
+
+        #[test]
+        fn include_test_symbols_test() {
+            let (_, finder) = fixture_finder("test_symbols");
+
+            assert_eq!(vec!["FakeUser", "User", "UserHelper"], search(&finder, "user"));
+        }
+
+        #[test]
+        fn exclude_test_symbols_test() {
+            let config = Config {
+                exclude_test_symbols: true,
+                ..Default::default()
+            };
+            let (root, finder) = fixture_finder_with_config("test_symbols", "", config);
+
+            assert_eq!(vec!["User"], search(&finder, "user"));
+
+            // goto definition still finds the test symbols
+            let point = Point {
+                row: 1,
+                column: 2,
+            };
+            assert_definitions(&finder, &root.join("spec/user_spec.rb"), point, &[("class", "FakeUser")]);
+        }
+
+        #[test]
+        fn min_query_length_test() {
+            let (_, finder) = fixture_finder("test_symbols");
+
+            assert!(search(&finder, "u").is_empty());
+            assert!(!search(&finder, "us").is_empty());
+        }
+
+        #[test]
+        fn max_symbol_results_test() {
+            let config = Config {
+                max_symbol_results: 1,
+                ..Default::default()
+            };
+            let (_, finder) = fixture_finder_with_config("test_symbols", "", config);
+
+            assert_eq!(vec!["User"], search(&finder, "user"));
+        }
+    }
+
+    #[cfg(test)]
+    mod autoload_tests {
+        use super::*;
+
+        #[test]
+        fn autoload_constant_test() {
+            let (root, finder) = fixture_finder("autoload");
+            let file = root.join("lib/checkout.rb");
+
+            let point = Point {
+                row: 2,
+                column: 10,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Shop::Widget")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod class_new_tests {
+        use super::*;
+
+        #[test]
+        fn inherited_method_test() {
+            let (root, finder) = fixture_finder("class_new");
+            let file = root.join("admin.rb");
+
+            let point = Point {
+                row: 2,
+                column: 4,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "ApplicationRecord::save")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod global_fallback_tests {
+        use super::*;
+
+        fn assert_fallback(global_fallback: GlobalFallback, expected: &[&[(&str, &str)]]) {
+            let config = Config {
+                global_fallback,
+                ..Default::default()
+            };
+            let (root, finder) = fixture_finder_with_config("global_fallback", "project", config);
+            let file = root.join("app.rb");
+
+            let settings = Point {
+                row: 2,
+                column: 4,
+            };
+            assert_definitions(&finder, &file, settings, expected[0]);
+
+            let parser = Point {
+                row: 3,
+                column: 8,
+            };
+            assert_definitions(&finder, &file, parser, expected[1]);
+        }
+
+        #[test]
+        fn all_test() {
+            assert_fallback(GlobalFallback::All, &[&[("class", "Settings")], &[("class", "Oj::Parser")]]);
+        }
+
+        #[test]
+        fn project_test() {
+            assert_fallback(GlobalFallback::Project, &[&[("class", "Settings")], &[]]);
+        }
+
+        #[test]
+        fn disabled_test() {
+            assert_fallback(GlobalFallback::Disabled, &[&[], &[]]);
+        }
+    }
+
+    #[cfg(test)]
+    mod alias_tests {
+        use super::*;
+
+        #[test]
+        fn alias_method_test() {
+            let (root, finder) = fixture_finder("aliases");
+            let file = root.join("account.rb");
+
+            let point = Point {
+                row: 5,
+                column: 35,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Account::balance")]);
+
+            let point = Point {
+                row: 5,
+                column: 17,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Account::current_balance")]);
+
+            let point = Point {
+                row: 7,
+                column: 28,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Account::balance")]);
+        }
+
+        #[test]
+        fn alias_keyword_test() {
+            let (root, finder) = fixture_finder("aliases");
+            let file = root.join("account.rb");
+
+            let point = Point {
+                row: 6,
+                column: 14,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Account::balance")]);
+
+            let point = Point {
+                row: 6,
+                column: 8,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Account::total")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod syntax_errors_tests {
+        use super::*;
+
+        #[test]
+        fn constant_in_error_region_test() {
+            let (root, finder) = fixture_finder("syntax_errors");
+            let file = root.join("greeter.rb");
+
+            let point = Point {
+                row: 4,
+                column: 14,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "GREETING")]);
+
+            let point = Point {
+                row: 9,
+                column: 14,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Farewell::MESSAGE")]);
+        }
+
+        #[test]
+        fn unfinished_call_test() {
+            let (root, finder) = fixture_finder("syntax_errors");
+            let file = root.join("greeter.rb");
+
+            let point = Point {
+                row: 5,
+                column: 14,
+            };
+            assert_definitions(&finder, &file, point, &[("variable", "message")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod default_arguments_tests {
+        use super::*;
+
+        #[test]
+        fn default_argument_constant_test() {
+            let (root, finder) = fixture_finder("default_arguments");
+            let file = root.join("client.rb");
+
+            let point = Point {
+                row: 3,
+                column: 27,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Client::DEFAULT_TIMEOUT")]);
+
+            let point = Point {
+                row: 3,
+                column: 62,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Retry::COUNT")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod reopened_namespaces_tests {
+        use super::*;
+
+        #[test]
+        fn cross_file_constant_test() {
+            let (root, finder) = fixture_finder("reopened_namespaces");
+            let file = root.join("lib/reports/summary.rb");
+
+            let point = Point {
+                row: 3,
+                column: 11,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Foo::BAR")]);
+
+            let point = Point {
+                row: 3,
+                column: 22,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Foo::MAX")]);
+        }
+
+        #[test]
+        fn outer_namespace_constant_test() {
+            let (root, finder) = fixture_finder("reopened_namespaces");
+            let file = root.join("lib/foo/limits.rb");
+
+            let point = Point {
+                row: 5,
+                column: 6,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Foo::MAX")]);
+
+            let point = Point {
+                row: 5,
+                column: 12,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "Foo::BAR")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod bare_calls_tests {
+        use super::*;
+
+        #[test]
+        fn bare_method_call_test() {
+            let (root, finder) = fixture_finder("bare_calls");
+            let file = root.join("document.rb");
+
+            let point = Point {
+                row: 6,
+                column: 4,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Document::reload")]);
+        }
+
+        #[test]
+        fn local_variable_shadows_method_test() {
+            let (root, finder) = fixture_finder("bare_calls");
+            let file = root.join("document.rb");
+
+            let point = Point {
+                row: 8,
+                column: 4,
+            };
+            assert_definitions(&finder, &file, point, &[("variable", "content")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod conditionals_tests {
+        use super::*;
+
+        #[test]
+        fn guarded_constant_test() {
+            let (root, finder) = fixture_finder("conditionals");
+            let file = root.join("platform.rb");
+
+            let point = Point {
+                row: 3,
+                column: 5,
+            };
+            assert_definitions(&finder, &file, point, &[("constant", "WINDOWS")]);
+        }
+
+        #[test]
+        fn guarded_method_test() {
+            let (root, finder) = fixture_finder("conditionals");
+            let file = root.join("platform.rb");
+
+            let point = Point {
+                row: 14,
+                column: 18,
+            };
+            assert_definitions(
+                &finder,
+                &file,
+                point,
+                &[("method", "Platform::separator"), ("method", "Platform::separator")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod included_methods_tests {
+        use super::*;
+
+        #[test]
+        fn methods_of_test() {
+            let (_, finder) = fixture_finder("included_methods");
+            let user = finder.symbols.load().iter().find(|s| s.name() == "User").unwrap().clone();
+
+            let methods: Vec<String> = finder.methods_of(&user).iter().map(|m| m.name().to_string()).collect();
+
+            let expected = vec!["User::save", "Loggable::log", "Loggable::persist", "Trackable::track"];
+            assert_eq!(expected, methods);
+        }
+
+        #[test]
+        fn included_method_test() {
+            let (root, finder) = fixture_finder("included_methods");
+            let file = root.join("app/models/user.rb");
+
+            let point = Point {
+                row: 5,
+                column: 4,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Trackable::track")]);
+
+            let point = Point {
+                row: 6,
+                column: 4,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Loggable::log")]);
+
+            let point = Point {
+                row: 7,
+                column: 9,
+            };
+            // Loggable is included after the superclass, so its method overrides the one of Base
+            assert_definitions(&finder, &file, point, &[("method", "Loggable::persist")]);
+        }
+
+        #[test]
+        fn concerning_methods_test() {
+            let (root, finder) = fixture_finder("concerning");
+            let user = finder.symbols.load().iter().find(|s| s.name() == "User").unwrap().clone();
+
+            let methods: Vec<String> = finder.methods_of(&user).iter().map(|m| m.name().to_string()).collect();
+
+            // the concern defined last is included last, so it comes first in the ancestors
+            let expected = vec!["User::save", "User::Naming::full_name", "User::Trackable::track"];
+            assert_eq!(expected, methods);
+
+            let point = Point {
+                row: 12,
+                column: 4,
+            };
+            assert_definitions(&finder, &root.join("user.rb"), point, &[("method", "User::Trackable::track")]);
+        }
+
+        #[test]
+        fn nested_include_test() {
+            let (root, finder) = fixture_finder("included_methods");
+            let file = root.join("app/models/concerns/trackable.rb");
+
+            let point = Point {
+                row: 4,
+                column: 4,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Loggable::log")]);
+        }
+    }
+
+    #[cfg(test)]
+    mod open_struct_tests {
+        use super::*;
+
+        #[test]
+        fn open_struct_readers_test() {
+            let (root, finder) = fixture_finder("open_struct");
+            let file = root.join("settings.rb");
+
+            let point = Point {
+                row: 10,
+                column: 12,
+            };
+            assert_definitions(&finder, &file, point, &[("singleton_method", "CONFIG::host")]);
+
+            let point = Point {
+                row: 11,
+                column: 14,
+            };
+            assert_definitions(&finder, &file, point, &[("singleton_method", "Client::DEFAULTS::timeout")]);
+
+            let point = Point {
+                row: 12,
+                column: 14,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Client::@options::verbose")]);
+        }
+    }
+
+    mod yard_types_tests {
+        use super::*;
+
+        #[test]
+        fn yard_return_type_test() {
+            let config = Config {
+                yard_types: true,
+                ..Default::default()
+            };
+            let (root, finder) = fixture_finder_with_config("yard", "", config);
+            let file = root.join("users_repository.rb");
+
+            let point = Point {
+                row: 2,
+                column: 30,
+            };
+            assert_definitions(&finder, &file, point, &[("class", "Models::User")]);
+
+            // Models is opened in both files
+            let point = Point {
+                row: 2,
+                column: 22,
+            };
+            assert_definitions(&finder, &file, point, &[("module", "Models"), ("module", "Models")]);
+        }
+
+        #[test]
+        fn yard_types_disabled_test() {
+            let (root, finder) = fixture_finder("yard");
+            let file = root.join("users_repository.rb");
+
+            let point = Point {
+                row: 2,
+                column: 30,
+            };
+            assert_definitions(&finder, &file, point, &[]);
+        }
+    }
+
+    #[cfg(test)]
+    mod literal_constants_tests {
+        use super::*;
+
+        #[test]
+        fn array_literal_test() {
+            let (root, finder) = fixture_finder("literal_constants");
+            let file = root.join("routing.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 4,
+                    column: 13,
+                },
+                &[("class", "FooHandler")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 4,
+                    column: 25,
+                },
+                &[("class", "BarHandler")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 4,
+                    column: 2,
+                },
+                &[("constant", "Routing::ALLOWED")],
+            );
+        }
+
+        #[test]
+        fn hash_literal_test() {
+            let (root, finder) = fixture_finder("literal_constants");
+            let file = root.join("routing.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 5,
+                    column: 20,
+                },
+                &[("class", "FooHandler")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 5,
+                    column: 41,
+                },
+                &[("class", "BarHandler")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 5,
+                    column: 2,
+                },
+                &[("constant", "Routing::HANDLERS")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod send_tests {
+        use super::*;
+
+        #[test]
+        fn send_symbol_test() {
+            let (root, finder) = fixture_finder("send");
+            let file = root.join("worker.rb");
+            let process = [("method", "Worker::process")];
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 15,
+                },
+                &process,
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 10,
+                },
+                &process,
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 3,
+                    column: 17,
+                },
+                &[("method", "Worker::cleanup")],
+            );
+        }
+
+        #[test]
+        fn send_other_argument_test() {
+            let (root, finder) = fixture_finder("send");
+            let point = Point {
+                row: 4,
+                column: 21,
+            };
+
+            assert_definitions(&finder, &root.join("worker.rb"), point, &[]);
+        }
+    }
+
+    #[cfg(test)]
+    mod fqn_tests {
+        use super::*;
+
+        fn find(finder: &Finder, fqn: &str) -> Vec<(String, String)> {
+            finder.find_by_fqn(fqn).iter().map(|s| (s.kind().to_string(), s.name().to_string())).collect()
+        }
+
+        fn symbol(kind: &str, name: &str) -> Vec<(String, String)> {
+            vec![(kind.to_string(), name.to_string())]
+        }
+
+        #[test]
+        fn find_by_fqn_test() {
+            let (_, finder) = fixture_finder("fqn");
+
+            assert_eq!(symbol("class", "ActiveRecord::Base"), find(&finder, "ActiveRecord::Base"));
+            assert_eq!(symbol("module", "ActiveRecord::Persistence"), find(&finder, "ActiveRecord::Persistence"));
+            assert!(find(&finder, "Base").is_empty());
+        }
+
+        #[test]
+        fn find_methods_by_fqn_test() {
+            let (_, finder) = fixture_finder("fqn");
+
+            assert_eq!(symbol("method", "ActiveRecord::Base::save"), find(&finder, "ActiveRecord::Base#save"));
+            assert_eq!(symbol("method", "ActiveRecord::Base::save"), find(&finder, "ActiveRecord::Base::save"));
+            assert_eq!(
+                symbol("singleton_method", "ActiveRecord::Base::create"),
+                find(&finder, "ActiveRecord::Base.create")
+            );
+            assert!(find(&finder, "ActiveRecord::Base#create").is_empty());
+        }
+    }
+
+    mod publish_symbols_tests {
+        use super::*;
+
+        #[test]
+        fn publish_symbols_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("money.rb");
+            let symbols = finder.find_by_path(&file);
+            assert!(!symbols.is_empty());
+
+            let previous = finder.symbols.load_full();
+            finder.publish_symbols(RSymbolIndex::default());
+
+            assert!(finder.find_by_path(&file).is_empty());
+            assert!(previous.iter().any(|s| s.file() == file));
+        }
+
+        #[test]
+        fn publish_file_index_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("money.rb");
+            let document = Document::new(
+                "class Cash
+  def +(other)
+  end
+end
+"
+                .to_string(),
+            )
+            .unwrap();
+
+            finder.publish_file_index(Indexer::index_tree(
+                file.clone(),
+                &document.tree,
+                &document.source,
+                &Config::default(),
+            ));
+
+            let names: Vec<String> = finder.find_by_path(&file).iter().map(|s| s.name().to_string()).collect();
+            assert_eq!(vec!["Cash::+", "Cash"], names);
+            assert!(!finder.find_by_path(&root.join("vector.rb")).is_empty());
+
+            assert_eq!(
+                vec![(
+                    file,
+                    Point {
+                        row: 0,
+                        column: 6
+                    }
+                )],
+                finder.find_reference_candidates("Cash")
+            );
+            assert_eq!(
+                vec![(
+                    root.join("usage.rb"),
+                    Point {
+                        row: 0,
+                        column: 4
+                    }
+                )],
+                finder.find_reference_candidates("Money")
+            );
+        }
+
+        #[test]
+        fn unsaved_document_definition_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("usage.rb");
+            // the constant is on the second line of the document only, the file on disk isn't changed
+            let document = Document::new("\n  Vector\n".to_string()).unwrap();
+            let point = Point {
+                row: 1,
+                column: 2,
+            };
+
+            let symbols = finder.find_definition_in_tree(&file, &document.tree, &document.source, point).unwrap();
+
+            assert_eq!(vec!["Vector"], symbols.iter().map(|s| s.name()).collect::<Vec<_>>());
+        }
+    }
+
+    mod reference_candidates_tests {
+        use super::*;
+
+        #[test]
+        fn find_reference_candidates_test() {
+            let (root, finder) = fixture_finder("operators");
+
+            let expected = vec![
+                (
+                    root.join("money.rb"),
+                    Point {
+                        row: 0,
+                        column: 6,
+                    },
+                ),
+                (
+                    root.join("usage.rb"),
+                    Point {
+                        row: 0,
+                        column: 4,
+                    },
+                ),
+            ];
+            assert_eq!(expected, finder.find_reference_candidates("Money"));
+
+            assert!(finder.find_reference_candidates("Unknown").is_empty());
+        }
+
+        #[test]
+        fn project_reference_candidates_test() {
+            let (root, finder) = fixture_finder_with_root("rename_project", "app");
+
+            // the call in the gem outside of the root isn't kept
+            let expected = vec![(
+                root.join("report.rb"),
+                Point {
+                    row: 2,
+                    column: 16,
+                },
+            )];
+            assert_eq!(expected, finder.find_reference_candidates("total"));
+        }
+    }
+
+    mod operator_method_tests {
+        use super::*;
+
+        #[test]
+        fn binary_operator_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("usage.rb");
+
+            let point = Point {
+                row: 1,
+                column: 6,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Money::+"), ("method", "Vector::+")]);
+
+            let point = Point {
+                row: 4,
+                column: 3,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Money::<=>")]);
+        }
+
+        #[test]
+        fn unary_operator_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("usage.rb");
+
+            let point = Point {
+                row: 5,
+                column: 0,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Money::-@")]);
+        }
+
+        #[test]
+        fn element_reference_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("usage.rb");
+
+            let point = Point {
+                row: 2,
+                column: 1,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Money::[]")]);
+
+            let point = Point {
+                row: 3,
+                column: 1,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Money::[]=")]);
+        }
+
+        #[test]
+        fn constant_receiver_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("usage.rb");
+
+            let point = Point {
+                row: 0,
+                column: 9,
+            };
+            assert_definitions(&finder, &file, point, &[("singleton_method", "Money::[]")]);
+        }
+
+        #[test]
+        fn self_receiver_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("money.rb");
+
+            let point = Point {
+                row: 21,
+                column: 9,
+            };
+            assert_definitions(&finder, &file, point, &[("method", "Money::+")]);
+        }
+
+        #[test]
+        fn non_method_operator_test() {
+            let (root, finder) = fixture_finder("operators");
+            let file = root.join("usage.rb");
+
+            let point = Point {
+                row: 6,
+                column: 2,
+            };
+            assert_definitions(&finder, &file, point, &[]);
+        }
+    }
+
+    mod comparable_tests {
+        use super::*;
+
+        #[test]
+        fn included_operator_test() {
+            let (root, finder) = fixture_finder("comparable");
+            let file = root.join("version.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 17,
+                    column: 4,
+                },
+                &[("method", "Comparable::<")],
+            );
+        }
+
+        #[test]
+        fn self_included_operator_test() {
+            let (root, finder) = fixture_finder("comparable");
+            let file = root.join("version.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 9,
+                },
+                &[("method", "Comparable::>")],
+            );
+        }
+
+        #[test]
+        fn included_method_test() {
+            let (root, finder) = fixture_finder("comparable");
+            let file = root.join("version.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 18,
+                    column: 4,
+                },
+                &[("method", "Comparable::between?")],
+            );
+        }
+    }
+
+    mod block_variable_tests {
+        use super::*;
+
+        fn assert_variable(finder: &Finder, file: &Path, point: Point, name: &str, expected_point: Point) {
+            let symbols = finder.find_definition(file, point).unwrap();
+            let actual: Vec<(&str, Point)> = symbols.iter().map(|s| (s.name(), *s.location())).collect();
+
+            assert_eq!(vec![(name, expected_point)], actual);
+        }
+
+        #[test]
+        fn lambda_params_test() {
+            let (root, finder) = fixture_finder("lambdas");
+            let file = root.join("handlers.rb");
+
+            let point = Point {
+                row: 2,
+                column: 27,
+            };
+            let expected_point = Point {
+                row: 2,
+                column: 15,
+            };
+            assert_variable(&finder, &file, point, "a", expected_point);
+
+            let point = Point {
+                row: 2,
+                column: 31,
+            };
+            let expected_point = Point {
+                row: 2,
+                column: 18,
+            };
+            assert_variable(&finder, &file, point, "b", expected_point);
+        }
+
+        #[test]
+        fn lambda_and_proc_block_params_test() {
+            let (root, finder) = fixture_finder("lambdas");
+            let file = root.join("handlers.rb");
+
+            let point = Point {
+                row: 3,
+                column: 32,
+            };
+            let expected_point = Point {
+                row: 3,
+                column: 24,
+            };
+            assert_variable(&finder, &file, point, "c", expected_point);
+
+            let point = Point {
+                row: 5,
+                column: 6,
+            };
+            let expected_point = Point {
+                row: 4,
+                column: 22,
+            };
+            assert_variable(&finder, &file, point, "d", expected_point);
+        }
+
+        #[test]
+        fn lambda_variable_test() {
+            let (root, finder) = fixture_finder("lambdas");
+            let point = Point {
+                row: 7,
+                column: 5,
+            };
+            let expected_point = Point {
+                row: 2,
+                column: 4,
+            };
+
+            assert_variable(&finder, &root.join("handlers.rb"), point, "adder", expected_point);
+        }
+    }
+
+    #[cfg(test)]
+    mod source_priority_tests {
+        use super::*;
+
+        fn priority_finder() -> (PathBuf, Finder) {
+            let fixture_dir = Path::new(FIXTURES_DIR).join("source_priority");
+            let root = fixture_dir.join("app");
+            let finder = empty_finder(&root, Config::default()).with_stubs_dir(Some(fixture_dir.join("core_stubs")));
+            finder.publish_file_indexes(index_fixture_files(&fixture_dir));
+
+            (root, finder)
+        }
+
+        fn definition_sources(finder: &Finder, file: &Path, point: Point) -> Vec<SourceKind> {
+            finder.find_definition(file, point).unwrap().iter().map(|s| finder.source_kind(s)).collect()
+        }
+
+        #[test]
+        fn source_kind_test() {
+            let (_, finder) = priority_finder();
+            let symbols = finder.symbols.load();
+            let kind_of = |file: &str| finder.source_kind(symbols.iter().find(|s| s.file().ends_with(file)).unwrap());
+
+            assert_eq!(SourceKind::Project, kind_of("app/lib/core_ext/string.rb"));
+            assert_eq!(SourceKind::Gem, kind_of("vendor/active_support/string.rb"));
+            assert_eq!(SourceKind::Stub, kind_of("core_stubs/string.rb"));
+        }
+
+        #[test]
+        fn monkey_patched_constant_test() {
+            let (root, finder) = priority_finder();
+            let point = Point {
+                row: 2,
+                column: 4,
+            };
+
+            let expected = vec![SourceKind::Project, SourceKind::Gem, SourceKind::Stub];
+            assert_eq!(expected, definition_sources(&finder, &root.join("formatter.rb"), point));
+        }
+
+        #[test]
+        fn monkey_patched_method_test() {
+            let (root, finder) = priority_finder();
+            let point = Point {
+                row: 2,
+                column: 22,
+            };
+
+            let expected = vec![SourceKind::Project, SourceKind::Gem, SourceKind::Stub];
+            assert_eq!(expected, definition_sources(&finder, &root.join("formatter.rb"), point));
+        }
+
+        #[test]
+        fn monkey_patched_symbol_search_test() {
+            let (_, finder) = priority_finder();
+            let sources: Vec<SourceKind> = finder
+                .fuzzy_find_symbol("String", &CancellationToken::default())
+                .unwrap()
+                .iter()
+                .filter(|m| m.symbol.name() == "String")
+                .map(|m| finder.source_kind(&m.symbol))
+                .collect();
+
+            assert_eq!(vec![SourceKind::Project, SourceKind::Gem, SourceKind::Stub], sources);
+        }
+    }
+
+    #[cfg(test)]
+    mod raise_tests {
+        use super::*;
+
+        #[test]
+        fn raise_argument_test() {
+            let (root, finder) = fixture_finder("raise_errors");
+            let file = root.join("billing/charge.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 3,
+                    column: 20,
+                },
+                &[("class", "Billing::Errors::PaymentFailed")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 3,
+                    column: 12,
+                },
+                &[("module", "Billing::Errors")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 7,
+                    column: 20,
+                },
+                &[("class", "Billing::Errors::PaymentFailed")],
+            );
+        }
+
+        #[test]
+        fn rescue_clause_test() {
+            let (root, finder) = fixture_finder("raise_errors");
+            let file = root.join("billing/charge.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 19,
+                },
+                &[("class", "Billing::Errors::GatewayTimeout")],
+            );
+        }
+
+        #[test]
+        fn raise_file_scope_test() {
+            let (root, finder) = fixture_finder("raise_errors");
+            let file = root.join("billing/refund.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 18,
+                },
+                &[("class", "Billing::Errors::PaymentFailed")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod rename_tests {
+        use super::*;
+
+        fn find_rename(finder: &Finder, file: &Path, position: Point) -> Result<MethodRename> {
+            finder.find_method_rename(&HashMap::new(), file, position)
+        }
+
+        fn point(row: usize, column: usize) -> Point {
+            Point {
+                row,
+                column,
+            }
+        }
+
+        #[test]
+        fn rename_definition_test() {
+            let (root, finder) = fixture_finder("rename");
+            let invoice = root.join("invoice.rb");
+            let report = root.join("report.rb");
+
+            let rename = find_rename(&finder, &invoice, point(1, 6)).unwrap();
+
+            let expected = MethodRename {
+                name: "total".to_string(),
+                locations: vec![
+                    (invoice.clone(), point(1, 6)),
+                    (invoice.clone(), point(6, 19)),
+                    (report.clone(), point(2, 16)),
+                ],
+                // the receiver of unknown type can be either an Invoice or an Order
+                ambiguous: vec![(report.clone(), point(4, 13))],
+            };
+            assert_eq!(expected, rename);
+        }
+
+        #[test]
+        fn rename_call_test() {
+            let (root, finder) = fixture_finder("rename");
+            let report = root.join("report.rb");
+
+            let from_call = find_rename(&finder, &report, point(2, 16)).unwrap();
+            let from_definition = find_rename(&finder, &root.join("invoice.rb"), point(1, 6)).unwrap();
+            assert_eq!(from_definition, from_call);
+
+            let rename = find_rename(&finder, &report, point(3, 14)).unwrap();
+            let locations = vec![(root.join("order.rb"), point(1, 6)), (report.clone(), point(3, 14))];
+            assert_eq!(locations, rename.locations);
+        }
+
+        #[test]
+        fn rename_open_document_test() {
+            let (root, finder) = fixture_finder("rename");
+            let report = root.join("report.rb");
+            let source = std::fs::read_to_string(&report).unwrap();
+            let source = source.replace("Invoice.new.total", "Order.new  .total");
+            let documents = HashMap::from([(report.clone(), Document::new(source).unwrap())]);
+
+            // the call is of Order#total in the unsaved document
+            let rename = finder.find_method_rename(&documents, &root.join("invoice.rb"), point(1, 6)).unwrap();
+
+            let locations = vec![(root.join("invoice.rb"), point(1, 6)), (root.join("invoice.rb"), point(6, 19))];
+            assert_eq!(locations, rename.locations);
+        }
+
+        #[test]
+        fn rename_project_files_test() {
+            let (root, finder) = fixture_finder_with_root("rename_project", "app");
+
+            // the call in the gem isn't renamed
+            let rename = find_rename(&finder, &root.join("report.rb"), point(2, 16)).unwrap();
+            let locations = vec![(root.join("invoice.rb"), point(1, 6)), (root.join("report.rb"), point(2, 16))];
+            assert_eq!(locations, rename.locations);
+            assert!(rename.ambiguous.is_empty());
+
+            // the method of the gem can't be renamed
+            assert!(find_rename(&finder, &root.join("invoice.rb"), point(2, 15)).is_err());
+        }
+
+        #[test]
+        fn rename_not_method_test() {
+            let (root, finder) = fixture_finder("rename");
+
+            assert!(find_rename(&finder, &root.join("report.rb"), point(2, 4)).is_err());
+        }
+    }
+
+    #[cfg(test)]
+    mod acronyms_tests {
+        use super::*;
+
+        #[test]
+        fn unconfigured_acronym_test() {
+            let (root, finder) = fixture_finder("acronyms");
+            let file = root.join("html_parser/document.rb");
+
+            // without the acronym the file is in HtmlParser, which isn't HTMLParser
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 1,
+                    column: 2,
+                },
+                &[],
+            );
+        }
+
+        #[test]
+        fn configured_acronym_test() {
+            let config = Config {
+                acronyms: vec!["HTML".to_string()],
+                ..Default::default()
+            };
+            let (root, finder) = fixture_finder_with_config("acronyms", "", config);
+            let file = root.join("html_parser/document.rb");
+
+            // XMLParser::Node is nested in another namespace, the file is in the one of HTMLParser
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 1,
+                    column: 2,
+                },
+                &[("class", "HTMLParser::Node")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod rename_path_tests {
+        use super::*;
+
+        #[test]
+        fn rename_file_test() {
+            let (root, old_file, finder) = temp_finder("rename", "class User\n  def name; end\nend\n");
+            let new_file = root.path().join("account.rb");
+
+            fs::rename(&old_file, &new_file).unwrap();
+            finder.rename_path(&old_file, &new_file).unwrap();
+
+            let names = |file: &Path| finder.find_by_path(file).iter().map(|s| s.name().to_string()).collect_vec();
+            assert!(names(&old_file).is_empty());
+            assert_eq!(vec!["User::name", "User"], names(&new_file));
+            assert_eq!(
+                vec![(
+                    new_file,
+                    Point {
+                        row: 0,
+                        column: 6
+                    }
+                )],
+                finder.find_reference_candidates("User")
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod data_define_tests {
+        use super::*;
+
+        #[test]
+        fn member_reader_test() {
+            let (root, finder) = fixture_finder("data_define");
+            let file = root.join("point.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 11,
+                    column: 7,
+                },
+                &[("method", "Point::y")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 14,
+                },
+                &[("method", "Point::x")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod dynamic_constant_tests {
+        use std::sync::Once;
+
+        use log::{Level, LevelFilter, Log, Metadata, Record};
+
+        use super::*;
+
+        thread_local! {
+            static ERRORS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+        }
+
+        // collects the error-level messages of the logging thread, the tests run in parallel
+        struct ErrorsLogger;
+
+        impl Log for ErrorsLogger {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                metadata.level() == Level::Error
+            }
+
+            fn log(&self, record: &Record) {
+                if self.enabled(record.metadata()) {
+                    ERRORS.with(|e| e.borrow_mut().push(record.args().to_string()));
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        fn assert_no_definitions(finder: &Finder, file: &Path, position: Point) {
+            static LOGGER: Once = Once::new();
+            LOGGER.call_once(|| {
+                log::set_logger(&ErrorsLogger).unwrap();
+                log::set_max_level(LevelFilter::Error);
+            });
+            ERRORS.with(|e| e.borrow_mut().clear());
+
+            let definitions = finder.find_definition(file, position).unwrap();
+            assert!(definitions.is_empty(), "Expected no definitions at {position:?}, got {definitions:?}");
+            ERRORS.with(|e| assert!(e.borrow().is_empty(), "Expected no errors at {position:?}, got {:?}", e.borrow()));
+        }
+
+        #[test]
+        fn defined_described_class_test() {
+            let source = "class InvoiceSpec\n  def described_class\n    Invoice\n  end\n\n  def subject\n    described_class.new\n  end\nend\n";
+            let (_root, file, finder) = temp_finder("defined_described_class", source);
+
+            // a method defined with the name isn't skipped
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 4,
+                },
+                &[("method", "InvoiceSpec::described_class")],
+            );
+        }
+
+        #[test]
+        fn described_class_test() {
+            let (root, finder) = fixture_finder("dynamic_constants");
+            let file = root.join("spec/invoice_spec.rb");
+
+            assert_no_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 1,
+                    column: 22,
+                },
+            );
+            assert_no_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 48,
+                },
+            );
+        }
+
+        #[test]
+        fn const_get_test() {
+            let (root, finder) = fixture_finder("dynamic_constants");
+            let file = root.join("spec/invoice_spec.rb");
+
+            assert_no_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 5,
+                    column: 19,
+                },
+            );
+            assert_no_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 17,
+                },
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 27,
+                },
+                &[("variable", "name")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod kernel_helper_tests {
+        use super::*;
+
+        #[test]
+        fn bare_call_test() {
+            let (root, finder) = fixture_finder("kernel_helpers");
+            let file = root.join("app/importer.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 4,
+                },
+                &[("method", "Kernel::log_duration")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 6,
+                },
+                &[("method", "Kernel::log_duration")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 7,
+                    column: 26,
+                },
+                &[("method", "Kernel::log_duration")],
+            );
+        }
+
+        #[test]
+        fn class_method_first_test() {
+            let (root, finder) = fixture_finder("kernel_helpers");
+            let file = root.join("app/report.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 6,
+                    column: 4,
+                },
+                &[("method", "Report::log_duration")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 2,
+                    column: 4,
+                },
+                &[("method", "Kernel::log_duration")],
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod definitions_cache_tests {
+        use super::*;
+
+        #[test]
+        fn cache_hit_test() {
+            let (_root, file, finder) =
+                temp_finder("cache-hit", "class User\n  def name; end\n\n  def to_s = name\nend\n");
+            let position = Point {
+                row: 3,
+                column: 13,
+            };
+            let names = |definitions: Vec<Arc<RSymbol>>| definitions.iter().map(|s| s.name().to_string()).collect_vec();
+
+            assert_eq!(vec!["User::name"], names(finder.find_definition(&file, position).unwrap()));
+
+            // the file isn't read and parsed again while the cached definitions are valid
+            fs::remove_file(&file).unwrap();
+            assert_eq!(vec!["User::name"], names(finder.find_definition(&file, position).unwrap()));
+
+            finder.publish_symbols(RSymbolIndex::clone(&finder.symbols.load()));
+            assert!(finder.find_definition(&file, position).is_err());
+        }
+
+        #[test]
+        fn invalidate_definitions_test() {
+            let (root, file, finder) =
+                temp_finder("cache-invalidate", "class User\n  def name; end\n\n  def to_s = name\nend\n");
+            let position = Point {
+                row: 3,
+                column: 13,
+            };
+
+            assert_eq!(1, finder.find_definition(&file, position).unwrap().len());
+
+            fs::remove_file(&file).unwrap();
+            finder.invalidate_definitions(&root.path().join("other.rb"));
+            assert!(finder.find_definition(&file, position).is_ok());
+
+            finder.invalidate_definitions(&file);
+            assert!(finder.find_definition(&file, position).is_err());
+        }
+    }
+
+    #[cfg(test)]
+    mod module_body_tests {
+        use super::*;
+
+        #[test]
+        fn module_body_call_test() {
+            let (root, finder) = fixture_finder("module_body");
+            let file = root.join("settings.rb");
+
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 9,
+                    column: 2,
+                },
+                &[("singleton_method", "Settings::load!")],
+            );
+            assert_definitions(
+                &finder,
+                &file,
+                Point {
+                    row: 10,
+                    column: 12,
+                },
+                &[("singleton_method", "Settings::defaults")],
+            );
+        }
+
+        #[test]
+        fn module_body_variable_test() {
+            let (root, finder) = fixture_finder("module_body");
+            let file = root.join("settings.rb");
+
+            let definitions = finder
+                .find_definition(
+                    &file,
+                    Point {
+                        row: 11,
+                        column: 12,
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                vec![
+                    (Point {
+                        row: 10,
+                        column: 2
+                    })
+                ],
+                definitions.iter().map(|d| *d.location()).collect_vec()
+            );
+
+            let definitions = finder
+                .find_definition(
+                    &file,
+                    Point {
+                        row: 15,
+                        column: 4,
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                vec![
+                    (Point {
+                        row: 14,
+                        column: 4
+                    })
+                ],
+                definitions.iter().map(|d| *d.location()).collect_vec()
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod unresolved_tests {
+        use super::*;
+
+        #[test]
+        fn class_body_test() {
+            let (root, finder) = fixture_finder("unresolved");
+            let file = root.join("report.rb");
+            let definitions = |row, column| {
+                finder
+                    .find_definition(
+                        &file,
+                        Point {
+                            row,
+                            column,
+                        },
+                    )
+                    .unwrap()
+            };
+
+            assert!(definitions(1, 2).is_empty());
+            assert!(definitions(5, 4).is_empty());
+            // the class body variable isn't visible in the methods
+            assert!(definitions(9, 4).is_empty());
+        }
     }
 }
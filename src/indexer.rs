@@ -1,4 +1,3 @@
-use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
 use std::rc::Rc;
@@ -7,88 +6,170 @@ use std::time::Instant;
 
 use anyhow::Result;
 
-use log::info;
+use log::{info, warn};
 use rayon::prelude::*;
+use tree_sitter::Tree;
 use walkdir::WalkDir;
 
+use crate::config::Config;
 use crate::parsers::general::{parse, read_file_tree};
+use crate::parsers::references::parse_references;
+use crate::parsers::types::SCOPE_DELIMITER;
 use crate::progress_reporter::ProgressReporter;
 use crate::ruby_env_provider::RubyEnvProvider;
 use crate::ruby_filename_converter::RubyFilenameConverter;
 
-use crate::types::RSymbol;
+use crate::types::{RReferences, RSymbol};
+
+/*
+ * Symbols and references found in a single file.
+ */
+pub struct FileIndex {
+    pub file: PathBuf,
+    pub symbols: Vec<Arc<RSymbol>>,
+    pub references: RReferences,
+}
 
 pub struct Indexer<'a> {
     root_dir: PathBuf,
+    config: Rc<Config>,
     progress_reporter: Rc<ProgressReporter<'a>>,
     ruby_env_provider: Rc<RubyEnvProvider>,
-    ruby_filename_converter: Rc<RubyFilenameConverter>,
 }
 
 impl<'a> Indexer<'a> {
     pub fn new(
         root_dir: &Path,
+        config: Rc<Config>,
         progress_reporter: Rc<ProgressReporter<'a>>,
         ruby_env_provider: Rc<RubyEnvProvider>,
-        ruby_filename_converter: Rc<RubyFilenameConverter>,
     ) -> Indexer<'a> {
         let root_dir = root_dir.to_path_buf();
 
         Indexer {
             ruby_env_provider,
-            ruby_filename_converter,
             root_dir,
+            config,
             progress_reporter,
         }
     }
 
-    pub fn index(&mut self) -> Result<Vec<Arc<RSymbol>>> {
+    pub fn index(&mut self) -> Result<Vec<FileIndex>> {
         let start = Instant::now();
-        let stubs_dir = self.ruby_env_provider.stubs_dir()?;
-        let gems_dir = self.ruby_env_provider.gems_dir()?;
+        let stubs_dir = Self::existing_dir("stubs", self.ruby_env_provider.stubs_dir()?);
+        let gems_dir = Self::existing_dir("gems", self.ruby_env_provider.gems_dir()?);
 
-        let symbols = [stubs_dir.as_ref(), gems_dir.as_ref(), Some(&self.root_dir)]
+        let file_indexes: Vec<FileIndex> = [stubs_dir.as_ref(), gems_dir.as_ref(), Some(&self.root_dir)]
             .into_iter()
             .flatten()
             .flat_map(|d| self.index_dir(d))
             .flatten()
-            .collect::<Vec<Arc<RSymbol>>>();
+            .collect();
+
+        let symbols_count: usize = file_indexes.iter().map(|i| i.symbols.len()).sum();
+        info!("Found {symbols_count} symbols in {} files, took {:?}", file_indexes.len(), start.elapsed());
 
-        info!("Found {} symbols, took {:?}", symbols.len(), start.elapsed());
+        Ok(file_indexes)
+    }
+
+    /*
+     * Drops the detected directory if it doesn't exist, e.g. when the ruby environment was guessed wrong,
+     * so that no progress is reported for it.
+     */
+    fn existing_dir(kind: &str, dir: Option<PathBuf>) -> Option<PathBuf> {
+        match dir {
+            Some(d) if !d.is_dir() => {
+                info!("skipping missing {kind} dir: {d:?}");
+                None
+            }
 
-        Ok(symbols)
+            d => d,
+        }
     }
 
-    fn index_dir(&self, dir: &Path) -> Result<Vec<Arc<RSymbol>>> {
+    fn index_dir(&self, dir: &Path) -> Result<Vec<FileIndex>> {
         let progress_token = self.progress_reporter.send_progress_begin(format!("Indexing {dir:?}"), "", 0)?;
 
-        let classes: Vec<Arc<RSymbol>> = WalkDir::new(dir)
+        // the top-level methods are scoped by the finder when the files are published
+        let classes = Self::index_files(dir, &self.config);
+
+        self.progress_reporter.send_progress_end(progress_token, format!("Indexing of {dir:?}"))?;
+
+        Ok(classes)
+    }
+
+    pub fn index_files(dir: &Path, config: &Config) -> Vec<FileIndex> {
+        WalkDir::new(dir)
             .into_iter()
             .par_bridge()
             .filter_map(Result::ok)
             .filter(|e| !e.file_type().is_dir())
-            .filter(|e| "rb" == e.path().extension().and_then(OsStr::to_str).unwrap_or(""))
-            .flat_map(|entry| Self::index_file_cursor(entry.into_path()).unwrap())
-            .collect();
+            .filter(|e| config.is_ruby_file(e.path()))
+            .filter_map(|entry| {
+                let path = entry.into_path();
+                Self::index_file(path.clone(), config).map_err(|e| warn!("skipping unreadable file {path:?}: {e}")).ok()
+            })
+            .collect()
+    }
 
-        self.progress_reporter.send_progress_end(progress_token, format!("Indexing of {dir:?}"))?;
+    pub fn index_file(path: PathBuf, config: &Config) -> Result<FileIndex> {
+        let (tree, source) = read_file_tree(&path)?;
 
-        Ok(classes)
+        Ok(Self::index_tree(path, &tree, &source, config))
     }
 
-    fn index_file_cursor(path: PathBuf) -> Result<Vec<Arc<RSymbol>>> {
-        let (tree, source) = read_file_tree(&path)?;
+    /*
+     * Scopes the methods defined outside of classes by the scope of the file, Rails expects the file to define it.
+     * Files outside of the project (e.g. gems) don't have a scope, their methods stay global.
+     * The methods which are already scoped are kept as they are, so it can be applied more than once.
+     */
+    pub fn scope_top_level_methods(file_index: FileIndex, converter: &RubyFilenameConverter) -> FileIndex {
+        let file_scope = match converter.path_to_scope(&file_index.file) {
+            Ok(scope) if !scope.is_empty() => scope,
+            _ => return file_index,
+        };
+
+        let symbols = file_index
+            .symbols
+            .into_iter()
+            .map(|symbol| {
+                if !matches!(*symbol, RSymbol::Method(_)) || symbol.parent().is_some() || symbol.full_scope().len() > 1
+                {
+                    return symbol;
+                }
+
+                match Arc::try_unwrap(symbol) {
+                    Ok(RSymbol::Method(mut method)) => {
+                        method.name = format!("{file_scope}{SCOPE_DELIMITER}{}", method.name);
+                        method.scope = file_scope.join(&method.scope);
+                        Arc::new(RSymbol::Method(method))
+                    }
+                    Ok(symbol) => Arc::new(symbol),
+                    Err(symbol) => symbol,
+                }
+            })
+            .collect();
+
+        FileIndex {
+            symbols,
+            ..file_index
+        }
+    }
+
+    /*
+     * Indexes the already parsed file, e.g. a document changed in the editor.
+     */
+    pub fn index_tree(path: PathBuf, tree: &Tree, source: &[u8], config: &Config) -> FileIndex {
         let mut result: Vec<Arc<RSymbol>> = Vec::new();
         let mut cursor = tree.walk();
         loop {
             let node = cursor.node();
-            let source = &source[..];
 
             if node.kind() == "program" {
                 cursor.goto_first_child();
             }
 
-            let mut parsed = parse(&path, source, cursor.node(), None);
+            let mut parsed = parse(&path, source, cursor.node(), None, config.max_nesting_depth);
             result.append(&mut parsed);
 
             if !cursor.goto_next_sibling() {
@@ -96,6 +177,350 @@ impl<'a> Indexer<'a> {
             }
         }
 
-        Ok(result)
+        let references = parse_references(source, tree.root_node());
+
+        FileIndex {
+            file: path,
+            symbols: result,
+            references,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tree_sitter::Parser;
+
+    use super::*;
+
+    use itertools::Itertools;
+
+    use crate::types::RMethodParam;
+
+    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+    const BENCH_FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/fixtures");
+
+    // generous on purpose, it's only supposed to catch severe regressions
+    const INDEXING_TIME_LIMIT: Duration = Duration::from_secs(5);
+
+    /*
+     * Gets the kinds and names of the symbols, e.g. "method User::save".
+     */
+    fn symbol_names(symbols: &[Arc<RSymbol>]) -> Vec<String> {
+        symbols.iter().map(|s| format!("{} {}", s.kind(), s.name())).collect()
+    }
+
+    fn indexed_names(dir: &Path) -> Vec<String> {
+        let symbols: Vec<Arc<RSymbol>> =
+            Indexer::index_files(dir, &Config::default()).into_iter().flat_map(|i| i.symbols).collect();
+        symbol_names(&symbols)
+    }
+
+    #[test]
+    fn existing_dir_test() {
+        let bogus = PathBuf::from(FIXTURES_DIR).join("nonexistent_stubs");
+        assert_eq!(None, Indexer::existing_dir("stubs", Some(bogus)));
+
+        let fixtures = PathBuf::from(FIXTURES_DIR);
+        assert_eq!(Some(fixtures.clone()), Indexer::existing_dir("gems", Some(fixtures)));
+
+        assert_eq!(None, Indexer::existing_dir("gems", None));
+    }
+
+    #[test]
+    fn index_files_time_limit_test() {
+        let start = Instant::now();
+
+        let file_indexes = Indexer::index_files(Path::new(BENCH_FIXTURES_DIR), &Config::default());
+
+        assert!(file_indexes.iter().any(|i| !i.symbols.is_empty()));
+        assert!(start.elapsed() < INDEXING_TIME_LIMIT, "Indexing took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn index_files_golden_test() {
+        let dir = Path::new(FIXTURES_DIR).join("golden");
+        let snapshot_path = Path::new(GOLDEN_DIR).join("indexer.snap");
+
+        let actual = Indexer::index_files(&dir, &Config::default())
+            .iter()
+            .flat_map(|i| i.symbols.iter())
+            .map(|s| {
+                let file = s.file().strip_prefix(&dir).unwrap().display();
+                let location = s.location();
+                (
+                    s.file().to_path_buf(),
+                    *location,
+                    format!(
+                        "{file}:{}:{} {} {} [{}]",
+                        location.row,
+                        location.column,
+                        s.kind(),
+                        s.name(),
+                        s.full_scope()
+                    ),
+                )
+            })
+            .sorted()
+            .map(|(_, _, line)| line + "\n")
+            .collect::<String>();
+
+        // run with UPDATE_GOLDEN=1 to accept the changes
+        if std::env::var("UPDATE_GOLDEN").is_ok() {
+            std::fs::write(&snapshot_path, &actual).unwrap();
+        }
+
+        let expected = std::fs::read_to_string(&snapshot_path).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn index_files_gem_dsl_test() {
+        let dir = Path::new(FIXTURES_DIR).join("gemspec");
+
+        let file_indexes = Indexer::index_files(&dir, &Config::default());
+
+        assert_eq!(2, file_indexes.len());
+        for file_index in file_indexes {
+            let names: Vec<&str> = file_index.symbols.iter().map(|s| s.name()).collect();
+            assert!(names.is_empty(), "{:?} has symbols: {names:?}", file_index.file);
+        }
+    }
+
+    #[test]
+    fn index_files_data_section_test() {
+        let dir = Path::new(FIXTURES_DIR).join("data_section");
+
+        let names = indexed_names(&dir);
+
+        let expected = vec!["constant STARTED_AT", "method Script::run", "class Script", "method report"];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn index_files_concerning_test() {
+        let dir = Path::new(FIXTURES_DIR).join("concerning");
+
+        let names = indexed_names(&dir);
+
+        let expected = vec![
+            "method User::Trackable::track",
+            "singleton_method User::Trackable::tracked_events",
+            "module User::Trackable",
+            "method User::Naming::full_name",
+            "module User::Naming",
+            "method User::save",
+            "class User",
+        ];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn index_file_data_define_test() {
+        let file_index =
+            Indexer::index_file(Path::new(FIXTURES_DIR).join("data_define/point.rb"), &Config::default()).unwrap();
+
+        let names = symbol_names(&file_index.symbols);
+        let expected = vec![
+            "method Point::distance",
+            "method Point::x",
+            "method Point::y",
+            "method Point::initialize",
+            "class Point",
+            "method Coordinates::lat",
+            "method Coordinates::lng",
+            "method Coordinates::initialize",
+            "class Coordinates",
+            "method Marker::initialize",
+            "class Marker",
+            // the explicit initializer replaces the synthesized one
+            "method Span::initialize",
+            "method Span::from",
+            "method Span::to",
+            "class Span",
+        ];
+        assert_eq!(expected, names);
+
+        let initializer = file_index.symbols.iter().find(|s| s.name() == "Point::initialize").unwrap();
+        let RSymbol::Method(method) = &**initializer else {
+            panic!("Expected method, got {initializer:?}");
+        };
+        let params: Vec<&str> = method
+            .parameters
+            .iter()
+            .map(|p| match p {
+                RMethodParam::Keyword(p) => p.name.as_str(),
+                _ => panic!("Expected keyword parameter"),
+            })
+            .collect();
+        assert_eq!(vec!["x", "y"], params);
+    }
+
+    #[test]
+    fn index_files_rest_assignments_test() {
+        let dir = Path::new(FIXTURES_DIR).join("rest_assignments");
+
+        let names = indexed_names(&dir);
+
+        let expected = vec![
+            "constant Limits::MIN",
+            "constant Limits::REST",
+            "constant Limits::Defaults::SIZES",
+            "constant Limits::MAX",
+            "constant Limits::Defaults::LOW",
+            "constant Limits::HIGH",
+            "module Limits",
+        ];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn index_files_operator_assignments_test() {
+        let dir = Path::new(FIXTURES_DIR).join("operator_assignments");
+
+        let names = indexed_names(&dir);
+
+        let expected = vec!["constant Registry::HANDLERS", "constant Registry::VERSION", "module Registry"];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn index_file_malformed_test() {
+        let file = Path::new(FIXTURES_DIR).join("malformed/catalog.rb");
+
+        let names = symbol_names(&Indexer::index_file(file, &Config::default()).unwrap().symbols);
+
+        // the module without a name is skipped, the rest of the file is still indexed
+        let expected = vec![
+            "method Catalog::initialize",
+            "method Catalog::find",
+            "method Catalog::count",
+            "class Catalog",
+            "constant VERSION",
+        ];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn index_file_empty_arguments_test() {
+        let file = Path::new(FIXTURES_DIR).join("empty_arguments/profile.rb");
+
+        let names = symbol_names(&Indexer::index_file(file, &Config::default()).unwrap().symbols);
+
+        // macros called without arguments don't define anything
+        assert_eq!(vec!["method Profile::name", "class Profile"], names);
+    }
+
+    #[test]
+    fn index_tree_deep_nesting_test() {
+        let source: String =
+            (1..=1000).map(|i| format!("module M{i}\n")).chain((1..=1000).map(|_| "end\n".to_string())).collect();
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ruby::language()).unwrap();
+        let tree = parser.parse(&source, None).unwrap();
+        let index =
+            |config: &Config| Indexer::index_tree(PathBuf::from("generated.rb"), &tree, source.as_bytes(), config);
+
+        // the modules nested deeper than the limit are skipped
+        let file_index = index(&Config::default());
+        assert_eq!(Config::default().max_nesting_depth, file_index.symbols.len());
+        assert_eq!("M1", file_index.symbols.last().unwrap().name());
+
+        let config = Config {
+            max_nesting_depth: 10,
+            ..Default::default()
+        };
+        assert_eq!(10, index(&config).symbols.len());
+    }
+
+    fn index_nested(source: &str, max_nesting_depth: usize) -> Vec<String> {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ruby::language()).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let config = Config {
+            max_nesting_depth,
+            ..Default::default()
+        };
+
+        symbol_names(&Indexer::index_tree(PathBuf::from("nested.rb"), &tree, source.as_bytes(), &config).symbols)
+    }
+
+    #[test]
+    fn nesting_depth_boundary_test() {
+        let index = index_nested;
+        let source = "module A\n  module B\n    def c; end\n  end\nend\n";
+
+        assert_eq!(vec!["method A::B::c", "module A::B", "module A"], index(source, 3));
+        assert_eq!(vec!["module A::B", "module A"], index(source, 2));
+
+        // the conditional and its branch take the levels as well
+        let source = "module A\n  if defined?(B)\n    def c; end\n  end\nend\n";
+        assert_eq!(vec!["method A::c", "module A"], index(source, 4));
+        assert_eq!(vec!["module A"], index(source, 3));
+    }
+
+    #[test]
+    fn dsl_block_nesting_depth_test() {
+        // the blocks take a level of their own besides the call
+        let source = "module A\n  included do\n    def c; end\n  end\nend\n";
+        assert_eq!(vec!["method A::c", "module A"], index_nested(source, 4));
+        assert_eq!(vec!["module A"], index_nested(source, 3));
+
+        let source = "class A\n  concerning :B do\n    def c; end\n  end\nend\n";
+        assert_eq!(vec!["method A::B::c", "module A::B", "class A"], index_nested(source, 4));
+        assert_eq!(vec!["module A::B", "class A"], index_nested(source, 3));
+
+        // concerns nested in the blocks deeper than the limit are skipped instead of overflowing the stack
+        let source: String = (1..=1000)
+            .map(|i| format!("concerning :C{i} do\n"))
+            .chain((1..=1000).map(|_| "end\n".to_string()))
+            .collect();
+        let source = format!("class A\n{source}end\n");
+        // the class and a module for every call and block levels
+        assert_eq!(
+            1 + Config::default().max_nesting_depth / 2,
+            index_nested(&source, Config::default().max_nesting_depth).len()
+        );
+    }
+
+    #[test]
+    fn index_files_platform_conditionals_test() {
+        let dir = Path::new(FIXTURES_DIR).join("platform_conditionals");
+
+        let names = indexed_names(&dir);
+
+        let expected = vec![
+            "method NativeParser::parse",
+            "class NativeParser",
+            "method NativeParser::parse",
+            "class NativeParser",
+            "class Native::Parser",
+            "module Native",
+            "constant FAST_PATH",
+            "class MacClipboard",
+            "class LinuxClipboard",
+            "class NullClipboard",
+        ];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn index_files_extensions_test() {
+        let dir = Path::new(FIXTURES_DIR).join("file_extensions");
+
+        let files: Vec<PathBuf> = Indexer::index_files(&dir, &Config::default())
+            .iter()
+            .map(|i| i.file.strip_prefix(&dir).unwrap().to_path_buf())
+            .sorted()
+            .collect();
+
+        let expected_files: Vec<PathBuf> =
+            ["Gemfile", "Rakefile", "app.rb", "lib/tasks/db.rake"].iter().map(PathBuf::from).collect();
+        assert_eq!(expected_files, files);
     }
 }
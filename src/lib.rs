@@ -0,0 +1,19 @@
+#[macro_use]
+extern crate anyhow;
+
+pub mod cancellation;
+pub mod config;
+pub mod debounce;
+pub mod documents;
+pub mod finder;
+pub mod indexer;
+pub mod parsers;
+pub mod progress_reporter;
+pub mod rbs;
+pub mod request_log;
+pub mod ruby_env_provider;
+pub mod ruby_filename_converter;
+pub mod server;
+pub mod symbols_dump;
+pub mod symbols_matcher;
+pub mod types;
@@ -1,34 +1,41 @@
 #[cfg(not(target_env = "msvc"))]
 use jemallocator::Jemalloc;
-use log::info;
+use log::{error, info, warn};
 
 #[cfg(not(target_env = "msvc"))]
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
-#[macro_use]
-extern crate anyhow;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+use crossbeam_channel::RecvTimeoutError;
 
-use lsp_server::{Connection, Message};
-use lsp_types::{InitializeParams, OneOf, ServerCapabilities};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Response};
+use lsp_types::{
+    notification::{DidOpenTextDocument, Notification as _, ShowMessage},
+    DidOpenTextDocumentParams, FileOperationFilter, FileOperationPattern, FileOperationRegistrationOptions,
+    HoverProviderCapability, InitializeParams, MessageType, OneOf, ServerCapabilities, ShowMessageParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, WorkspaceFileOperationsServerCapabilities,
+    WorkspaceServerCapabilities, WorkspaceSymbolOptions,
+};
 
-mod finder;
-mod indexer;
-mod parsers;
-mod progress_reporter;
-mod ruby_env_provider;
-mod ruby_filename_converter;
-mod server;
-mod symbols_matcher;
-mod types;
-
-use crate::server::Server;
+use rust_ruby_ls::{
+    cancellation::Cancellations, config::Config, request_log::REQUEST_LOG_KEY, server::Server, symbols_dump::dump_dir,
+};
 
 fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => {}
+        [flag, dir] if flag == "--index" => return print_index(Path::new(dir)),
+        _ => bail!("Usage: rust-ruby-ls [--index <dir>]"),
+    }
+
     let file = log4rs::append::file::FileAppender::builder()
-        .encoder(Box::new(log4rs::encode::pattern::PatternEncoder::new("{d} - {m}{n}")))
+        .encoder(Box::new(log4rs::encode::pattern::PatternEncoder::new(&format!(
+            "{{d}} - {{X({REQUEST_LOG_KEY})(-)}} - {{m}}{{n}}"
+        ))))
         .build("/Users/oleksandr.oksenenko/code/rust-ruby-ls/lsp.log")
         .unwrap();
     let config = log4rs::Config::builder()
@@ -39,16 +46,34 @@ fn main() -> Result<()> {
 
     let (connection, io_threads) = Connection::stdio();
 
-    let server_capabilities = serde_json::to_value(ServerCapabilities {
-        workspace_symbol_provider: Some(OneOf::Left(true)),
-        document_symbol_provider: Some(OneOf::Left(true)),
-        definition_provider: Some(OneOf::Left(true)),
-        ..Default::default()
-    })
-    .unwrap();
+    let (id, params) = connection.initialize_start()?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    // a mistake in the settings mustn't stop the server, it falls back to the defaults instead
+    let options = params.initialization_options.clone().map(serde_json::from_value::<Config>).transpose();
+    let (config, config_error) = match options {
+        Ok(config) => (config.unwrap_or_default(), None),
+        Err(e) => {
+            error!("invalid initialization options, using the default config: {e}");
+            (Config::default(), Some(e))
+        }
+    };
+    info!("using config: {config:?}");
+
+    let root = Server::workspace_root(&params).map(|path| config.project_root(&path));
+    let initialize_result = serde_json::json!({ "capabilities": server_capabilities(&config, root.as_deref()) });
+    connection.initialize_finish(id, initialize_result)?;
+
+    if let Some(e) = config_error {
+        let params = ShowMessageParams {
+            typ: MessageType::WARNING,
+            message: format!("Invalid rust-ruby-ls settings, the defaults are used instead: {e}"),
+        };
+        let notification = Notification::new(ShowMessage::METHOD.to_string(), params);
+        connection.sender.send(Message::Notification(notification))?;
+    }
 
-    let initialization_params = connection.initialize(server_capabilities)?;
-    main_loop(connection, initialization_params)?;
+    main_loop(connection, params, config, root)?;
     io_threads.join()?;
 
     info!("shutting down the server");
@@ -56,20 +81,108 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn main_loop(connection: Connection, params: serde_json::Value) -> Result<()> {
-    let params: InitializeParams = serde_json::from_value(params).unwrap();
+/*
+ * Indexes the directory with the default config and prints the symbols as JSON instead of running the server.
+ */
+fn print_index(dir: &Path) -> Result<()> {
+    let symbols = dump_dir(dir, &Config::default())?;
+    println!("{}", serde_json::to_string_pretty(&symbols)?);
+
+    Ok(())
+}
+
+/*
+ * Gets the capabilities of the server, the optional features are advertised only when they're enabled.
+ */
+fn server_capabilities(config: &Config, root: Option<&Path>) -> serde_json::Value {
+    let hover = root.and_then(|r| config.rbs_signatures_dir(r)).is_some();
+
+    let mut server_capabilities = serde_json::to_value(ServerCapabilities {
+        workspace_symbol_provider: Some(OneOf::Right(WorkspaceSymbolOptions {
+            work_done_progress_options: Default::default(),
+            resolve_provider: Some(true),
+        })),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: hover.then_some(HoverProviderCapability::Simple(true)),
+        rename_provider: config.experimental_rename.then_some(OneOf::Left(true)),
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
+        // the renamed files and directories are reindexed
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: None,
+            file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                did_rename: Some(FileOperationRegistrationOptions {
+                    filters: vec![FileOperationFilter {
+                        scheme: Some("file".to_string()),
+                        pattern: FileOperationPattern {
+                            glob: "**/*".to_string(),
+                            matches: None,
+                            options: None,
+                        },
+                    }],
+                }),
+                ..Default::default()
+            }),
+        }),
+        ..Default::default()
+    })
+    .unwrap();
+    // not supported by lsp-types yet
+    server_capabilities["typeHierarchyProvider"] = serde_json::Value::Bool(true);
 
+    server_capabilities
+}
+
+fn main_loop(connection: Connection, params: InitializeParams, config: Config, root: Option<PathBuf>) -> Result<()> {
     info!("start main loop");
 
-    // TODO: fix unwraps
-    let path = params.root_uri.unwrap().to_file_path().unwrap();
+    let (path, first_document) = match root {
+        Some(path) => (path, None),
+
+        None => {
+            info!("no workspace root, waiting for the first opened document");
+            match wait_for_first_document(&connection)? {
+                Some((path, notification)) => (path, Some(notification)),
+                None => return Ok(()),
+            }
+        }
+    };
+    info!("project root: {path:?}");
+
+    let cancellations = Cancellations::default();
+    let (receiver, forwarder) = cancellations.forward(connection.receiver.clone());
+
+    let server = match first_document {
+        Some(_) => Server::new_single_file(&path, config, &params.capabilities, &connection.sender, cancellations)?,
+        None => Server::new(&path, config, &params.capabilities, &connection.sender, cancellations)?,
+    };
+    if let Some(notification) = first_document {
+        if let Err(e) = server.handle_notification(notification) {
+            warn!("first document notification failed: {e}");
+        }
+    }
 
-    let server = Server::new(&path, &connection.sender)?;
+    loop {
+        server.reindex_pending();
+
+        // wake up when a changed document has to be reindexed even if no messages arrive
+        let msg = match server.reindex_timeout() {
+            Some(timeout) => match receiver.recv_timeout(timeout) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match receiver.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+        };
 
-    for msg in &connection.receiver {
         match msg {
             Message::Request(req) => {
                 if connection.handle_shutdown(&req)? {
+                    server.shutdown();
+                    forwarder.join().map_err(|_| anyhow!("Failed to join the messages forwarder"))?;
                     return Ok(());
                 }
 
@@ -81,10 +194,52 @@ fn main_loop(connection: Connection, params: serde_json::Value) -> Result<()> {
             }
 
             Message::Notification(not) => {
-                info!("got notification: {not:?}")
+                if let Err(e) = server.handle_notification(not) {
+                    warn!("notification failed: {e}");
+                }
             }
         }
     }
 
     Ok(())
 }
+
+/*
+ * Waits for the first opened document when the client has no workspace (e.g. a single file is opened),
+ * its directory is used as the root, though only the opened documents are indexed. Requests received
+ * before that are rejected.
+ */
+fn wait_for_first_document(connection: &Connection) -> Result<Option<(PathBuf, Notification)>> {
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(None);
+                }
+
+                let message = format!("{} can't be handled before a document is opened", req.method);
+                let response = Response::new_err(req.id, ErrorCode::RequestFailed as i32, message);
+                connection.sender.send(Message::Response(response))?;
+            }
+
+            Message::Response(resp) => {
+                info!("got response: {resp:?}")
+            }
+
+            Message::Notification(not) if not.method == DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(not.params.clone())?;
+
+                match Server::document_root(&params.text_document.uri) {
+                    Some(path) => return Ok(Some((path, not))),
+                    None => info!("skipping document without a local directory: {}", params.text_document.uri),
+                }
+            }
+
+            Message::Notification(not) => {
+                info!("got notification before a document is opened: {not:?}");
+            }
+        }
+    }
+
+    Ok(None)
+}
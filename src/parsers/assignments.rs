@@ -1,48 +1,86 @@
-use std::{path::Path, sync::Arc};
+use std::{iter, path::Path, sync::Arc};
 
 use tree_sitter::Node;
 
 use crate::{
     parsers::{
+        calls::parse_open_struct_readers,
+        classes::parse_class_new,
         constants::parse_constant,
+        scopes::is_constant_path,
         types::{NodeKind, NodeName, Scope},
     },
     types::{RSymbol, RVariable},
 };
 
-pub fn parse_assignment(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<Vec<RSymbol>> {
+pub fn parse_assignment(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    parent: Option<Arc<RSymbol>>,
+    depth: usize,
+) -> Option<Vec<Arc<RSymbol>>> {
     assert_eq!(node.kind(), NodeKind::Assignment);
 
-    let lhs = node.child_by_field_name(NodeName::Left).unwrap();
+    let lhs = node.child_by_field_name(NodeName::Left)?;
 
     let node_kind: NodeKind = match lhs.kind().try_into() {
         Err(_) => return None,
         Ok(nk) => nk,
     };
     match node_kind {
-        NodeKind::Constant => parse_constant(file, source, &lhs, parent).map(|c| vec![c]),
+        NodeKind::Constant => {
+            let class = node
+                .child_by_field_name(NodeName::Right)
+                .and_then(|value| parse_class_new(file, source, &lhs, value, parent.clone(), depth));
+            if class.is_some() {
+                return class;
+            }
+
+            let constant = Arc::new(parse_constant(file, source, &lhs, parent)?);
+            let readers = node
+                .child_by_field_name(NodeName::Right)
+                .map(|value| {
+                    let scope = constant.full_scope();
+                    parse_open_struct_readers(
+                        file,
+                        source,
+                        value,
+                        scope,
+                        Some(constant.clone()),
+                        RSymbol::SingletonMethod,
+                    )
+                })
+                .unwrap_or_default();
+
+            Some(iter::once(constant).chain(readers.into_iter().map(Arc::new)).collect())
+        }
 
         NodeKind::LeftAssignmentList => {
             // Only handle constants
             let mut cursor = lhs.walk();
             Some(
                 lhs.named_children(&mut cursor)
-                    .filter(|n| n.kind() == NodeKind::Constant || n.kind() == NodeKind::RestAssignment)
+                    .filter(|n| {
+                        let target = if n.kind() == NodeKind::RestAssignment { n.named_child(0) } else { Some(*n) };
+                        target.is_some_and(|t| is_constant_path(&t))
+                    })
                     .filter_map(|node| parse_constant(file, source, &node, parent.clone()))
+                    .map(Arc::new)
                     .collect(),
             )
         }
 
         NodeKind::GlobalVariable => {
-            let name = lhs.utf8_text(source).unwrap().to_string();
+            let name = lhs.utf8_text(source).ok()?.to_string();
             let scope: Scope = (&name).into();
-            Some(vec![RSymbol::GlobalVariable(RVariable {
+            Some(vec![Arc::new(RSymbol::GlobalVariable(RVariable {
                 file: file.to_path_buf(),
                 name,
                 scope,
                 location: node.start_position(),
                 parent: None,
-            })])
+            }))])
         }
 
         NodeKind::ScopeResolution => {
@@ -75,3 +113,19 @@ pub fn parse_assignment(file: &Path, source: &[u8], node: Node, parent: Option<A
         }
     }
 }
+
+/*
+ * Parses constants defined with an operator assignment, e.g. the memoized default `CACHE ||= {}`.
+ */
+pub fn parse_operator_assignment(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    parent: Option<Arc<RSymbol>>,
+) -> Option<Vec<Arc<RSymbol>>> {
+    assert_eq!(node.kind(), NodeKind::OperatorAssignment);
+
+    let lhs = node.child_by_field_name(NodeName::Left).filter(|l| l.kind() == NodeKind::Constant)?;
+
+    Some(vec![Arc::new(parse_constant(file, source, &lhs, parent)?)])
+}
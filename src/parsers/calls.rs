@@ -0,0 +1,695 @@
+use std::{path::Path, sync::Arc};
+
+use log::debug;
+use tree_sitter::Node;
+
+use crate::{
+    parsers::{
+        classes::parse_concerning,
+        constants::new_constant,
+        general::{nested_depth, parse},
+        methods::{new_method, parse_method, parse_method_params, parse_singleton_method},
+        scopes::{get_full_scope_resolution, is_constant_path},
+        types::{NodeKind, NodeName, Scope},
+    },
+    types::{RMethod, RSymbol, Visibility},
+};
+
+/*
+ * Parses DSL calls (e.g. define_method) that produce symbols, other calls are ignored.
+ */
+pub fn parse_call(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<Vec<RSymbol>> {
+    assert_eq!(node.kind(), NodeKind::Call);
+
+    // DSL calls are always made on the implicit self
+    if node.child_by_field_name(NodeName::Receiver).is_some() {
+        return None;
+    }
+
+    let method = node.child_by_field_name(NodeName::Method)?.utf8_text(source).ok()?;
+    let has_arguments = node.child_by_field_name(NodeName::Arguments).and_then(|a| a.named_child(0)).is_some();
+
+    match method {
+        // e.g. `define_method()` in the middle of typing
+        "define_method" | "alias_method" | "autoload" | "refine" if !has_arguments => {
+            debug!("Skipping {method} without arguments in {file:?} at {:?}", node.start_position());
+            None
+        }
+
+        "define_method" => parse_define_method(file, source, node, parent).map(|m| vec![m]),
+
+        "alias_method" => parse_alias_method(file, source, node, parent).map(|m| vec![m]),
+
+        "autoload" => parse_autoload(file, source, node, parent).map(|c| vec![c]),
+
+        "refine" => parse_refinement(file, source, node, parent),
+
+        "private" | "protected" | "public" => {
+            let visibility = method.parse().ok()?;
+            parse_inline_visibility(file, source, node, parent, visibility)
+        }
+
+        _ => None,
+    }
+}
+
+/*
+ * Parses the method defined with `define_method :name` or `define_method "name"`, interpolated names are skipped.
+ */
+fn parse_define_method(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<RSymbol> {
+    let name_node = node.child_by_field_name(NodeName::Arguments)?.named_child(0)?;
+    let Some(method_name) = get_literal_key(&name_node, source) else {
+        debug!("Skipping define_method with a dynamic name in {file:?} at {:?}", node.start_position());
+        return None;
+    };
+
+    let params = node.child_by_field_name(NodeName::Block).map(|b| parse_method_params(file, source, &b));
+
+    Some(new_method(file, method_name, name_node.start_position(), params.unwrap_or_default(), parent))
+}
+
+/*
+ * Parses the alias created with `alias_method :new_name, :original` as a method.
+ */
+fn parse_alias_method(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<RSymbol> {
+    let name_node = node.child_by_field_name(NodeName::Arguments)?.named_child(0)?;
+    let Some(method_name) = get_literal_key(&name_node, source) else {
+        debug!("Skipping alias_method with a dynamic name in {file:?} at {:?}", node.start_position());
+        return None;
+    };
+
+    Some(new_method(file, method_name, name_node.start_position(), vec![], parent))
+}
+
+/*
+ * Gets the body of the block passed to the call, both `do ... end` and `{ ... }` forms.
+ */
+pub fn get_block_body<'a>(call: &Node<'a>) -> Option<Node<'a>> {
+    call.child_by_field_name(NodeName::Block)
+        .filter(|b| matches!(b.kind().try_into(), Ok(NodeKind::Block | NodeKind::DoBlock)))?
+        .child_by_field_name(NodeName::Body)
+}
+
+/*
+ * Parses definitions in the blocks of the concern DSL in a module:
+ * `included do ... end` (and `prepended`) is evaluated in the including class, its definitions are indexed
+ * as the module's ones, and methods of `class_methods do ... end` become singleton methods of the module.
+ * The inline concern `concerning :Name do ... end` is a module nested in the class or module.
+ */
+pub fn parse_dsl_block(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    parent: Option<Arc<RSymbol>>,
+    depth: usize,
+) -> Option<Vec<Arc<RSymbol>>> {
+    if node.child_by_field_name(NodeName::Receiver).is_some() {
+        return None;
+    }
+
+    let method = node.child_by_field_name(NodeName::Method)?.utf8_text(source).ok()?;
+    if method == "concerning" && matches!(parent.as_deref(), Some(RSymbol::Class(_) | RSymbol::Module(_))) {
+        return parse_concerning(file, source, node, parent, depth);
+    }
+    if !matches!(parent.as_deref(), Some(RSymbol::Module(_))) {
+        return None;
+    }
+
+    let body = match method {
+        "included" | "prepended" | "class_methods" => get_block_body(&node)?,
+        _ => return None,
+    };
+    // the block is a nesting level of its own, like the body of a class
+    let Some(depth) = nested_depth(file, &body, depth) else {
+        return Some(vec![]);
+    };
+
+    let mut cursor = body.walk();
+    let children = body.named_children(&mut cursor);
+    let symbols = if method == "class_methods" {
+        children
+            .filter(|n| matches!(n.kind().try_into(), Ok(NodeKind::Method | NodeKind::SingletonMethod)))
+            .filter_map(|n| parse_singleton_method(file, source, n, parent.clone()))
+            .map(Arc::new)
+            .collect()
+    } else {
+        children.flat_map(|n| parse(file, source, n, parent.clone(), depth)).collect()
+    };
+
+    Some(symbols)
+}
+
+/*
+ * Parses methods of the refinement, e.g. `refine String do def shout; end end` in module StringExt.
+ * They are only visible with `using`, so they're scoped by the refinement (StringExt::refine(String)::shout)
+ * without a parent instead of being attributed to the refined class or the enclosing module.
+ */
+fn parse_refinement(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<Vec<RSymbol>> {
+    let target = node.child_by_field_name(NodeName::Arguments)?.named_child(0).filter(is_constant_path)?;
+    let body = get_block_body(&node)?;
+
+    let outer_scope = match parent.as_deref() {
+        Some(RSymbol::Class(c)) | Some(RSymbol::Module(c)) => c.scope.clone(),
+        _ => Scope::default(),
+    };
+    let refinement_scope = outer_scope.join(&format!("refine({})", get_full_scope_resolution(&target, source)).into());
+
+    let mut cursor = body.walk();
+    let methods = body
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == NodeKind::Method)
+        .filter_map(|n| match parse_method(file, source, n, None)? {
+            RSymbol::Method(mut method) => {
+                method.scope = refinement_scope.join(&method.scope);
+                method.name = method.scope.to_string();
+                Some(RSymbol::Method(method))
+            }
+            _ => None,
+        })
+        .collect();
+
+    Some(methods)
+}
+
+/*
+ * Parses the constant declared with `autoload :Widget, "path/to/widget"`, it's located at the declaration.
+ */
+fn parse_autoload(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<RSymbol> {
+    let arguments = node.child_by_field_name(NodeName::Arguments)?;
+    let name_node = arguments.named_child(0)?;
+    let path_node = arguments.named_child(1)?;
+    if name_node.kind() != NodeKind::SimpleSymbol || path_node.kind() != NodeKind::String {
+        debug!("Skipping autoload with dynamic arguments in {file:?} at {:?}", node.start_position());
+        return None;
+    }
+
+    let constant_name = get_literal_key(&name_node, source)?;
+
+    Some(new_constant(file, constant_name, name_node.start_position(), parent))
+}
+
+/*
+ * Parses the alias created with `alias new_name original` as a method.
+ */
+pub fn parse_alias(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<RSymbol> {
+    assert_eq!(node.kind(), NodeKind::Alias);
+
+    let name_node = node.child_by_field_name(NodeName::Name)?;
+    let method_name = get_alias_keyword_name(&name_node, source)?;
+
+    Some(new_method(file, method_name, name_node.start_position(), vec![], parent))
+}
+
+/*
+ * Gets the method name of the alias keyword, it's either an identifier or a symbol, e.g. "b" in "alias b a".
+ */
+pub fn get_alias_keyword_name<'a>(node: &Node, source: &'a [u8]) -> Option<&'a str> {
+    if node.kind() == NodeKind::Identifier {
+        return node.utf8_text(source).ok();
+    }
+
+    get_literal_key(node, source)
+}
+
+/*
+ * Parses a method definition passed to a visibility modifier, e.g. `private def secret; end`.
+ */
+fn parse_inline_visibility(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    parent: Option<Arc<RSymbol>>,
+    visibility: Visibility,
+) -> Option<Vec<RSymbol>> {
+    let definition = node.child_by_field_name(NodeName::Arguments)?.named_child(0)?;
+    if definition.kind() != NodeKind::Method {
+        return None;
+    }
+
+    let RSymbol::Method(method) = parse_method(file, source, definition, parent)? else {
+        return None;
+    };
+
+    Some(vec![RSymbol::Method(RMethod {
+        visibility,
+        ..method
+    })])
+}
+
+/*
+ * Parses readers of an OpenStruct created with literal keys, e.g. `host` in `OpenStruct.new(host: "localhost")`.
+ * The readers are scoped by the holder of the struct (a constant or an instance variable).
+ */
+pub fn parse_open_struct_readers(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    holder_scope: &Scope,
+    parent: Option<Arc<RSymbol>>,
+    reader: fn(RMethod) -> RSymbol,
+) -> Vec<RSymbol> {
+    if !is_open_struct_new(&node, source) {
+        return vec![];
+    }
+
+    let arguments = match node.child_by_field_name(NodeName::Arguments) {
+        Some(a) => a,
+        None => return vec![],
+    };
+
+    let mut cursor = arguments.walk();
+    arguments
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == NodeKind::Pair)
+        .filter_map(|pair| pair.child_by_field_name(NodeName::Key))
+        .filter_map(|key| {
+            let Some(name) = get_literal_key(&key, source) else {
+                debug!("Skipping OpenStruct dynamic key in {file:?} at {:?}", key.start_position());
+                return None;
+            };
+            let scope = holder_scope.join(&name.into());
+
+            Some(reader(RMethod {
+                file: file.to_path_buf(),
+                name: scope.to_string(),
+                scope,
+                location: key.start_position(),
+                parameters: vec![],
+                visibility: Visibility::Public,
+                parent: parent.clone(),
+            }))
+        })
+        .collect()
+}
+
+/*
+ * Gets the kind of the anonymous class or module created with `Class.new` or `Module.new`.
+ */
+pub fn get_class_new_kind(node: &Node, source: &[u8]) -> Option<NodeKind> {
+    if node.kind() != NodeKind::Call {
+        return None;
+    }
+
+    let method = node.child_by_field_name(NodeName::Method)?.utf8_text(source).ok()?;
+    let receiver = node.child_by_field_name(NodeName::Receiver).filter(|r| r.kind() == NodeKind::Constant)?;
+
+    match (receiver.utf8_text(source).ok()?, method) {
+        ("Class", "new") => Some(NodeKind::Class),
+        ("Module", "new") => Some(NodeKind::Module),
+        ("Data", "define") => Some(NodeKind::Class),
+        _ => None,
+    }
+}
+
+fn is_open_struct_new(node: &Node, source: &[u8]) -> bool {
+    if node.kind() != NodeKind::Call {
+        return false;
+    }
+
+    let method = node.child_by_field_name(NodeName::Method).and_then(|m| m.utf8_text(source).ok());
+    let receiver = node.child_by_field_name(NodeName::Receiver).map(|r| {
+        if r.kind() == NodeKind::ScopeResolution {
+            r.child_by_field_name(NodeName::Name).unwrap_or(r)
+        } else {
+            r
+        }
+    });
+    let receiver = receiver.filter(|r| r.kind() == NodeKind::Constant).and_then(|r| r.utf8_text(source).ok());
+
+    method == Some("new") && receiver == Some("OpenStruct")
+}
+
+/*
+ * Gets the key of a hash pair if it's a symbol or a string without interpolation.
+ */
+pub fn get_literal_key<'a>(node: &Node, source: &'a [u8]) -> Option<&'a str> {
+    let node_kind: NodeKind = node.kind().try_into().ok()?;
+
+    match node_kind {
+        NodeKind::HashKeySymbol => node.utf8_text(source).ok(),
+        NodeKind::SimpleSymbol => node.utf8_text(source).ok().map(|s| s.trim_start_matches(':')),
+        NodeKind::String if node.named_child_count() == 1 => {
+            node.named_child(0).filter(|c| c.kind() == NodeKind::StringContent).and_then(|c| c.utf8_text(source).ok())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::{Parser, Tree};
+
+    use crate::{config::Config, parsers::general::parse, types::RMethodParam};
+
+    use super::*;
+
+    fn param_name(param: &RMethodParam) -> &str {
+        match param {
+            RMethodParam::Regular(p) | RMethodParam::Optional(p) | RMethodParam::Keyword(p) => &p.name,
+        }
+    }
+
+    fn parse_source(source: &str) -> Vec<Arc<RSymbol>> {
+        let tree = parse_tree(source);
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+
+        root.named_children(&mut cursor)
+            .flat_map(|n| parse(Path::new("foo.rb"), source.as_bytes(), n, None, Config::default().max_nesting_depth))
+            .collect()
+    }
+
+    fn names(symbols: &[Arc<RSymbol>]) -> Vec<(&str, &str)> {
+        symbols.iter().map(|s| (s.kind(), s.name())).collect()
+    }
+
+    fn parse_tree(source: &str) -> Tree {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ruby::language()).unwrap();
+        parser.parse(source.as_bytes(), None).unwrap()
+    }
+
+    #[cfg(test)]
+    mod define_method_tests {
+        use super::*;
+
+        #[test]
+        fn define_method_block_params_test() {
+            let source = r#"
+class Foo
+  define_method(:bar) { |a, b = 1| a + b }
+
+  define_method :baz do |c:|
+    c
+  end
+end
+"#;
+            let symbols = parse_source(source);
+
+            let methods: Vec<(&str, Vec<&str>)> = symbols
+                .iter()
+                .filter_map(|s| match &**s {
+                    RSymbol::Method(m) => Some((m.name.as_str(), m.parameters.iter().map(param_name).collect())),
+                    _ => None,
+                })
+                .collect();
+
+            assert_eq!(vec![("Foo::bar", vec!["a", "b"]), ("Foo::baz", vec!["c"])], methods);
+        }
+
+        #[test]
+        fn define_method_param_kinds_test() {
+            let source = "define_method(:bar) { |a, b = 1, c:| }";
+            let symbols = parse_source(source);
+
+            let params = match &*symbols[0] {
+                RSymbol::Method(m) => &m.parameters,
+                _ => panic!("Expected method, got {:?}", symbols[0]),
+            };
+
+            assert!(matches!(params[0], RMethodParam::Regular(_)));
+            assert!(matches!(params[1], RMethodParam::Optional(_)));
+            assert!(matches!(params[2], RMethodParam::Keyword(_)));
+        }
+
+        #[test]
+        fn define_method_string_name_test() {
+            let symbol_source = "class Foo\n  define_method(:bar) { |a| a }\nend\n";
+            let string_source = "class Foo\n  define_method(\"bar\") { |a| a }\nend\n";
+
+            let symbols = parse_source(symbol_source);
+            let strings = parse_source(string_source);
+
+            assert_eq!(vec![("method", "Foo::bar"), ("class", "Foo")], names(&strings));
+            assert_eq!(names(&symbols), names(&strings));
+            assert_eq!(symbols[0].location(), strings[0].location());
+        }
+
+        #[test]
+        fn define_method_interpolated_name_test() {
+            let source = "class Foo\n  %w[a b].each { |n| define_method(\"bar_#{n}\") { } }\n  define_method(\"baz_#{1}\") { }\nend\n";
+            let symbols = parse_source(source);
+
+            assert_eq!(vec![("class", "Foo")], names(&symbols));
+        }
+    }
+
+    #[cfg(test)]
+    mod dsl_block_tests {
+        use super::*;
+
+        #[test]
+        fn block_forms_test() {
+            let do_blocks = r#"
+module Trackable
+  extend ActiveSupport::Concern
+
+  included do
+    TRACKED_EVENTS = [].freeze
+
+    def track(event); end
+  end
+
+  class_methods do
+    def tracked_events; end
+  end
+end
+"#;
+            let brace_blocks = r#"
+module Trackable
+  extend ActiveSupport::Concern
+
+  included {
+    TRACKED_EVENTS = [].freeze
+
+    def track(event); end
+  }
+
+  class_methods { def tracked_events; end }
+end
+"#;
+            let do_symbols = parse_source(do_blocks);
+            let brace_symbols = parse_source(brace_blocks);
+
+            let expected = vec![
+                ("constant", "Trackable::TRACKED_EVENTS"),
+                ("method", "Trackable::track"),
+                ("singleton_method", "Trackable::tracked_events"),
+                ("module", "Trackable"),
+            ];
+            assert_eq!(expected, names(&do_symbols));
+            assert_eq!(expected, names(&brace_symbols));
+        }
+
+        #[test]
+        fn class_included_test() {
+            let source = "class Foo\n  included do\n    def bar; end\n  end\nend";
+
+            assert_eq!(vec![("class", "Foo")], names(&parse_source(source)));
+        }
+    }
+
+    #[cfg(test)]
+    mod refinement_tests {
+        use super::*;
+
+        #[test]
+        fn refine_test() {
+            let source = r#"
+module StringExt
+  refine String do
+    def shout; end
+  end
+
+  refine(Foo::Bar) { def whisper; end }
+end
+"#;
+            let symbols = parse_source(source);
+            let methods: Vec<(&str, &str, bool)> = symbols
+                .iter()
+                .filter(|s| matches!(***s, RSymbol::Method(_)))
+                .map(|s| (s.name(), s.kind(), s.parent().is_some()))
+                .collect();
+
+            let expected = vec![
+                ("StringExt::refine(String)::shout", "method", false),
+                ("StringExt::refine(Foo::Bar)::whisper", "method", false),
+            ];
+            assert_eq!(expected, methods);
+        }
+    }
+
+    #[cfg(test)]
+    mod autoload_tests {
+        use super::*;
+
+        #[test]
+        fn autoload_test() {
+            let source = r#"
+module Shop
+  autoload :Widget, "shop/widget"
+  autoload :Gadget, File.join(__dir__, "gadget")
+  autoload name, "shop/other"
+end
+autoload :Cart, "cart"
+"#;
+            let symbols = parse_source(source);
+            let names: Vec<(&str, &str)> = symbols.iter().map(|s| (s.kind(), s.name())).collect();
+
+            let expected = vec![("constant", "Shop::Widget"), ("module", "Shop"), ("constant", "Cart")];
+            assert_eq!(expected, names);
+        }
+    }
+
+    #[cfg(test)]
+    mod class_new_tests {
+        use super::*;
+
+        #[test]
+        fn class_new_test() {
+            let source = r#"
+module Admin
+  Role = Class.new(Base::Role) do
+    include Comparable
+
+    def promote; end
+  end
+
+  Helpers = Module.new { def helper; end }
+  Other = Class.new
+end
+"#;
+            let symbols = parse_source(source);
+
+            let names: Vec<(&str, &str)> = symbols.iter().map(|s| (s.kind(), s.name())).collect();
+            let expected = vec![
+                ("method", "Admin::Role::promote"),
+                ("class", "Admin::Role"),
+                ("method", "Admin::Helpers::helper"),
+                ("module", "Admin::Helpers"),
+                ("class", "Admin::Other"),
+                ("module", "Admin"),
+            ];
+            assert_eq!(expected, names);
+
+            let RSymbol::Class(role) = &*symbols[1] else {
+                panic!("Expected class, got {:?}", symbols[1]);
+            };
+            assert_eq!("Base::Role", role.superclass_scopes.to_string());
+            assert_eq!(vec!["Comparable"], role.include_scopes.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        }
+    }
+
+    #[cfg(test)]
+    mod alias_tests {
+        use super::*;
+
+        #[test]
+        fn alias_test() {
+            let source = r#"
+class Account
+  def balance; end
+
+  alias_method :current_balance, :balance
+  alias_method "amount", "balance"
+  alias total balance
+  alias :sum :balance
+  alias_method name, :balance
+end
+"#;
+            let names: Vec<String> = parse_source(source).iter().map(|s| s.name().to_string()).collect();
+
+            let expected = vec![
+                "Account::balance",
+                "Account::current_balance",
+                "Account::amount",
+                "Account::total",
+                "Account::sum",
+                "Account",
+            ];
+            assert_eq!(expected, names);
+        }
+    }
+
+    #[cfg(test)]
+    mod visibility_tests {
+        use crate::indexer::Indexer;
+
+        use super::*;
+
+        const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+        #[test]
+        fn inline_visibility_test() {
+            let file_index =
+                Indexer::index_file(Path::new(FIXTURES_DIR).join("visibility/account.rb"), &Config::default()).unwrap();
+
+            let methods: Vec<(&str, Visibility)> = file_index
+                .symbols
+                .iter()
+                .filter_map(|s| match &**s {
+                    RSymbol::Method(m) => Some((m.name.as_str(), m.visibility)),
+                    _ => None,
+                })
+                .collect();
+
+            let expected = vec![
+                ("Account::balance", Visibility::Public),
+                ("Account::secret", Visibility::Private),
+                ("Account::audit", Visibility::Protected),
+                ("Account::deposit", Visibility::Public),
+            ];
+            assert_eq!(expected, methods);
+        }
+    }
+
+    #[cfg(test)]
+    mod open_struct_tests {
+        use crate::parsers::methods::parse_instance_variable_readers;
+
+        use super::*;
+
+        fn symbol_names(symbols: &[Arc<RSymbol>]) -> Vec<(&str, &str)> {
+            symbols.iter().map(|s| (s.kind(), s.name())).collect()
+        }
+
+        #[test]
+        fn constant_open_struct_test() {
+            let source =
+                r##"CONFIG = OpenStruct.new(host: "localhost", "port" => 3000, :ssl => false, "#{x}" => 1, **opts)"##;
+            let symbols = parse_source(source);
+
+            let expected = vec![
+                ("constant", "CONFIG"),
+                ("singleton_method", "CONFIG::host"),
+                ("singleton_method", "CONFIG::port"),
+                ("singleton_method", "CONFIG::ssl"),
+            ];
+            assert_eq!(expected, symbol_names(&symbols));
+            assert!(symbols[1..].iter().all(|s| s.parent().as_ref() == Some(&symbols[0])));
+        }
+
+        #[test]
+        fn instance_variable_open_struct_test() {
+            let source = r#"
+class Client
+  def initialize
+    @options = ::OpenStruct.new(verbose: true)
+    @other = Struct.new(:a)
+  end
+end
+"#;
+            // the readers of instance variables aren't indexed, they'd show up in the workspace symbols
+            let symbols = parse_source(source);
+            assert_eq!(vec![("method", "Client::initialize"), ("class", "Client")], symbol_names(&symbols));
+
+            let tree = parse_tree(source);
+            let method = tree.root_node().named_child(0).unwrap().child_by_field_name(NodeName::Body).unwrap();
+            let method = method.named_child(0).unwrap();
+            let readers =
+                parse_instance_variable_readers(Path::new("client.rb"), source.as_bytes(), method, &"Client".into());
+            let names: Vec<(&str, &str)> = readers.iter().map(|s| (s.kind(), s.name())).collect();
+            assert_eq!(vec![("method", "Client::@options::verbose")], names);
+        }
+    }
+}
@@ -1,30 +1,48 @@
-use std::{path::Path, sync::Arc};
+use std::{iter, path::Path, sync::Arc};
 
-use log::debug;
+use log::{debug, warn};
 use tree_sitter::Node;
 
 use crate::{
     parsers::{
-        general::parse,
-        scopes::get_full_and_context_scope,
-        types::{NodeKind, NodeName, Scope},
+        calls::{get_block_body, get_class_new_kind, get_literal_key},
+        constants::{new_constant, parse_constant},
+        general::{nested_depth, parse},
+        methods::new_method,
+        scopes::{get_full_and_context_scope, get_full_scope_resolution, is_constant_path},
+        types::{NodeKind, NodeName, Scope, DATA_SCOPE_VALUE},
     },
-    types::{RClass, RSymbol},
+    types::{MethodParam, RClass, RMethodParam, RSymbol},
 };
 
-pub fn parse_class(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Vec<Arc<RSymbol>> {
+pub fn parse_class(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    parent: Option<Arc<RSymbol>>,
+    depth: usize,
+) -> Vec<Arc<RSymbol>> {
     debug!("Parsing {:?} at {:?}", file, node.start_position());
 
     assert!(node.kind() == NodeKind::Class || node.kind() == NodeKind::Module);
 
-    let name_node = node.child_by_field_name(NodeName::Name).unwrap();
+    // error recovery produces names with missing constants, e.g. in "module ::"
+    let Some(name_node) = node.child_by_field_name(NodeName::Name).filter(|n| !n.has_error()) else {
+        warn!("Skipping {} without a name in {file:?} at {:?}", node.kind(), node.start_position());
+        return vec![];
+    };
     let scopes = get_full_and_context_scope(&name_node, source);
     let name = scopes.to_string();
+    // superclass and included modules are kept as written, they're resolved lexically by the finder
     let superclass_scopes = node
         .child_by_field_name(NodeName::Superclass)
-        .and_then(|n| n.child_by_field_name(NodeName::Name))
-        .map(|n| get_full_and_context_scope(&n, source))
-        .unwrap_or(Scope::default());
+        .and_then(|n| n.named_child(0))
+        .filter(is_constant_path)
+        .map(|n| get_full_scope_resolution(&n, source))
+        .unwrap_or_default();
+    let body = node.child_by_field_name(NodeName::Body);
+    let include_scopes = body.map(|n| parse_mixin_scopes(&n, source, "include")).unwrap_or_default();
+    let prepend_scopes = body.map(|n| parse_mixin_scopes(&n, source, "prepend")).unwrap_or_default();
 
     let rclass = RClass {
         file: file.to_path_buf(),
@@ -32,6 +50,8 @@ pub fn parse_class(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RS
         scope: scopes,
         location: name_node.start_position(),
         superclass_scopes,
+        include_scopes,
+        prepend_scopes,
         parent,
     };
 
@@ -41,22 +61,226 @@ pub fn parse_class(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RS
         Arc::new(RSymbol::Module(rclass))
     };
 
+    let mut result: Vec<Arc<RSymbol>> =
+        body.map(|body_node| parse_body(file, source, body_node, &parent_symbol, depth)).unwrap_or_default();
+    result.push(parent_symbol);
+
+    result
+}
+
+/*
+ * Parses a class or a module created with `Class.new(Base) do ... end` and assigned to a constant,
+ * returns None if the value isn't `Class.new`, `Module.new` or `Data.define`.
+ */
+pub fn parse_class_new(
+    file: &Path,
+    source: &[u8],
+    constant_node: &Node,
+    value: Node,
+    parent: Option<Arc<RSymbol>>,
+    depth: usize,
+) -> Option<Vec<Arc<RSymbol>>> {
+    let kind = get_class_new_kind(&value, source)?;
+
+    let RSymbol::Constant(constant) = parse_constant(file, source, constant_node, parent.clone())? else {
+        return None;
+    };
+    let data_members = get_data_members(&value, source);
+    let superclass_scopes = match data_members {
+        Some(_) => Scope::from(DATA_SCOPE_VALUE),
+        None => value
+            .child_by_field_name(NodeName::Arguments)
+            .and_then(|a| a.named_child(0))
+            .filter(|n| kind == NodeKind::Class && is_constant_path(n))
+            .map(|n| get_full_scope_resolution(&n, source))
+            .unwrap_or_default(),
+    };
+    let body = get_block_body(&value);
+    let include_scopes = body.map(|n| parse_mixin_scopes(&n, source, "include")).unwrap_or_default();
+    let prepend_scopes = body.map(|n| parse_mixin_scopes(&n, source, "prepend")).unwrap_or_default();
+
+    let rclass = RClass {
+        file: file.to_path_buf(),
+        name: constant.name,
+        scope: constant.scope,
+        location: constant.location,
+        superclass_scopes,
+        include_scopes,
+        prepend_scopes,
+        parent,
+    };
+
+    let parent_symbol =
+        if kind == NodeKind::Class { Arc::new(RSymbol::Class(rclass)) } else { Arc::new(RSymbol::Module(rclass)) };
+
+    let mut result = body.map(|b| parse_body(file, source, b, &parent_symbol, depth)).unwrap_or_default();
+    if let Some(members) = data_members {
+        let explicit_initializer = result.iter().any(|s| {
+            matches!(&**s, RSymbol::Method(_))
+                && s.full_scope().last() == Some("initialize")
+                && s.parent().as_ref() == Some(&parent_symbol)
+        });
+        result.extend(parse_data_members(file, &value, &members, &parent_symbol, explicit_initializer));
+    }
+    result.push(parent_symbol);
+
+    Some(result)
+}
+
+/*
+ * Gets the names of the members with their nodes of `Data.define(:x, :y)`, dynamic ones are skipped.
+ */
+fn get_data_members<'a>(value: &Node<'a>, source: &'a [u8]) -> Option<Vec<(&'a str, Node<'a>)>> {
+    let receiver = value.child_by_field_name(NodeName::Receiver)?;
+    let method = value.child_by_field_name(NodeName::Method)?;
+    if receiver.utf8_text(source).ok()? != DATA_SCOPE_VALUE || method.utf8_text(source).ok()? != "define" {
+        return None;
+    }
+
+    let Some(arguments) = value.child_by_field_name(NodeName::Arguments) else {
+        return Some(vec![]);
+    };
+    let mut cursor = arguments.walk();
+    let members =
+        arguments.named_children(&mut cursor).filter_map(|a| Some((get_literal_key(&a, source)?, a))).collect();
+
+    Some(members)
+}
+
+/*
+ * Creates the readers of the Data members and the initializer accepting them as keywords,
+ * e.g. `x`, `y` and `initialize(x:, y:)` for `Data.define(:x, :y)`.
+ * The initializer isn't created if the block defines its own one.
+ */
+fn parse_data_members(
+    file: &Path,
+    value: &Node,
+    members: &[(&str, Node)],
+    class: &Arc<RSymbol>,
+    explicit_initializer: bool,
+) -> Vec<Arc<RSymbol>> {
+    let readers =
+        members.iter().map(|(name, node)| new_method(file, name, node.start_position(), vec![], Some(class.clone())));
+    if explicit_initializer {
+        return readers.map(Arc::new).collect();
+    }
+
+    let params = members
+        .iter()
+        .map(|(name, node)| {
+            RMethodParam::Keyword(MethodParam {
+                file: file.to_path_buf(),
+                name: name.to_string(),
+                location: node.start_position(),
+            })
+        })
+        .collect();
+    let location = value.child_by_field_name(NodeName::Method).unwrap_or(*value).start_position();
+    let initializer = new_method(file, "initialize", location, params, Some(class.clone()));
+
+    readers.chain(iter::once(initializer)).map(Arc::new).collect()
+}
+
+/*
+ * Parses the inline concern `concerning :Trackable do ... end` as the module nested in the enclosing one,
+ * the block is its body.
+ */
+pub fn parse_concerning(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    parent: Option<Arc<RSymbol>>,
+    depth: usize,
+) -> Option<Vec<Arc<RSymbol>>> {
+    let name_node = node.child_by_field_name(NodeName::Arguments)?.named_child(0)?;
+    let Some(name) = get_literal_key(&name_node, source) else {
+        debug!("Skipping concerning with a dynamic name in {file:?} at {:?}", node.start_position());
+        return None;
+    };
+    node.child_by_field_name(NodeName::Block)?;
+
+    let RSymbol::Constant(constant) = new_constant(file, name, name_node.start_position(), parent.clone()) else {
+        return None;
+    };
+    let body = get_block_body(&node);
+    let include_scopes = body.map(|n| parse_mixin_scopes(&n, source, "include")).unwrap_or_default();
+    let prepend_scopes = body.map(|n| parse_mixin_scopes(&n, source, "prepend")).unwrap_or_default();
+    // the block is a nesting level of its own, like the body of a class
+    let body = body.and_then(|b| Some((b, nested_depth(file, &b, depth)?)));
+
+    let module = Arc::new(RSymbol::Module(RClass {
+        file: file.to_path_buf(),
+        name: constant.name,
+        scope: constant.scope,
+        location: constant.location,
+        superclass_scopes: Scope::default(),
+        include_scopes,
+        prepend_scopes,
+        parent,
+    }));
+
+    let mut result = body.map(|(b, depth)| parse_body(file, source, b, &module, depth)).unwrap_or_default();
+    result.push(module);
+
+    Some(result)
+}
+
+fn parse_body(
+    file: &Path,
+    source: &[u8],
+    body_node: Node,
+    parent_symbol: &Arc<RSymbol>,
+    depth: usize,
+) -> Vec<Arc<RSymbol>> {
     let mut result: Vec<Arc<RSymbol>> = Vec::new();
-    if let Some(body_node) = node.child_by_field_name(NodeName::Body) {
-        let mut cursor = body_node.walk();
-        cursor.goto_first_child();
-        let mut node = cursor.node();
-        loop {
-            let mut parsed = parse(file, source, node, Some(parent_symbol.clone()));
-            result.append(&mut parsed);
-
-            node = match node.next_sibling() {
-                None => break,
-                Some(n) => n,
-            }
+
+    let mut cursor = body_node.walk();
+    cursor.goto_first_child();
+    let mut node = cursor.node();
+    loop {
+        let mut parsed = parse(file, source, node, Some(parent_symbol.clone()), depth);
+        result.append(&mut parsed);
+
+        node = match node.next_sibling() {
+            None => break,
+            Some(n) => n,
         }
     }
-    result.push(parent_symbol);
 
     result
 }
+
+/*
+ * Parses the modules mixed in with the method, e.g. "include" or "prepend", in the order of the calls.
+ * The inline concerns `concerning :Trackable do ... end` are included as well, like Rails does.
+ */
+fn parse_mixin_scopes(body_node: &Node, source: &[u8], mixin_method: &str) -> Vec<Scope> {
+    let mut cursor = body_node.walk();
+
+    body_node
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == NodeKind::Call && n.child_by_field_name(NodeName::Receiver).is_none())
+        .filter_map(|n| Some((n.child_by_field_name(NodeName::Method)?.utf8_text(source).ok()?, n)))
+        .flat_map(|(method, n)| match method {
+            "concerning" if mixin_method == "include" && n.child_by_field_name(NodeName::Block).is_some() => n
+                .child_by_field_name(NodeName::Arguments)
+                .and_then(|args| args.named_child(0))
+                .and_then(|name| get_literal_key(&name, source))
+                .map(|name| vec![Scope::from(name)])
+                .unwrap_or_default(),
+
+            _ if method == mixin_method => {
+                let Some(args) = n.child_by_field_name(NodeName::Arguments) else {
+                    return vec![];
+                };
+                let mut cursor = args.walk();
+                args.named_children(&mut cursor)
+                    .filter(is_constant_path)
+                    .map(|arg| get_full_scope_resolution(&arg, source))
+                    .collect::<Vec<Scope>>()
+            }
+
+            _ => vec![],
+        })
+        .collect()
+}
@@ -0,0 +1,80 @@
+use crate::parsers::types::Scope;
+
+// YARD tags which are followed by a list of types in brackets
+const YARD_TYPE_TAGS: [&str; 5] = ["@return", "@param", "@raise", "@yieldparam", "@yieldreturn"];
+
+/*
+ * Finds the constant referenced in a YARD type list at the byte offset of the comment,
+ * e.g. `Foo::Bar` in `# @return [Array<Foo::Bar>]`. Like for regular constants, only the part of
+ * the path till the segment under the offset is returned.
+ */
+pub fn get_yard_type_at(comment: &str, offset: usize) -> Option<Scope> {
+    if !comment.is_char_boundary(offset) {
+        return None;
+    }
+
+    let line_start = comment[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = comment[offset..].find('\n').map(|i| offset + i).unwrap_or(comment.len());
+    let line = &comment[line_start..line_end];
+    let offset = offset - line_start;
+
+    let tag_end = YARD_TYPE_TAGS.iter().filter_map(|t| line.find(t).map(|i| i + t.len())).min()?;
+    let types_start = tag_end + line[tag_end..].find('[')? + 1;
+    let types_end = types_start + line[types_start..].find(']')?;
+    if offset < types_start || offset >= types_end {
+        return None;
+    }
+
+    let is_path_char = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+    let start = line[..offset]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !is_path_char(*c))
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let end = line[offset..].find(|c| !is_path_char(c)).map(|i| offset + i).unwrap_or(line.len());
+    let path = &line[start..end];
+
+    let segment_end = path[offset - start..].find("::").map(|i| offset - start + i).unwrap_or(path.len());
+    let segments: Vec<String> =
+        path[..segment_end].split("::").filter(|s| !s.is_empty()).map(|s| s.to_string()).collect();
+
+    let is_constant = |s: &String| s.chars().next().map(|c| c.is_uppercase()).unwrap_or(false);
+    if segments.is_empty() || !segments.iter().all(is_constant) {
+        return None;
+    }
+
+    Some(Scope::new(segments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yard_type_at(comment: &str, needle: &str) -> Option<String> {
+        let offset = comment.find(needle).unwrap();
+        get_yard_type_at(comment, offset).map(|s| s.to_string())
+    }
+
+    #[test]
+    fn test_get_yard_type_at() {
+        let comment = "# @return [Foo::Bar, nil] the bar";
+        assert_eq!(Some("Foo::Bar".to_string()), yard_type_at(comment, "Bar"));
+        assert_eq!(Some("Foo".to_string()), yard_type_at(comment, "Foo"));
+        assert_eq!(None, yard_type_at(comment, "nil"));
+        assert_eq!(None, yard_type_at(comment, "the"));
+
+        let comment = "# @param user [Array<Models::User>] users";
+        assert_eq!(Some("Models::User".to_string()), yard_type_at(comment, "User"));
+        assert_eq!(None, yard_type_at(comment, "user"));
+
+        let comment = "# returns [Foo]";
+        assert_eq!(None, yard_type_at(comment, "Foo"));
+    }
+
+    #[test]
+    fn test_get_yard_type_at_multiline() {
+        let comment = "=begin\n@return [Foo]\n=end";
+        assert_eq!(Some("Foo".to_string()), yard_type_at(comment, "Foo"));
+    }
+}
@@ -0,0 +1,96 @@
+use std::{path::Path, sync::Arc};
+
+use tree_sitter::Node;
+
+use crate::{
+    parsers::{general::parse, types::NodeName},
+    types::RSymbol,
+};
+
+/*
+ * Parses definitions guarded by conditionals (e.g. "FOO = 1 if bar" or "def foo" inside of "if"),
+ * conditions themselves can't define anything, so they are skipped.
+ */
+pub fn parse_conditional(
+    file: &Path,
+    source: &[u8],
+    node: Node,
+    parent: Option<Arc<RSymbol>>,
+    depth: usize,
+) -> Vec<Arc<RSymbol>> {
+    let mut result = Vec::new();
+
+    let mut cursor = node.walk();
+    if !cursor.goto_first_child() {
+        return result;
+    }
+
+    loop {
+        let field_name = cursor.field_name().and_then(|f| NodeName::try_from(f).ok());
+        let is_condition = matches!(field_name, Some(NodeName::Condition | NodeName::Value | NodeName::Pattern));
+
+        if cursor.node().is_named() && !is_condition {
+            result.extend(parse(file, source, cursor.node(), parent.clone(), depth));
+        }
+
+        if !cursor.goto_next_sibling() {
+            break;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Parser;
+
+    use crate::config::Config;
+
+    use super::*;
+
+    fn parse_names(source: &str) -> Vec<String> {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ruby::language()).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+
+        root.named_children(&mut cursor)
+            .flat_map(|n| parse(Path::new("foo.rb"), source.as_bytes(), n, None, Config::default().max_nesting_depth))
+            .map(|s| s.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_modifiers_test() {
+        let source = "FOO = 1 if something\nBAR = 2 unless FOO\n";
+
+        assert_eq!(vec!["FOO", "BAR"], parse_names(source));
+    }
+
+    #[test]
+    fn parse_conditionals_test() {
+        let source = r#"
+if RUBY_VERSION > "3"
+  def modern; end
+elsif IS_OLD = legacy?
+  A = 1
+else
+  B = 2
+end
+
+unless x
+  C = 3
+end
+
+case x
+when D = 1 then E = 1
+else
+  F = 2
+end
+"#;
+
+        assert_eq!(vec!["modern", "A", "B", "C", "E", "F"], parse_names(source));
+    }
+}
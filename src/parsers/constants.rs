@@ -1,19 +1,45 @@
 use std::{path::Path, sync::Arc};
 
 use log::error;
-use tree_sitter::Node;
+use tree_sitter::{Node, Point};
 
 use crate::types::{RConstant, RSymbol};
 
-use super::types::{NodeKind, SCOPE_DELIMITER};
+use super::{
+    scopes::{get_full_scope_resolution, is_constant_path},
+    types::{NodeKind, NodeName, Scope},
+};
 
 pub fn parse_constant(file: &Path, source: &[u8], node: &Node, parent: Option<Arc<RSymbol>>) -> Option<RSymbol> {
-    if node.kind() != NodeKind::Constant && node.kind() != NodeKind::RestAssignment {
-        error!("{} instead of constant in {file:?} at {:?}", node.kind(), node.range());
+    // the target of a splat, e.g. REST in `FIRST, *REST = list`
+    let node = if node.kind() == NodeKind::RestAssignment { node.named_child(0)? } else { *node };
+
+    match node.kind().try_into() {
+        Ok(NodeKind::Constant) => Some(new_constant(file, node.utf8_text(source).ok()?, node.start_position(), parent)),
+
+        // a constant of the nested namespace, e.g. `Defaults::LOW, HIGH = 1, 2`
+        Ok(NodeKind::ScopeResolution) if is_constant_path(&node) => {
+            let name_node = node.child_by_field_name(NodeName::Name)?;
+            let scope = get_full_scope_resolution(&node, source);
+
+            Some(new_scoped_constant(file, scope, name_node.start_position(), parent))
+        }
+
+        _ => {
+            error!("{} instead of constant in {file:?} at {:?}", node.kind(), node.range());
+            None
+        }
     }
+}
 
-    let node = if node.kind() == NodeKind::RestAssignment { node.child(0).unwrap() } else { *node };
+/*
+ * Creates a constant symbol scoped by the parent class or module.
+ */
+pub fn new_constant(file: &Path, name: &str, location: Point, parent: Option<Arc<RSymbol>>) -> RSymbol {
+    new_scoped_constant(file, name.into(), location, parent)
+}
 
+fn new_scoped_constant(file: &Path, constant_scope: Scope, location: Point, parent: Option<Arc<RSymbol>>) -> RSymbol {
     let parent_scope = match &parent {
         Some(p) => match &**p {
             RSymbol::Class(c) | RSymbol::Module(c) => Some(&c.scope),
@@ -22,15 +48,13 @@ pub fn parse_constant(file: &Path, source: &[u8], node: &Node, parent: Option<Ar
 
         None => None,
     };
-    let text = node.utf8_text(source).unwrap().to_string();
-
-    let scope = parent_scope.map(|s| s.join(&(&text).into())).unwrap_or_default();
+    let scope = parent_scope.map(|s| s.join(&constant_scope)).unwrap_or(constant_scope);
 
-    Some(RSymbol::Constant(RConstant {
+    RSymbol::Constant(RConstant {
         file: file.to_owned(),
         name: scope.to_string(),
         scope,
-        location: node.start_position(),
+        location,
         parent,
-    }))
+    })
 }
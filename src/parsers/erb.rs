@@ -0,0 +1,87 @@
+use std::{ffi::OsStr, path::Path};
+
+const TAG_START: &[u8] = b"<%";
+const TAG_END: &[u8] = b"%>";
+
+pub fn is_erb_file(path: &Path) -> bool {
+    path.extension().and_then(OsStr::to_str) == Some("erb")
+}
+
+/*
+ * Converts the ERB template to Ruby source with the same byte offsets: the code of `<% %>` and `<%= %>` tags
+ * is kept in place, the rest of the template is replaced by spaces (except for newlines), and every tag is
+ * terminated by ";" so the fragments don't merge into one expression. Comment tags `<%# %>` and escaped
+ * `<%%` are dropped.
+ */
+pub fn erb_to_ruby(template: &[u8]) -> Vec<u8> {
+    let mut ruby: Vec<u8> = template.iter().map(|b| if *b == b'\n' { b'\n' } else { b' ' }).collect();
+
+    let mut offset = 0;
+    while let Some(tag_start) = find(template, offset, TAG_START) {
+        let mut code_start = tag_start + TAG_START.len();
+        let tag_end = find(template, code_start, TAG_END).unwrap_or(template.len());
+        offset = tag_end + TAG_END.len();
+
+        match template.get(code_start) {
+            Some(b'%') => {
+                offset = code_start + 1;
+                continue;
+            }
+            Some(b'#') => continue,
+            _ => {}
+        }
+
+        // `<%=`, `<%==` and `<%-` only differ in the output of the code
+        while code_start < tag_end && matches!(template[code_start], b'=' | b'-') {
+            code_start += 1;
+        }
+        let code_end = if tag_end > code_start && template[tag_end - 1] == b'-' { tag_end - 1 } else { tag_end };
+
+        ruby[code_start..code_end].copy_from_slice(&template[code_start..code_end]);
+        if tag_end < template.len() {
+            ruby[tag_end] = b';';
+        }
+    }
+
+    ruby
+}
+
+fn find(source: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    source.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|i| from + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(template: &str) -> String {
+        String::from_utf8(erb_to_ruby(template.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn erb_to_ruby_test() {
+        assert_eq!("       User.name ;     ", convert("<p><%= User.name %></p>"));
+        assert_eq!(
+            "   items.each do |i| ; \n        i ;      \n   end ; ",
+            convert("<% items.each do |i| %>\n<li><%= i %></li>\n<% end %>")
+        );
+        assert_eq!("                  ", convert("<%# User.name %>ü"));
+        assert_eq!("      ", convert("<%%= a"));
+        assert_eq!("     raw  ; ", convert("<%== raw -%>"));
+    }
+
+    #[test]
+    fn erb_offsets_test() {
+        let template = "<div>\n  <%= link_to Account.name %>\n</div>\n";
+        let ruby = erb_to_ruby(template.as_bytes());
+
+        assert_eq!(template.len(), ruby.len());
+        assert_eq!(template.find("Account"), String::from_utf8(ruby).unwrap().find("Account"));
+    }
+
+    #[test]
+    fn is_erb_file_test() {
+        assert!(is_erb_file(Path::new("app/views/users/show.html.erb")));
+        assert!(!is_erb_file(Path::new("app/models/user.rb")));
+    }
+}
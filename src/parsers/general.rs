@@ -1,20 +1,34 @@
-use std::{fs, path::Path, sync::Arc};
+use std::{fs, iter, path::Path, sync::Arc};
 
 use anyhow::Result;
-use log::info;
-use tree_sitter::{Node, Parser, Tree};
+use log::{info, warn};
+use tree_sitter::{Node, Parser, Point, Tree};
 use tree_sitter_ruby::language;
 
 use crate::types::RSymbol;
 
 use super::{
-    assignments::parse_assignment,
+    assignments::{parse_assignment, parse_operator_assignment},
+    calls::{parse_alias, parse_call, parse_dsl_block},
     classes::parse_class,
+    conditionals::parse_conditional,
+    erb::{erb_to_ruby, is_erb_file},
     methods::{parse_method, parse_singleton_method},
-    types::NodeKind,
+    types::{NodeKind, NodeName},
 };
 
-pub fn parse(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Vec<Arc<RSymbol>> {
+const GRAMMAR_CHECK_SOURCE: &str = "class Foo < Bar\n  def baz(a)\n    qux.quux(a)\n  end\nend\n";
+
+/*
+ * Parses the definitions of the node and the ones nested in it, the depth is the number of the nesting levels
+ * left to descend, so that deeply nested generated code (e.g. thousands of modules) doesn't overflow the stack.
+ * Every parsed node takes a level, including the conditionals and the other nodes wrapping the definitions.
+ */
+pub fn parse(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>, depth: usize) -> Vec<Arc<RSymbol>> {
+    let Some(depth) = nested_depth(file, &node, depth) else {
+        return vec![];
+    };
+
     let node_kind = match node.kind().try_into() {
         Ok(k) => k,
         Err(_) => return vec![],
@@ -26,21 +40,51 @@ pub fn parse(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>
             vec![]
         }
 
-        NodeKind::Class | NodeKind::Module => parse_class(file, source, node, parent),
+        NodeKind::Class | NodeKind::Module => parse_class(file, source, node, parent, depth),
 
-        NodeKind::Method => {
-            vec![Arc::new(parse_method(file, source, node, parent))]
-        }
+        NodeKind::Method => parse_method(file, source, node, parent).map(Arc::new).into_iter().collect(),
 
         NodeKind::SingletonMethod => {
-            vec![Arc::new(parse_singleton_method(file, source, node, parent))]
+            parse_singleton_method(file, source, node, parent).map(Arc::new).into_iter().collect()
+        }
+
+        NodeKind::Assignment => parse_assignment(file, source, node, parent, depth).unwrap_or_default(),
+
+        NodeKind::OperatorAssignment => parse_operator_assignment(file, source, node, parent).unwrap_or_default(),
+
+        NodeKind::Call => match parse_dsl_block(file, source, node, parent.clone(), depth) {
+            Some(symbols) => symbols,
+            None => parse_call(file, source, node, parent).unwrap_or_default().into_iter().map(Arc::new).collect(),
+        },
+
+        NodeKind::Alias => parse_alias(file, source, node, parent).map(Arc::new).into_iter().collect(),
+
+        NodeKind::If
+        | NodeKind::Unless
+        | NodeKind::IfModifier
+        | NodeKind::UnlessModifier
+        | NodeKind::Elsif
+        | NodeKind::Else
+        | NodeKind::Then
+        | NodeKind::Case
+        | NodeKind::When => parse_conditional(file, source, node, parent, depth),
+
+        // `BEGIN { }` and `END { }` run at the start and the end of the program, definitions in them are top-level
+        NodeKind::BeginBlock | NodeKind::EndBlock => {
+            let mut cursor = node.walk();
+            node.named_children(&mut cursor).flat_map(|c| parse(file, source, c, parent.clone(), depth)).collect()
         }
 
-        NodeKind::Assignment => {
-            parse_assignment(file, source, node, parent).unwrap_or(Vec::new()).into_iter().map(Arc::new).collect()
+        // the data after `__END__` isn't code, even if it looks like it
+        NodeKind::Uninterpreted => vec![],
+
+        // partially parsed code of a file with syntax errors, definitions in it are indexed on the best effort basis
+        NodeKind::Error => {
+            let mut cursor = node.walk();
+            node.named_children(&mut cursor).flat_map(|c| parse(file, source, c, parent.clone(), depth)).collect()
         }
 
-        NodeKind::Comment | NodeKind::Call => {
+        NodeKind::Comment => {
             // TODO: Implement
             vec![]
         }
@@ -52,8 +96,104 @@ pub fn parse(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>
     }
 }
 
+/*
+ * Takes a nesting level to descend into the node, None if there are no levels left.
+ */
+pub fn nested_depth(file: &Path, node: &Node, depth: usize) -> Option<usize> {
+    if depth == 0 {
+        warn!("Skipping definitions nested too deep in {file:?} at {:?}", node.start_position());
+        return None;
+    }
+
+    Some(depth - 1)
+}
+
+/*
+ * Gets the name node of the symbol at the location (extended through scope resolutions, e.g. "Foo::Bar")
+ * and the node of the whole definition, e.g. the class with its body.
+ */
+pub fn get_definition_nodes(tree: &Tree, location: Point) -> Option<(Node<'_>, Node<'_>)> {
+    let mut name_node = tree.root_node().named_descendant_for_point_range(location, location)?;
+    while let Some(p) = name_node.parent().filter(|p| p.kind() == NodeKind::ScopeResolution) {
+        name_node = p;
+    }
+
+    let definition_node = iter::successors(name_node.parent(), |p| p.parent())
+        .find(|p| {
+            matches!(
+                p.kind().try_into(),
+                Ok(NodeKind::Class
+                    | NodeKind::Module
+                    | NodeKind::Method
+                    | NodeKind::SingletonMethod
+                    | NodeKind::Assignment
+                    | NodeKind::OperatorAssignment
+                    | NodeKind::Alias
+                    | NodeKind::Call)
+            )
+        })
+        .unwrap_or(name_node);
+
+    Some((name_node, definition_node))
+}
+
+/*
+ * Parses a known snippet and checks that the node kinds and field names the parsers rely on
+ * are still produced by the grammar, so an incompatible tree-sitter-ruby doesn't result in a silently empty index.
+ */
+pub fn check_grammar() -> Result<()> {
+    let mut parser = Parser::new();
+    parser.set_language(language())?;
+    let tree =
+        parser.parse(GRAMMAR_CHECK_SOURCE, None).ok_or_else(|| anyhow!("Failed to parse the grammar check snippet"))?;
+
+    let mut missing = Vec::new();
+    let class = check_node(tree.root_node().named_child(0), NodeKind::Class, "class", &mut missing);
+    for field in [NodeName::Name, NodeName::Superclass, NodeName::Body] {
+        check_field(class, field, "class", &mut missing);
+    }
+
+    let method_node = class.and_then(|c| c.child_by_field_name(NodeName::Body)).and_then(|b| b.named_child(0));
+    let method = check_node(method_node, NodeKind::Method, "method", &mut missing);
+    for field in [NodeName::Name, NodeName::Parameters, NodeName::Body] {
+        check_field(method, field, "method", &mut missing);
+    }
+
+    let call_node = method.and_then(|m| m.child_by_field_name(NodeName::Body)).and_then(|b| b.named_child(0));
+    let call = check_node(call_node, NodeKind::Call, "call", &mut missing);
+    for field in [NodeName::Receiver, NodeName::Method] {
+        check_field(call, field, "call", &mut missing);
+    }
+
+    if !missing.is_empty() {
+        bail!("Incompatible tree-sitter-ruby grammar, missing: {}", missing.join(", "));
+    }
+
+    Ok(())
+}
+
+fn check_node<'a>(node: Option<Node<'a>>, kind: NodeKind, label: &str, missing: &mut Vec<String>) -> Option<Node<'a>> {
+    let node = node.filter(|n| n.kind() == kind);
+    if node.is_none() {
+        missing.push(format!("{label} node"));
+    }
+
+    node
+}
+
+fn check_field(node: Option<Node>, field: NodeName, label: &str, missing: &mut Vec<String>) {
+    if let Some(node) = node {
+        if node.child_by_field_name(&field).is_none() {
+            missing.push(format!("{label}.{field}"));
+        }
+    }
+}
+
 pub fn read_file_tree(path: &Path) -> Result<(Tree, Vec<u8>)> {
-    let source = fs::read(path)?;
+    let mut source = fs::read(path)?;
+    if is_erb_file(path) {
+        source = erb_to_ruby(&source);
+    }
 
     let mut parser = Parser::new();
     parser.set_language(language())?;
@@ -61,3 +201,13 @@ pub fn read_file_tree(path: &Path) -> Result<(Tree, Vec<u8>)> {
 
     Ok((tree, source))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_grammar_test() {
+        assert!(check_grammar().is_ok());
+    }
+}
@@ -1,12 +1,17 @@
+use log::debug;
 use tree_sitter::Node;
 
 use crate::parsers::types::NodeKind;
 
+/*
+ * Gets the method, class, module or program the identifier is used in, None for the other kinds of nodes.
+ */
 pub fn get_identifier_context<'a>(node: &Node<'a>) -> Option<Node<'a>> {
-    let node_kind = node.kind().try_into();
-    assert!(node_kind.is_ok());
-    let node_kind: NodeKind = node_kind.unwrap();
-    assert!(node_kind == NodeKind::Identifier);
+    // constants are method names of the calls like `Array(x)`
+    if !matches!(node.kind().try_into(), Ok(NodeKind::Identifier | NodeKind::Constant)) {
+        debug!("Not an identifier at {:?}: {}", node.start_position(), node.kind());
+        return None;
+    }
 
     let mut parent = node.parent();
     while let Some(p) = parent {
@@ -14,11 +19,12 @@ pub fn get_identifier_context<'a>(node: &Node<'a>) -> Option<Node<'a>> {
             Err(_) => parent = p.parent(),
 
             Ok(k) => match k {
-                NodeKind::Call => return Some(p),
                 NodeKind::Method => return Some(p),
                 NodeKind::SingletonMethod => return Some(p),
                 NodeKind::Class => return Some(p),
                 NodeKind::Module => return Some(p),
+                // top-level code of a script or an ERB template
+                NodeKind::Program => return Some(p),
 
                 _ => parent = p.parent(),
             },
@@ -27,3 +33,44 @@ pub fn get_identifier_context<'a>(node: &Node<'a>) -> Option<Node<'a>> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::parsers::general::read_file_tree;
+
+    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    #[test]
+    fn single_line_method_context_test() {
+        // the class and the method span the same line, the innermost one has to be chosen
+        let (tree, _) = read_file_tree(&Path::new(FIXTURES_DIR).join("local_variables/square.rb")).unwrap();
+        let point = Point {
+            row: 0,
+            column: 37,
+        };
+        let node = tree.root_node().descendant_for_point_range(point, point).unwrap();
+
+        let context = get_identifier_context(&node).unwrap();
+
+        assert_eq!(NodeKind::Method.as_ref(), context.kind());
+        assert_eq!(
+            Point {
+                row: 0,
+                column: 14
+            },
+            context.start_position()
+        );
+    }
+
+    #[test]
+    fn non_identifier_context_test() {
+        let (tree, _) = read_file_tree(&Path::new(FIXTURES_DIR).join("local_variables/square.rb")).unwrap();
+
+        assert_eq!(None, get_identifier_context(&tree.root_node()));
+    }
+}
@@ -1,18 +1,42 @@
 use std::{path::Path, sync::Arc};
 
 use log::{error, info, warn};
-use tree_sitter::{Node, Query, QueryCursor};
+use tree_sitter::{Node, Point, Query, QueryCursor};
 
 use itertools::Itertools;
 
 use crate::{
-    parsers::types::{NodeKind, NodeName, Scope, SCOPE_DELIMITER},
-    types::{MethodParam, RMethod, RMethodParam, RSymbol},
+    parsers::{
+        calls::parse_open_struct_readers,
+        identifiers::get_identifier_context,
+        types::{NodeKind, NodeName, Scope, SCOPE_DELIMITER},
+    },
+    types::{MethodParam, RMethod, RMethodParam, RSymbol, Visibility},
 };
 
-pub fn parse_method(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> RSymbol {
+/*
+ * Parses the method definition, malformed ones (e.g. without a name) are skipped.
+ */
+pub fn parse_method(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<RSymbol> {
     assert!(node.kind() == NodeKind::Method || node.kind() == NodeKind::SingletonMethod);
 
+    let name_node = node.child_by_field_name(NodeName::Name)?;
+    let method_name = name_node.utf8_text(source).ok()?;
+    let params = parse_method_params(file, source, &node);
+
+    Some(new_method(file, method_name, name_node.start_position(), params, parent))
+}
+
+/*
+ * Creates a method symbol scoped by the parent class or module.
+ */
+pub fn new_method(
+    file: &Path,
+    method_name: &str,
+    location: Point,
+    parameters: Vec<RMethodParam>,
+    parent: Option<Arc<RSymbol>>,
+) -> RSymbol {
     let scope = match &parent {
         Some(p) => match &**p {
             RSymbol::Class(c) | RSymbol::Module(c) => Some(&c.scope),
@@ -22,78 +46,92 @@ pub fn parse_method(file: &Path, source: &[u8], node: Node, parent: Option<Arc<R
         None => None,
     };
 
-    let name_node = node.child_by_field_name(NodeName::Name).unwrap();
-    let name = name_node.utf8_text(source).unwrap().to_string();
     let name = match scope {
-        Some(s) => s.to_string() + SCOPE_DELIMITER + &name,
-        None => name,
+        Some(s) => s.to_string() + SCOPE_DELIMITER + method_name,
+        None => method_name.to_string(),
     };
 
-    let mut params: Vec<RMethodParam> = Vec::new();
-
-    for param in get_method_param_nodes(file, &node) {
-        let param = match param.kind().try_into().unwrap() {
-            NodeKind::Identifier => {
-                let name = param.utf8_text(source).unwrap().to_string();
-
-                RMethodParam::Regular(MethodParam {
-                    file: file.to_path_buf(),
-                    name,
-                    location: param.start_position(),
-                })
-            }
-
-            NodeKind::OptionalParameter => {
-                let name_node = param.child_by_field_name(NodeName::Name).unwrap();
-                let name = name_node.utf8_text(source).unwrap().to_string();
-                RMethodParam::Optional(MethodParam {
-                    file: file.to_path_buf(),
-                    name,
-                    location: param.start_position(),
-                })
-            }
-            NodeKind::KeywordParameter => {
-                let name_node = param.child_by_field_name(NodeName::Name).unwrap();
-                let name = name_node.utf8_text(source).unwrap().to_string();
-                RMethodParam::Keyword(MethodParam {
-                    file: file.to_path_buf(),
-                    name,
-                    location: param.start_position(),
-                })
-            }
-
-            _ => unreachable!(),
-        };
-
-        params.push(param);
-    }
-
-    let scope = scope.map(|s| s.join(&(&name).into())).unwrap_or(Scope::from(&name));
+    let scope = scope.map(|s| s.join(&method_name.into())).unwrap_or(Scope::from(method_name));
 
     RSymbol::Method(RMethod {
         file: file.to_owned(),
         name,
         scope,
-        location: name_node.start_position(),
-        parameters: params,
+        location,
+        parameters,
+        visibility: Visibility::Public,
         parent,
     })
 }
 
-pub fn parse_singleton_method(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> RSymbol {
-    match parse_method(file, source, node, parent) {
-        RSymbol::Method(method) => RSymbol::SingletonMethod(method),
-        _ => unreachable!(),
+/*
+ * Parses parameters of the node with the "parameters" field, e.g. a method or a block.
+ */
+pub fn parse_method_params(file: &Path, source: &[u8], node: &Node) -> Vec<RMethodParam> {
+    get_method_param_nodes(file, node)
+        .into_iter()
+        .filter_map(|param| {
+            let name = get_param_name_node(&param)?.utf8_text(source).ok()?.to_string();
+            let method_param = MethodParam {
+                file: file.to_path_buf(),
+                name,
+                location: param.start_position(),
+            };
+
+            match param.kind().try_into().ok()? {
+                NodeKind::Identifier => Some(RMethodParam::Regular(method_param)),
+                NodeKind::OptionalParameter => Some(RMethodParam::Optional(method_param)),
+                NodeKind::KeywordParameter => Some(RMethodParam::Keyword(method_param)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+pub fn parse_singleton_method(file: &Path, source: &[u8], node: Node, parent: Option<Arc<RSymbol>>) -> Option<RSymbol> {
+    match parse_method(file, source, node, parent)? {
+        RSymbol::Method(method) => Some(RSymbol::SingletonMethod(method)),
+        _ => None,
     }
 }
 
+/*
+ * Parses readers of OpenStructs assigned to instance variables in the method body,
+ * e.g. `@options = OpenStruct.new(verbose: true)`, scoped by the class and the variable.
+ * They aren't indexed, the finder parses them when a reader is called on the variable.
+ */
+pub fn parse_instance_variable_readers(file: &Path, source: &[u8], node: Node, class_scope: &Scope) -> Vec<RSymbol> {
+    let body = match node.child_by_field_name(NodeName::Body) {
+        Some(b) => b,
+        None => return vec![],
+    };
+
+    let mut cursor = body.walk();
+    body.named_children(&mut cursor)
+        .filter(|n| n.kind() == NodeKind::Assignment)
+        .flat_map(|assignment| {
+            let variable =
+                assignment.child_by_field_name(NodeName::Left).filter(|l| l.kind() == NodeKind::InstanceVariable);
+            let value = assignment.child_by_field_name(NodeName::Right);
+
+            match (variable.and_then(|v| v.utf8_text(source).ok()), value) {
+                (Some(variable), Some(value)) => {
+                    let scope = class_scope.join(&variable.into());
+                    parse_open_struct_readers(file, source, value, &scope, None, RSymbol::Method)
+                }
+                _ => vec![],
+            }
+        })
+        .collect()
+}
+
 pub fn get_method_variable_definition<'a>(
     node: &Node<'a>,
     context: &Node<'a>,
     context_file: &Path,
     source: &[u8],
 ) -> Option<Node<'a>> {
-    let variable_name = node.utf8_text(source).unwrap();
+    let variable_name = node.utf8_text(source).ok()?;
 
     let mut cursor = context.walk();
     if !cursor.goto_first_child() {
@@ -108,66 +146,77 @@ pub fn get_method_variable_definition<'a>(
             right: (_)) @assignment
         "#
     );
-    // TODO: handle unwrap
-    let query = Query::new(tree_sitter_ruby::language(), query.as_str()).unwrap();
+    let query = Query::new(tree_sitter_ruby::language(), query.as_str()).ok()?;
 
+    let variable_index = query.capture_index_for_name("variable")?;
+
+    // the assignment has to be finished before the node, e.g. in "a = a + 1" the right "a" isn't defined by it
     let closest_assignment = QueryCursor::new()
         .matches(&query, *context, source)
         .flat_map(|m| m.captures)
+        .filter(|c| c.index == variable_index)
         .map(|c| c.node)
-        .filter(|n| n.range() < node.range())
+        .filter(|n| n.parent().map(|p| p.end_byte() <= node.start_byte()).unwrap_or(false))
+        // top-level and class body variables aren't visible in the methods and vice versa
+        .filter(|n| {
+            !matches!(context.kind().try_into(), Ok(NodeKind::Program | NodeKind::Class | NodeKind::Module))
+                || get_identifier_context(n).as_ref() == Some(context)
+        })
         .sorted_by_key(|n| n.range())
         .last();
     // TODO: determine reachability from assignment to node (e.g. if assignment is not in the
     // correct if branch)
 
     match closest_assignment {
-        Some(n) => return Some(n),
+        Some(n) => Some(n),
 
         None => {
             info!("Variable assignment for '{variable_name}' wasn't found in the method body, checking method params");
 
-            // check method params
-            for param_node in get_method_param_nodes(context_file, context) {
-                info!("param_node: {param_node:?}");
-                match param_node.kind().try_into().unwrap() {
-                    NodeKind::Identifier => {
-                        let param_name = param_node.utf8_text(source).unwrap();
-
-                        info!("param name: {param_name}");
+            find_param_node(context, variable_name, context_file, source)
+        }
+    }
+}
 
-                        if param_name == variable_name {
-                            return Some(param_node);
-                        }
-                    }
-                    NodeKind::OptionalParameter => {
-                        let name_node = param_node.child_by_field_name(NodeName::Name).unwrap();
-                        let name = name_node.utf8_text(source).unwrap().to_string();
+/*
+ * Gets the parameter of the enclosing blocks and lambdas (e.g. "a" in "->(a) { a }" or "{ |a| a }")
+ * defining the variable. Search stops at the enclosing method since blocks don't see through it.
+ */
+pub fn get_block_variable_definition<'a>(node: &Node<'a>, file: &Path, source: &[u8]) -> Option<Node<'a>> {
+    let variable_name = node.utf8_text(source).ok()?;
+
+    let mut parent = node.parent();
+    while let Some(p) = parent {
+        match p.kind().try_into() {
+            Ok(NodeKind::Block | NodeKind::DoBlock | NodeKind::Lambda) => {
+                if let Some(param_node) = find_param_node(&p, variable_name, file, source) {
+                    return Some(param_node);
+                }
+            }
 
-                        info!("param name: {name}");
+            Ok(NodeKind::Method | NodeKind::SingletonMethod | NodeKind::Class | NodeKind::Module) => return None,
 
-                        if name == variable_name {
-                            return Some(param_node);
-                        }
-                    }
-                    NodeKind::KeywordParameter => {
-                        let name_node = param_node.child_by_field_name(NodeName::Name).unwrap();
-                        let name = name_node.utf8_text(source).unwrap().to_string();
+            _ => {}
+        }
 
-                        info!("param name: {name}");
+        parent = p.parent();
+    }
 
-                        if name == variable_name {
-                            return Some(param_node);
-                        }
-                    }
+    None
+}
 
-                    _ => unreachable!(),
-                }
-            }
-        }
-    };
+fn find_param_node<'a>(node: &Node<'a>, variable_name: &str, file: &Path, source: &[u8]) -> Option<Node<'a>> {
+    get_method_param_nodes(file, node).into_iter().find(|param_node| {
+        get_param_name_node(param_node).and_then(|n| n.utf8_text(source).ok()) == Some(variable_name)
+    })
+}
 
-    None
+fn get_param_name_node<'a>(param_node: &Node<'a>) -> Option<Node<'a>> {
+    match param_node.kind().try_into().ok()? {
+        NodeKind::Identifier => Some(*param_node),
+        NodeKind::OptionalParameter | NodeKind::KeywordParameter => param_node.child_by_field_name(NodeName::Name),
+        _ => None,
+    }
 }
 
 fn get_method_param_nodes<'a>(file: &Path, method_node: &Node<'a>) -> Vec<Node<'a>> {
@@ -195,3 +244,57 @@ fn get_method_param_nodes<'a>(file: &Path, method_node: &Node<'a>) -> Vec<Node<'
 
     params
 }
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::{Parser, Tree};
+
+    use super::*;
+
+    fn parse_tree(source: &str) -> Tree {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ruby::language()).unwrap();
+        parser.parse(source, None).unwrap()
+    }
+
+    fn param_names(source: &str, node: &Node) -> Vec<String> {
+        get_method_param_nodes(Path::new("foo.rb"), node)
+            .iter()
+            .map(|n| n.utf8_text(source.as_bytes()).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn method_param_nodes_test() {
+        let source = "def foo(a, b); end";
+        let tree = parse_tree(source);
+        let method = tree.root_node().named_child(0).unwrap();
+
+        assert_eq!(NodeKind::Method, method.kind().try_into().unwrap());
+        assert_eq!(vec!["a", "b"], param_names(source, &method));
+    }
+
+    #[test]
+    fn param_kinds_test() {
+        let source = "def self.foo(a, b = 1, c:, d: 2); end";
+        let tree = parse_tree(source);
+        let method = tree.root_node().named_child(0).unwrap();
+
+        assert_eq!(vec!["a", "b = 1", "c:", "d: 2"], param_names(source, &method));
+
+        let params = parse_method_params(Path::new("foo.rb"), source.as_bytes(), &method);
+        assert!(matches!(
+            params[..],
+            [RMethodParam::Regular(_), RMethodParam::Optional(_), RMethodParam::Keyword(_), RMethodParam::Keyword(_)]
+        ));
+    }
+
+    #[test]
+    fn block_param_nodes_test() {
+        let source = "foo { |a, b| a }";
+        let tree = parse_tree(source);
+        let block = tree.root_node().named_child(0).unwrap().child_by_field_name(NodeName::Block).unwrap();
+
+        assert_eq!(vec!["a", "b"], param_names(source, &block));
+    }
+}
@@ -1,8 +1,13 @@
 pub mod assignments;
+pub mod calls;
 pub mod classes;
+pub mod comments;
+pub mod conditionals;
 pub mod constants;
+pub mod erb;
 pub mod general;
 pub mod identifiers;
 pub mod methods;
+pub mod references;
 pub mod scopes;
 pub mod types;
@@ -0,0 +1,104 @@
+use tree_sitter::Node;
+
+use crate::parsers::types::{NodeKind, NodeName};
+use crate::types::RReferences;
+
+/*
+ * Collects positions of the method calls and constants in the tree, grouped by name,
+ * so references can be looked up without scanning every file again.
+ */
+pub fn parse_references(source: &[u8], node: Node) -> RReferences {
+    let mut references = RReferences::new();
+    let mut cursor = node.walk();
+    let mut visited_children = false;
+
+    loop {
+        let node = cursor.node();
+
+        if !visited_children {
+            if is_reference(&node) {
+                if let Ok(name) = node.utf8_text(source) {
+                    references.entry(name.to_string()).or_default().push(node.start_position());
+                }
+            }
+
+            if cursor.goto_first_child() {
+                continue;
+            }
+        }
+
+        if cursor.goto_next_sibling() {
+            visited_children = false;
+        } else if cursor.goto_parent() {
+            visited_children = true;
+        } else {
+            break;
+        }
+    }
+
+    references
+}
+
+/*
+ * Constants and method names of calls are references, identifiers can be local variables, so only
+ * the ones called with arguments or a receiver are considered.
+ */
+fn is_reference(node: &Node) -> bool {
+    if node.kind() == NodeKind::Constant {
+        return true;
+    }
+
+    node.kind() == NodeKind::Identifier
+        && node
+            .parent()
+            .filter(|p| p.kind() == NodeKind::Call)
+            .and_then(|p| p.child_by_field_name(NodeName::Method))
+            .map(|m| m == *node)
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::{Parser, Point};
+
+    use super::*;
+
+    #[test]
+    fn parse_references_test() {
+        let source = "class Foo\n  def bar\n    Baz.call(bar)\n    @x.call\n  end\nend\n";
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ruby::language()).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let references = parse_references(source.as_bytes(), tree.root_node());
+
+        assert_eq!(
+            Some(&vec![Point {
+                row: 0,
+                column: 6
+            }]),
+            references.get("Foo")
+        );
+        assert_eq!(
+            Some(&vec![Point {
+                row: 2,
+                column: 4
+            }]),
+            references.get("Baz")
+        );
+        assert_eq!(
+            Some(&vec![
+                Point {
+                    row: 2,
+                    column: 8
+                },
+                Point {
+                    row: 3,
+                    column: 7
+                }
+            ]),
+            references.get("call")
+        );
+        assert_eq!(None, references.get("bar"));
+    }
+}
@@ -1,7 +1,10 @@
 use log::warn;
 use tree_sitter::Node;
 
-use crate::parsers::types::GLOBAL_SCOPE_VALUE;
+use crate::parsers::{
+    calls::get_class_new_kind,
+    types::{GLOBAL_SCOPE_VALUE, OBJECT_SCOPE_VALUE},
+};
 
 use super::types::{NodeKind, NodeName, Scope};
 
@@ -36,10 +39,17 @@ pub fn get_context_scope(node: &Node, source: &[u8]) -> Scope {
 
             Ok(nk) => match nk {
                 NodeKind::Class | NodeKind::Module => {
-                    let class_name_node = p.child_by_field_name(NodeName::Name).unwrap();
-                    let class_scopes = get_full_scope_resolution(&class_name_node, source);
+                    if let Some(class_name_node) = p.child_by_field_name(NodeName::Name) {
+                        scopes.push(get_full_scope_resolution(&class_name_node, source));
+                    }
+
+                    parent = p.parent()
+                }
 
-                    scopes.push(class_scopes);
+                NodeKind::Block | NodeKind::DoBlock => {
+                    if let Some(constant) = get_class_new_constant(&p, source) {
+                        scopes.extend(constant.utf8_text(source).ok().map(Scope::from));
+                    }
 
                     parent = p.parent()
                 }
@@ -54,84 +64,108 @@ pub fn get_context_scope(node: &Node, source: &[u8]) -> Scope {
     // Scope::new(scopes.into_iter().rev().flatten().collect())
 }
 
+/*
+ * Gets the constant the block of `Class.new` or `Module.new` is assigned to, e.g. Foo in "Foo = Class.new do".
+ */
+fn get_class_new_constant<'a>(block: &Node<'a>, source: &[u8]) -> Option<Node<'a>> {
+    let call = block.parent().filter(|c| c.child_by_field_name(NodeName::Block) == Some(*block))?;
+    get_class_new_kind(&call, source)?;
+
+    let assignment = call.parent().filter(|p| p.kind() == NodeKind::Assignment)?;
+    assignment.child_by_field_name(NodeName::Left).filter(|l| l.kind() == NodeKind::Constant)
+}
+
 /*
  * Get the scope prior to the constant, e.g. if node is B in A::B::C the function will return [B, A].
+ * Shapes that can't be resolved statically (e.g. `self.class::A`) give an empty scope.
  */
 pub fn get_parent_scope_resolution(node: &Node, source: &[u8]) -> Scope {
-    let node = if node.kind() == NodeKind::ScopeResolution {
-        node.child_by_field_name(NodeName::Name).unwrap()
-    } else {
-        *node
-    };
+    match try_get_parent_scope_resolution(node, source) {
+        Some(scope) => scope,
+        None => {
+            warn!(
+                "Couldn't get parent scope resolution of {} at {:?}: {:?}",
+                node.kind(),
+                node.start_position(),
+                node.utf8_text(source)
+            );
+            Scope::new(vec![])
+        }
+    }
+}
 
-    assert!(node.kind() == NodeKind::Constant);
+fn try_get_parent_scope_resolution(node: &Node, source: &[u8]) -> Option<Scope> {
+    let node = if node.kind() == NodeKind::ScopeResolution { node.child_by_field_name(NodeName::Name)? } else { *node };
 
-    let parent = node.parent().unwrap();
+    if node.kind() != NodeKind::Constant {
+        return None;
+    }
+
+    let parent = node.parent()?;
     if parent.kind() != NodeKind::ScopeResolution {
         // single constant without a scope
-        return Scope::from(node.utf8_text(source).unwrap());
+        return Some(Scope::from(node.utf8_text(source).ok()?));
     }
 
     let scope_node = parent.child_by_field_name(NodeName::Scope);
-    let name_node = parent.child_by_field_name(NodeName::Name).unwrap();
+    let name_node = parent.child_by_field_name(NodeName::Name)?;
     let is_scope = scope_node.map(|n| n.range() == node.range()).unwrap_or(false);
     let is_name = name_node.range() == node.range();
-    assert!(is_scope || is_name);
 
     // it's the first constant in the "scope_resolution", just return it (e.g. A in A::B::C)
     if is_scope {
-        return Scope::from(node.utf8_text(source).unwrap());
+        return Some(Scope::from(node.utf8_text(source).ok()?));
+    }
+
+    if !is_name {
+        return None;
     }
 
     // go down from the current node to get scopes on the left (e.g. A::B::C in A::B::C::D if
     // cursor is on C)
-    let mut scopes = Vec::new();
-    let parent = node.parent();
-    if let Some(p) = parent {
-        // if let + condition is in nightly only
-        if p.kind() == NodeKind::ScopeResolution {
-            let name = p.child_by_field_name(NodeName::Name).unwrap();
-            scopes.push(name.utf8_text(source).unwrap());
-
-            let mut scope = p.child_by_field_name(NodeName::Scope);
-            while let Some(s) = scope {
-                match s.kind().try_into().unwrap() {
-                    NodeKind::ScopeResolution => {
-                        let name = s.child_by_field_name(NodeName::Name).unwrap();
-                        scopes.push(name.utf8_text(source).unwrap());
-                        let new_scope = s.child_by_field_name(NodeName::Scope);
+    let mut scopes = vec![name_node.utf8_text(source).ok()?];
 
-                        if new_scope.is_none() {
-                            scopes.push(GLOBAL_SCOPE_VALUE);
-                        }
+    let mut scope = scope_node;
+    // top-level constant without any scopes on the left (e.g. ::A)
+    if scope.is_none() {
+        scopes.push(GLOBAL_SCOPE_VALUE);
+    }
 
-                        scope = new_scope
-                    }
+    while let Some(s) = scope {
+        match s.kind().try_into().ok()? {
+            NodeKind::ScopeResolution => {
+                let name = s.child_by_field_name(NodeName::Name)?;
+                scopes.push(name.utf8_text(source).ok()?);
+                let new_scope = s.child_by_field_name(NodeName::Scope);
 
-                    NodeKind::Constant => {
-                        scopes.push(s.utf8_text(source).unwrap());
-                        break;
-                    }
+                if new_scope.is_none() {
+                    scopes.push(GLOBAL_SCOPE_VALUE);
+                }
 
-                    // weird module definitions with variables
-                    NodeKind::ClassVariable | NodeKind::InstanceVariable => {
-                        warn!(
-                            "Couldn't get parent scope resolution for definition: {}",
-                            node.utf8_text(source).unwrap()
-                        );
-                        return Scope::new(vec![]);
-                    }
+                scope = new_scope
+            }
 
-                    _ => {
-                        panic!("Impossible kind in scope resolution: {}: {}", p.to_sexp(), p.utf8_text(source).unwrap())
-                    }
-                }
+            NodeKind::Constant => {
+                scopes.push(s.utf8_text(source).ok()?);
+                break;
             }
+
+            // weird module definitions with variables or constants qualified by a variable (e.g. klass::A),
+            // calls (e.g. self.class::A) and other expressions
+            _ => return None,
         }
     }
 
     scopes.reverse();
-    Scope::from(scopes)
+
+    // top-level constants are defined on Object, so Object::A (and ::Object::A) is the same as ::A
+    if scopes.len() > 1 && scopes[0] == OBJECT_SCOPE_VALUE {
+        scopes[0] = GLOBAL_SCOPE_VALUE;
+    } else if scopes.len() > 2 && scopes[0] == GLOBAL_SCOPE_VALUE && scopes[1] == OBJECT_SCOPE_VALUE {
+        scopes.remove(1);
+    }
+
+    Some(Scope::from(scopes))
 }
 
 /*
@@ -187,6 +221,24 @@ pub fn get_full_scope_resolution(node: &Node, source: &[u8]) -> Scope {
     parent_scopes.join(&child_scopes)
 }
 
+/*
+ * Checks if the node is a constant or a scope resolution consisting of constants only (e.g. A::B::C).
+ */
+pub fn is_constant_path(node: &Node) -> bool {
+    match node.kind().try_into() {
+        Ok(NodeKind::Constant) => true,
+        Ok(NodeKind::ScopeResolution) => {
+            let scope_is_constant =
+                node.child_by_field_name(NodeName::Scope).map(|s| is_constant_path(&s)).unwrap_or(true);
+            let name_is_constant = node.child_by_field_name(NodeName::Name).map(|n| n.kind() == NodeKind::Constant);
+
+            scope_is_constant && name_is_constant.unwrap_or(false)
+        }
+
+        _ => false,
+    }
+}
+
 /*
  * Get combined context scope and full scope resolution.
  */
@@ -197,7 +249,7 @@ pub fn get_full_and_context_scope(node: &Node, source: &[u8]) -> Scope {
         return full_scope;
     }
 
-    get_context_scope(node, source)
+    get_context_scope(node, source).join(&full_scope)
 }
 
 #[cfg(test)]
@@ -305,6 +357,31 @@ end
 
             test(SOURCE, &point, &expected_scopes, |n| get_parent_scope_resolution(n, SOURCE.as_bytes()))
         }
+
+        #[test]
+        fn get_parent_scope_resolution_test_5() {
+            let point = Point {
+                row: 7,
+                column: 26,
+            };
+            let expected_scopes = vec!["$GLOBAL", "G"];
+
+            test(SOURCE, &point, &expected_scopes, |n| get_parent_scope_resolution(n, SOURCE.as_bytes()))
+        }
+
+        #[test]
+        fn dynamic_scope_test() {
+            let source = "self.class::LIMIT\nfoo(1)::BAR::BAZ\n@klass::X\n";
+            let points = [(0, 12), (1, 8), (1, 13), (2, 8)];
+
+            for (row, column) in points {
+                let point = Point {
+                    row,
+                    column,
+                };
+                test(source, &point, &[], |n| get_parent_scope_resolution(n, source.as_bytes()))
+            }
+        }
     }
 
     #[cfg(test)]
@@ -523,7 +600,7 @@ end
         }
     }
 
-    fn test<'a, F>(source: &str, point: &Point, expected_values: &[&'a str], f: F)
+    fn test<F>(source: &str, point: &Point, expected_values: &[&str], f: F)
     where
         F: FnOnce(&Node) -> Scope,
     {
@@ -5,6 +5,9 @@ use itertools::Itertools;
 pub const SCOPE_DELIMITER: &str = "::";
 
 pub const GLOBAL_SCOPE_VALUE: &str = "$GLOBAL";
+pub const OBJECT_SCOPE_VALUE: &str = "Object";
+pub const KERNEL_SCOPE_VALUE: &str = "Kernel";
+pub const DATA_SCOPE_VALUE: &str = "Data";
 
 #[derive(PartialEq, Eq, Debug, EnumString, AsRefStr, IntoStaticStr, Display)]
 #[strum(serialize_all = "snake_case")]
@@ -14,6 +17,7 @@ pub enum NodeKind {
     Method,
     SingletonMethod,
     Assignment,
+    OperatorAssignment,
     Program,
     Comment,
     Call,
@@ -27,6 +31,36 @@ pub enum NodeKind {
     RestAssignment,
     OptionalParameter,
     KeywordParameter,
+    #[strum(serialize = "self")]
+    SelfNode,
+    SimpleSymbol,
+    Block,
+    DoBlock,
+    Lambda,
+    Binary,
+    Unary,
+    ElementReference,
+    Pair,
+    HashKeySymbol,
+    String,
+    StringContent,
+    If,
+    Unless,
+    IfModifier,
+    UnlessModifier,
+    Elsif,
+    Else,
+    Then,
+    Case,
+    When,
+    #[strum(serialize = "ERROR")]
+    Error,
+    Alias,
+    ArgumentList,
+    BodyStatement,
+    BeginBlock,
+    EndBlock,
+    Uninterpreted,
 }
 
 impl PartialEq<NodeKind> for &str {
@@ -47,6 +81,16 @@ pub enum NodeName {
     Parameters,
     Receiver,
     Method,
+    Arguments,
+    Block,
+    Operator,
+    Operand,
+    Object,
+    Right,
+    Key,
+    Condition,
+    Value,
+    Pattern,
 }
 
 impl AsRef<[u8]> for NodeName {
@@ -55,15 +99,11 @@ impl AsRef<[u8]> for NodeName {
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub struct Scope {
     scopes: Vec<String>,
 }
 
-pub trait ScopeJoin<T> {
-    fn join(&self, rhs: T) -> Scope;
-}
-
 impl Scope {
     pub fn new(scopes: Vec<String>) -> Scope {
         Scope {
@@ -76,13 +116,28 @@ impl Scope {
     }
 
     pub fn join(&self, rhs: &Scope) -> Scope {
-        let rhs = if rhs.is_global() { rhs.scopes.iter().skip(1) } else { rhs.scopes.iter().skip(0) };
+        let skip = if rhs.is_global() { 1 } else { 0 };
 
-        let new_scopes = self.scopes.iter().chain(rhs).cloned().collect();
+        let new_scopes = self.scopes.iter().chain(rhs.scopes.iter().skip(skip)).cloned().collect();
 
         Scope::new(new_scopes)
     }
 
+    pub fn len(&self) -> usize {
+        self.scopes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /*
+     * Gets the first n scopes, e.g. [A, B] for n = 2 in A::B::C.
+     */
+    pub fn prefix(&self, n: usize) -> Scope {
+        Scope::new(self.scopes.iter().take(n).cloned().collect())
+    }
+
     pub fn last(&self) -> Option<&str> {
         self.scopes.last().map(|s| s.as_str())
     }
@@ -90,6 +145,20 @@ impl Scope {
     pub fn remove_last(&mut self) {
         self.scopes.pop();
     }
+
+    /*
+     * Checks if the scope ends with the other one, e.g. A::B::C ends with B::C.
+     */
+    pub fn ends_with(&self, other: &Scope) -> bool {
+        self.scopes.ends_with(&other.scopes)
+    }
+
+    /*
+     * Gets the number of the leading scopes shared with the other one, e.g. 1 for A::B::C and A::D.
+     */
+    pub fn common_prefix_len(&self, other: &Scope) -> usize {
+        self.scopes.iter().zip(other.scopes.iter()).take_while(|(a, b)| a == b).count()
+    }
 }
 
 impl From<String> for Scope {
@@ -147,6 +216,12 @@ impl PartialEq<&[&str]> for Scope {
     }
 }
 
+impl PartialEq<Scope> for &[&str] {
+    fn eq(&self, other: &Scope) -> bool {
+        other == self
+    }
+}
+
 impl Default for Scope {
     fn default() -> Self {
         Self::new(vec![])
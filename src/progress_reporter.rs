@@ -14,7 +14,7 @@ pub struct ProgressReporter<'a> {
 
 #[allow(dead_code)]
 impl<'a> ProgressReporter<'a> {
-    pub fn new(sender: &Sender<Message>) -> ProgressReporter {
+    pub fn new(sender: &Sender<Message>) -> ProgressReporter<'_> {
         ProgressReporter {
             sender,
             token_counter: Cell::new(0),
@@ -0,0 +1,156 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use log::{info, warn};
+use walkdir::WalkDir;
+
+use crate::parsers::types::SCOPE_DELIMITER;
+
+/*
+ * Method signatures of the RBS files (e.g. in "sig/") by the fully qualified method name,
+ * "Foo::Bar#baz" for the instance methods and "Foo::Bar.baz" for the singleton ones, see `Finder::find_by_fqn`.
+ * Only the method definitions are read, every overload is a separate signature, e.g. "(Integer total) -> void".
+ */
+#[derive(Debug, Default)]
+pub struct RbsSignatures {
+    signatures: HashMap<String, Vec<String>>,
+}
+
+impl RbsSignatures {
+    /*
+     * Reads the signatures of all the .rbs files in the directory, the unreadable ones are skipped.
+     */
+    pub fn index_dir(dir: &Path) -> RbsSignatures {
+        let mut signatures = RbsSignatures::default();
+        for entry in WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|e| e == "rbs"))
+        {
+            match fs::read_to_string(entry.path()) {
+                Ok(source) => signatures.parse(&source),
+                Err(e) => warn!("Failed to read signatures of {:?}: {e}", entry.path()),
+            }
+        }
+        info!("Read signatures of {} methods from {dir:?}", signatures.signatures.len());
+
+        signatures
+    }
+
+    pub fn get(&self, fqn: &str) -> &[String] {
+        self.signatures.get(fqn).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /*
+     * Parses the declarations line by line: classes and modules nest the scope until their `end`,
+     * interfaces are skipped since their methods don't belong to any class, and the overloads of a method
+     * start with "|" on the following lines. Signatures spanning several lines aren't supported.
+     */
+    fn parse(&mut self, source: &str) {
+        // None for the declarations which aren't scopes of methods, e.g. interfaces
+        let mut scopes: Vec<Option<String>> = Vec::new();
+        let mut last_methods: Vec<String> = Vec::new();
+
+        for line in source.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(overload) = line.strip_prefix('|') {
+                for fqn in &last_methods {
+                    self.signatures.entry(fqn.clone()).or_default().push(overload.trim().to_string());
+                }
+                continue;
+            }
+            last_methods.clear();
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                // aliases don't have a body, e.g. "class Foo = Bar"
+                Some("class" | "module") if line.split_whitespace().nth(2) == Some("=") => {}
+
+                Some("class" | "module") => {
+                    // generics and the superclass aren't part of the name, e.g. "Box[T] < Base"
+                    let name = words.next().and_then(|w| w.split(['[', '<']).next()).filter(|n| !n.is_empty());
+                    scopes.push(name.map(str::to_string));
+                }
+
+                Some("interface") => scopes.push(None),
+
+                Some("end") => {
+                    scopes.pop();
+                }
+
+                Some("def") => {
+                    if scopes.iter().any(Option::is_none) {
+                        continue;
+                    }
+                    let Some((name, signature)) = line["def".len()..].split_once(':') else {
+                        continue;
+                    };
+
+                    let owner =
+                        scopes.iter().flatten().map(String::as_str).collect::<Vec<&str>>().join(SCOPE_DELIMITER);
+                    let name = name.trim();
+                    last_methods = if let Some(name) = name.strip_prefix("self?.") {
+                        // module functions are both singleton and instance methods
+                        vec![format!("{owner}.{name}"), format!("{owner}#{name}")]
+                    } else if let Some(name) = name.strip_prefix("self.") {
+                        vec![format!("{owner}.{name}")]
+                    } else {
+                        vec![format!("{owner}#{name}")]
+                    };
+
+                    for fqn in &last_methods {
+                        self.signatures.entry(fqn.clone()).or_default().push(signature.trim().to_string());
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn parse(source: &str) -> RbsSignatures {
+        let mut signatures = RbsSignatures::default();
+        signatures.parse(source);
+        signatures
+    }
+
+    #[test]
+    fn parse_test() {
+        let signatures = parse(
+            "module Billing\n  class Invoice < Base[Integer]\n    def total: () -> Integer\n    def self.build: (Integer total) -> Invoice\n                  | () -> Invoice\n  end\n\n  interface _Payable\n    def pay: () -> void\n  end\n\n  module Tax\n    def self?.rate: (String country) -> Float\n  end\nend\n",
+        );
+
+        assert_eq!(["() -> Integer"], signatures.get("Billing::Invoice#total"));
+        assert_eq!(["(Integer total) -> Invoice", "() -> Invoice"], signatures.get("Billing::Invoice.build"));
+        assert_eq!(["(String country) -> Float"], signatures.get("Billing::Tax.rate"));
+        assert_eq!(["(String country) -> Float"], signatures.get("Billing::Tax#rate"));
+        assert!(signatures.get("Billing::_Payable#pay").is_empty());
+        assert!(signatures.get("Billing#pay").is_empty());
+    }
+
+    #[test]
+    fn parse_aliases_test() {
+        let signatures = parse(
+            "module Billing\n  class Bill = Invoice\n  module Taxes = Tax\n\n  class Invoice\n    def total: () -> Integer\n  end\nend\n",
+        );
+
+        assert_eq!(["() -> Integer"], signatures.get("Billing::Invoice#total"));
+    }
+
+    #[test]
+    fn index_dir_test() {
+        let signatures = RbsSignatures::index_dir(&Path::new(FIXTURES_DIR).join("rbs/sig"));
+
+        assert_eq!(["(Float rate) -> Float"], signatures.get("Invoice#total_with_tax"));
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::Instant;
+
+use log::{info, warn};
+use lsp_server::RequestId;
+
+/*
+ * The key of the mapped diagnostic context entry with the request being handled,
+ * used by the log pattern to correlate log lines of a single request.
+ */
+pub const REQUEST_LOG_KEY: &str = "request";
+
+/*
+ * Tags log lines of the current thread with the request id and method until dropped,
+ * then logs how long the request took.
+ */
+pub struct RequestLogContext {
+    start: Instant,
+}
+
+impl RequestLogContext {
+    pub fn enter(id: &RequestId, method: &str) -> RequestLogContext {
+        log_mdc::insert(REQUEST_LOG_KEY, format!("{method} #{id}"));
+        info!("started");
+
+        RequestLogContext {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for RequestLogContext {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+        if std::thread::panicking() {
+            warn!("panicked after {duration:?}");
+        } else {
+            info!("finished in {duration:?}");
+        }
+
+        log_mdc::remove(REQUEST_LOG_KEY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_log_context_test() {
+        {
+            let _context = RequestLogContext::enter(&RequestId::from(42), "textDocument/definition");
+            let value = log_mdc::get(REQUEST_LOG_KEY, |v| v.map(str::to_string));
+
+            assert_eq!(Some("textDocument/definition #42".to_string()), value);
+        }
+
+        assert_eq!(None, log_mdc::get(REQUEST_LOG_KEY, |v| v.map(str::to_string)));
+    }
+}
@@ -1,4 +1,6 @@
 use std::{
+    env,
+    ffi::OsStr,
     fs,
     path::{Path, PathBuf},
     process::Command,
@@ -9,15 +11,21 @@ use log::info;
 
 pub struct RubyEnvProvider {
     dir: PathBuf,
+    disable_shell_commands: bool,
 }
 
 impl RubyEnvProvider {
-    pub fn new(dir: &Path) -> RubyEnvProvider {
+    pub fn new(dir: &Path, disable_shell_commands: bool) -> RubyEnvProvider {
         RubyEnvProvider {
             dir: dir.to_path_buf(),
+            disable_shell_commands,
         }
     }
 
+    pub fn shell_commands_disabled(&self) -> bool {
+        self.disable_shell_commands
+    }
+
     pub fn stubs_dir(&self) -> Result<Option<PathBuf>> {
         let ruby_version = match self.ruby_version()? {
             None => return Ok(None),
@@ -55,13 +63,38 @@ impl RubyEnvProvider {
         Ok(gems_dir.map(|gems_dir| gems_dir.join("bin/")))
     }
 
+    /*
+     * Gets the ruby of the project version installed by rvm, or the ruby found on PATH (e.g. the system one)
+     * when there's no version or it isn't installed.
+     */
     pub fn ruby_path(&self) -> Result<PathBuf> {
-        let ruby_version = self.ruby_version()?.ok_or(anyhow!("Failed to determine ruby version"))?;
-        let path = "/Users/oleksandr.oksenenko/.rvm/rubies/".to_owned() + &ruby_version + "/bin/ruby";
-        Ok(PathBuf::from(path))
+        if let Some(ruby_version) = self.ruby_version()? {
+            let path =
+                PathBuf::from("/Users/oleksandr.oksenenko/.rvm/rubies/".to_owned() + &ruby_version + "/bin/ruby");
+            if path.exists() {
+                return Ok(path);
+            }
+
+            info!("ruby {ruby_version} isn't installed with rvm, looking for ruby on PATH");
+        }
+
+        let path = env::var_os("PATH").ok_or(anyhow!("Failed to find ruby, PATH isn't set"))?;
+        Self::find_executable(&path, "ruby")
+            .ok_or(anyhow!("Failed to find ruby, it isn't installed with rvm or on PATH"))
+    }
+
+    /*
+     * Finds the executable in the directories of the PATH-like value, the first one wins.
+     */
+    fn find_executable(path: &OsStr, name: &str) -> Option<PathBuf> {
+        env::split_paths(path).map(|dir| dir.join(name)).find(|file| file.is_file())
     }
 
     pub fn run_context_command(&self, args: &str) -> Result<Vec<u8>> {
+        if self.disable_shell_commands {
+            bail!("Shell commands are disabled, not running: {args}");
+        }
+
         let bundle_path = self.ruby_bin_dir().with_context(|| "Failed to find ruby bin dir")?.map(|d| d.join("bundle"));
         let ruby_path = self.ruby_path().with_context(|| "Failed to find ruby path")?;
         let cmd = bundle_path.unwrap_or(ruby_path);
@@ -102,3 +135,26 @@ impl RubyEnvProvider {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_shell_commands_test() {
+        let provider = RubyEnvProvider::new(Path::new("/nonexistent"), true);
+
+        let error = provider.run_context_command("rails runner 'puts 1'").unwrap_err();
+
+        assert!(error.to_string().starts_with("Shell commands are disabled"));
+    }
+
+    #[test]
+    fn find_executable_test() {
+        let fixture_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ruby_path");
+        let path = env::join_paths([fixture_dir.join("empty"), fixture_dir.join("bin")]).unwrap();
+
+        assert_eq!(Some(fixture_dir.join("bin/ruby")), RubyEnvProvider::find_executable(&path, "ruby"));
+        assert_eq!(None, RubyEnvProvider::find_executable(&path, "jruby"));
+    }
+}
@@ -1,4 +1,8 @@
-use std::path::{Path, PathBuf};
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Component, Path, PathBuf},
+};
 
 use anyhow::{anyhow, Context, Result};
 
@@ -14,19 +18,27 @@ const AUTOLOAD_PATHS_CMD: &str = "rails runner 'puts ActiveSupport::Dependencies
 pub struct RubyFilenameConverter {
     root_path: PathBuf,
     autoload_paths: Vec<PathBuf>,
+    acronyms: Vec<String>,
 }
 
 impl RubyFilenameConverter {
     pub fn new(root_path: &Path, ruby_env_provider: &RubyEnvProvider) -> Result<RubyFilenameConverter> {
+        if ruby_env_provider.shell_commands_disabled() {
+            let autoload_paths = Self::static_autoload_paths(root_path);
+            info!("Shell commands are disabled, using the conventional autoload paths: {:?}", autoload_paths);
+
+            return Ok(RubyFilenameConverter::with_autoload_paths(root_path, autoload_paths));
+        }
+
         let output = ruby_env_provider
             .run_context_command(AUTOLOAD_PATHS_CMD)
             .with_context(|| "Failed to run rails runner command")?;
         let mut autoload_paths: Vec<PathBuf> = String::from_utf8(output)?
             .split('\n')
-            .map(|s| s.to_string())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
             .unique()
             .map(PathBuf::from)
-            .map(|p| p.strip_prefix(root_path).map(|p| p.to_path_buf()).unwrap_or(p))
             .collect();
 
         let mut other_paths = RAILS_ROOT_PATHS.iter().map(PathBuf::from).collect();
@@ -35,10 +47,45 @@ impl RubyFilenameConverter {
 
         info!("Using the following autoload paths: {:?}", autoload_paths);
 
-        Ok(RubyFilenameConverter {
+        Ok(RubyFilenameConverter::with_autoload_paths(root_path, autoload_paths))
+    }
+
+    pub fn with_autoload_paths(root_path: &Path, autoload_paths: Vec<PathBuf>) -> RubyFilenameConverter {
+        // the most specific autoload path should win when they're nested (e.g. app/models over app)
+        let autoload_paths = autoload_paths
+            .iter()
+            .map(|p| Self::normalize_autoload_path(root_path, p))
+            .sorted_by_key(|p| Reverse(p.components().count()))
+            .collect();
+
+        RubyFilenameConverter {
             root_path: root_path.to_path_buf(),
             autoload_paths,
-        })
+            acronyms: vec![],
+        }
+    }
+
+    /*
+     * Sets the acronyms kept upper case in the scopes, e.g. "HTML" for html_parser.rb to be HTMLParser.
+     */
+    pub fn with_acronyms(mut self, acronyms: &[String]) -> RubyFilenameConverter {
+        self.acronyms = acronyms.to_vec();
+        self
+    }
+
+    /*
+     * Adds the directories mapped to the top-level scope besides the autoload paths, e.g. "lib" of gems.
+     */
+    pub fn with_autoload_roots(mut self, roots: &[String]) -> RubyFilenameConverter {
+        let roots = roots.iter().map(|r| Self::normalize_autoload_path(&self.root_path, Path::new(r)));
+        self.autoload_paths = self
+            .autoload_paths
+            .into_iter()
+            .chain(roots)
+            .unique()
+            .sorted_by_key(|p| Reverse(p.components().count()))
+            .collect();
+        self
     }
 
     pub fn path_to_scope(&self, path: &Path) -> Result<Scope> {
@@ -56,13 +103,47 @@ impl RubyFilenameConverter {
             return failures.into_iter().next().unwrap();
         }
 
-        let result: Vec<String> = sucesses.into_iter().map(Self::name_to_scope).collect();
+        let result: Vec<String> = sucesses.into_iter().map(|name| self.name_to_scope(name)).collect();
 
         Ok(Scope::from(result))
     }
 
-    fn name_to_scope(name: &str) -> String {
-        name.split('_').map(Self::capitalize).join("")
+    /*
+     * Gets the autoload paths of the Rails conventions without running the application: the subdirectories
+     * of "app" and their "concerns", plus the root paths added to the output of `rails runner`.
+     */
+    fn static_autoload_paths(root_path: &Path) -> Vec<PathBuf> {
+        let app_dirs: Vec<PathBuf> = fs::read_dir(root_path.join("app"))
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .sorted()
+            .collect();
+
+        let concerns_dirs: Vec<PathBuf> = app_dirs.iter().map(|d| d.join("concerns")).filter(|d| d.is_dir()).collect();
+
+        app_dirs.into_iter().chain(concerns_dirs).chain(RAILS_ROOT_PATHS.iter().map(PathBuf::from)).collect()
+    }
+
+    /*
+     * Makes autoload path relative to the root and drops redundant components, so that both
+     * "/root/app/models/" and "./app/models" become "app/models".
+     */
+    fn normalize_autoload_path(root_path: &Path, path: &Path) -> PathBuf {
+        let path = path.strip_prefix(root_path).unwrap_or(path);
+
+        path.components().filter(|c| !matches!(c, Component::CurDir)).collect()
+    }
+
+    fn name_to_scope(&self, name: &str) -> String {
+        name.split('_')
+            .map(|word| match self.acronyms.iter().find(|a| a.eq_ignore_ascii_case(word)) {
+                Some(acronym) => acronym.clone(),
+                None => Self::capitalize(word),
+            })
+            .join("")
     }
 
     fn capitalize(s: &str) -> String {
@@ -80,28 +161,116 @@ mod tests {
 
     #[cfg(test)]
     mod path_to_scope_tests {
-        // use super::*;
+        use super::*;
+
+        const ROOT_PATH: &str = "/a/b/c";
 
-        // #[test]
-        // fn test_path_to_scope() {
-        //     let root_path = Path::new("/a/b/c");
-        //     let converter = RubyFilenameConverter::new(root_path);
-        //     let path = Path::new("/a/b/c/module_one/module_two_three/class_four.rb");
-        //     let expected_scope = vec!["ModuleOne", "ModuleTwoThree", "ClassFour"];
+        #[test]
+        fn test_path_to_scope() {
+            let converter = RubyFilenameConverter::with_autoload_paths(Path::new(ROOT_PATH), vec![]);
+            let path = Path::new("/a/b/c/module_one/module_two_three/class_four.rb");
+            let expected_scope = vec!["ModuleOne", "ModuleTwoThree", "ClassFour"];
 
-        //     let result = converter.path_to_scope(path);
+            let result = converter.path_to_scope(path);
 
-        //     println!("{result:?}");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), expected_scope);
+        }
 
-        //     assert!(result.is_ok());
-        //     let result = result.unwrap();
-        //     assert_eq!(result, expected_scope);
-        // }
+        #[test]
+        fn test_path_to_scope_rails_autoload_paths() {
+            let autoload_paths = vec![
+                PathBuf::from("/a/b/c/app/models"),
+                PathBuf::from("app/controllers/"),
+                PathBuf::from("./app/helpers"),
+            ];
+            let converter = RubyFilenameConverter::with_autoload_paths(Path::new(ROOT_PATH), autoload_paths);
+
+            let cases = [
+                ("/a/b/c/app/models/user.rb", vec!["User"]),
+                ("/a/b/c/app/models/admin/user.rb", vec!["Admin", "User"]),
+                ("/a/b/c/app/controllers/admin/users_controller.rb", vec!["Admin", "UsersController"]),
+                ("/a/b/c/app/helpers/admin/users_helper.rb", vec!["Admin", "UsersHelper"]),
+            ];
+
+            for (path, expected_scope) in cases {
+                assert_eq!(converter.path_to_scope(Path::new(path)).unwrap(), expected_scope);
+            }
+        }
+
+        #[test]
+        fn test_path_to_scope_overlapping_autoload_paths() {
+            let autoload_paths =
+                vec![PathBuf::from("app"), PathBuf::from("app/models"), PathBuf::from("app/models/concerns")];
+            let converter = RubyFilenameConverter::with_autoload_paths(Path::new(ROOT_PATH), autoload_paths);
+
+            let cases = [
+                ("/a/b/c/app/models/user.rb", vec!["User"]),
+                ("/a/b/c/app/models/concerns/trackable.rb", vec!["Trackable"]),
+                ("/a/b/c/app/services/user_creator.rb", vec!["Services", "UserCreator"]),
+            ];
+
+            for (path, expected_scope) in cases {
+                assert_eq!(converter.path_to_scope(Path::new(path)).unwrap(), expected_scope);
+            }
+        }
+    }
+
+    #[test]
+    fn test_path_to_scope_acronyms() {
+        let acronyms = vec!["HTML".to_string(), "API".to_string()];
+        let converter = RubyFilenameConverter::with_autoload_paths(Path::new("/a"), vec![]).with_acronyms(&acronyms);
+
+        assert_eq!(converter.path_to_scope(Path::new("/a/html_parser.rb")).unwrap(), vec!["HTMLParser"]);
+        assert_eq!(converter.path_to_scope(Path::new("/a/api/v2/html.rb")).unwrap(), vec!["API", "V2", "HTML"]);
+        assert_eq!(converter.path_to_scope(Path::new("/a/xml_parser.rb")).unwrap(), vec!["XmlParser"]);
+    }
+
+    #[test]
+    fn test_path_to_scope_autoload_roots() {
+        let roots = vec!["lib".to_string(), "./engines/billing/lib/".to_string()];
+        let converter = RubyFilenameConverter::with_autoload_paths(Path::new("/a"), vec![PathBuf::from("app/models")])
+            .with_autoload_roots(&roots);
+
+        let cases = [
+            ("/a/lib/my_gem/client.rb", vec!["MyGem", "Client"]),
+            ("/a/lib/my_gem.rb", vec!["MyGem"]),
+            ("/a/engines/billing/lib/billing/invoice.rb", vec!["Billing", "Invoice"]),
+            ("/a/app/models/user.rb", vec!["User"]),
+            ("/a/library/reader.rb", vec!["Library", "Reader"]),
+        ];
+
+        for (path, expected_scope) in cases {
+            assert_eq!(converter.path_to_scope(Path::new(path)).unwrap(), expected_scope);
+        }
+
+        let converter = RubyFilenameConverter::with_autoload_paths(Path::new("/a"), vec![]);
+        assert_eq!(
+            converter.path_to_scope(Path::new("/a/lib/my_gem/client.rb")).unwrap(),
+            vec!["Lib", "MyGem", "Client"]
+        );
+    }
+
+    #[test]
+    fn test_disabled_shell_commands() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/static_autoload");
+        let converter = RubyFilenameConverter::new(&root, &RubyEnvProvider::new(&root, true)).unwrap();
+
+        let cases = [
+            ("app/models/user.rb", vec!["User"]),
+            ("app/models/concerns/trackable.rb", vec!["Trackable"]),
+            ("app/controllers/admin/users_controller.rb", vec!["Admin", "UsersController"]),
+        ];
+
+        for (path, expected_scope) in cases {
+            assert_eq!(converter.path_to_scope(&root.join(path)).unwrap(), expected_scope);
+        }
     }
 
     #[test]
     fn test_name_to_scope() {
-        assert_eq!("ModuleOneTwoThree", RubyFilenameConverter::name_to_scope("module_one_two_three"));
+        let converter = RubyFilenameConverter::with_autoload_paths(Path::new("/a"), vec![]);
+        assert_eq!("ModuleOneTwoThree", converter.name_to_scope("module_one_two_three"));
     }
 
     #[test]
@@ -1,80 +1,217 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
+use arc_swap::ArcSwap;
 
 use crossbeam_channel::Sender;
-use log::info;
-use lsp_server::{Connection, Message, RequestId, Response};
+use itertools::Itertools;
+use log::{info, warn};
+use lsp_server::{Connection, ErrorCode, Message, RequestId, Response};
 use lsp_types::{
-    request::{DocumentSymbolRequest, GotoDefinition, WorkspaceSymbolRequest},
-    DocumentSymbolParams, GotoDefinitionParams, GotoDefinitionResponse, Location, Position, Range, SymbolInformation,
-    SymbolKind, Url, WorkspaceSymbolParams,
+    notification::ShowMessage,
+    notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidRenameFiles},
+    request::{
+        DocumentSymbolRequest, GotoDefinition, HoverRequest, Rename, TypeHierarchyPrepare, TypeHierarchySubtypes,
+        TypeHierarchySupertypes, WorkspaceSymbolRequest, WorkspaceSymbolResolve,
+    },
+    ClientCapabilities, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverContents, HoverParams, InitializeParams, Location, LocationLink, MarkupContent, MarkupKind, MessageType,
+    OneOf, Position, Range, RenameFilesParams, RenameParams, ShowMessageParams, SymbolInformation, SymbolKind,
+    TextEdit, TypeHierarchyItem, TypeHierarchyPrepareParams, TypeHierarchySubtypesParams,
+    TypeHierarchySupertypesParams, Url, WorkspaceEdit, WorkspaceSymbol, WorkspaceSymbolParams, WorkspaceSymbolResponse,
 };
 use serde::de::DeserializeOwned;
-use tree_sitter::Point;
+use tree_sitter::{Node, Point, Tree};
 
 use crate::{
-    finder::Finder, indexer::Indexer, progress_reporter::ProgressReporter, ruby_env_provider::RubyEnvProvider,
-    ruby_filename_converter::RubyFilenameConverter, types::RSymbol,
+    cancellation::Cancellations,
+    config::{Config, SymbolContainer},
+    debounce::Debouncer,
+    documents::{Document, Documents},
+    finder::{Finder, MethodRename},
+    indexer::Indexer,
+    parsers::{
+        erb::is_erb_file,
+        general::{check_grammar, get_definition_nodes, read_file_tree},
+    },
+    progress_reporter::ProgressReporter,
+    rbs::RbsSignatures,
+    request_log::RequestLogContext,
+    ruby_env_provider::RubyEnvProvider,
+    ruby_filename_converter::RubyFilenameConverter,
+    types::{RSymbol, RSymbolIndex, RSymbols},
 };
 
-pub struct Server<'a> {
+pub struct Server {
     root_dir: PathBuf,
-    indexer: Indexer<'a>,
     pub finder: Finder,
-    symbols: Rc<Vec<Arc<RSymbol>>>,
-    ruby_env_provider: Rc<RubyEnvProvider>,
-    ruby_filename_converter: Rc<RubyFilenameConverter>,
-    progress_reporter: Rc<ProgressReporter<'a>>,
+    symbols: RSymbols,
+    config: Rc<Config>,
+    cancellations: Cancellations,
+    definition_link_support: bool,
+    documents: Documents,
+    // changed documents waiting for a pause in the changes to be reindexed
+    pending_reindex: RefCell<Debouncer>,
+    // no workspace is opened, only the opened documents are indexed instead of the root directory
+    single_file: bool,
 }
 
 trait Handler<P: DeserializeOwned> {
     fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, P)) -> Result<()>;
 }
 
-impl<'a> Server<'a> {
-    pub fn new(root_dir: &Path, sender: &'a Sender<Message>) -> Result<Server<'a>> {
+trait NotificationHandler<P: DeserializeOwned> {
+    fn notify<N>(&self, params: P) -> Result<()>;
+}
+
+impl Server {
+    pub fn new(
+        root_dir: &Path,
+        config: Config,
+        capabilities: &ClientCapabilities,
+        sender: &Sender<Message>,
+        cancellations: Cancellations,
+    ) -> Result<Server> {
+        Self::create(root_dir, config, capabilities, sender, cancellations, false)
+    }
+
+    /*
+     * Creates the server for the files opened without a workspace, e.g. a single script. Its directory
+     * can be anything (e.g. the home directory), so nothing is indexed but the opened documents.
+     */
+    pub fn new_single_file(
+        root_dir: &Path,
+        config: Config,
+        capabilities: &ClientCapabilities,
+        sender: &Sender<Message>,
+        cancellations: Cancellations,
+    ) -> Result<Server> {
+        Self::create(root_dir, config, capabilities, sender, cancellations, true)
+    }
+
+    fn create(
+        root_dir: &Path,
+        config: Config,
+        capabilities: &ClientCapabilities,
+        sender: &Sender<Message>,
+        cancellations: Cancellations,
+        single_file: bool,
+    ) -> Result<Server> {
         let root_dir = root_dir.to_path_buf();
+        let definition_link_support = capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.definition.as_ref())
+            .and_then(|d| d.link_support)
+            .unwrap_or(false);
+        let config = Rc::new(config);
+
+        if let Err(e) = check_grammar() {
+            warn!("!!! {e}, the index is likely to be incomplete !!!");
+        }
 
         let progress_reporter = Rc::new(ProgressReporter::new(sender));
-        let ruby_env_provider = Rc::new(RubyEnvProvider::new(&root_dir));
-        let ruby_filename_converter = Rc::new(RubyFilenameConverter::new(&root_dir, &ruby_env_provider)?);
-        let mut indexer = Indexer::new(
-            &root_dir,
-            progress_reporter.clone(),
-            ruby_env_provider.clone(),
-            ruby_filename_converter.clone(),
+        let ruby_env_provider = Rc::new(RubyEnvProvider::new(&root_dir, config.disable_shell_commands));
+        let ruby_filename_converter = Rc::new(
+            RubyFilenameConverter::new(&root_dir, &ruby_env_provider)?
+                .with_acronyms(&config.acronyms)
+                .with_autoload_roots(&config.autoload_roots),
         );
+        let mut indexer = Indexer::new(&root_dir, config.clone(), progress_reporter.clone(), ruby_env_provider.clone());
 
-        let symbols = Rc::new(indexer.index()?);
-        let finder = Finder::new(&root_dir, symbols.clone(), ruby_filename_converter.clone());
+        let file_indexes = if single_file {
+            info!("single file mode, only the opened documents are indexed");
+            vec![]
+        } else {
+            indexer.index()?
+        };
+        let symbols = Arc::new(ArcSwap::from_pointee(RSymbolIndex::default()));
+        let documents = Documents::default();
+        let finder = Finder::new(&root_dir, config.clone(), symbols.clone(), ruby_filename_converter.clone())
+            .with_stubs_dir(ruby_env_provider.stubs_dir()?)
+            .with_documents(documents.clone());
+        // the source of the files (e.g. gems) is recorded when they're published
+        finder.publish_file_indexes(file_indexes);
+        let finder = match config.rbs_signatures_dir(&root_dir) {
+            Some(sig_dir) => finder.with_signatures(RbsSignatures::index_dir(&sig_dir)),
+            None => finder,
+        };
+        let pending_reindex = RefCell::new(Debouncer::new(Duration::from_millis(config.reindex_debounce_ms)));
 
         Ok(Server {
             root_dir,
-            indexer,
             finder,
             symbols,
-            ruby_filename_converter,
-            ruby_env_provider,
-            progress_reporter,
+            config,
+            cancellations,
+            definition_link_support,
+            documents,
+            pending_reindex,
+            single_file,
         })
     }
 
+    /*
+     * Gets the root of the workspace opened by the client, it's missing when a single file is opened.
+     */
+    pub fn workspace_root(params: &InitializeParams) -> Option<PathBuf> {
+        let folder_uri = params.workspace_folders.as_ref().and_then(|f| f.first()).map(|f| &f.uri);
+
+        params.root_uri.as_ref().or(folder_uri).and_then(|uri| uri.to_file_path().ok())
+    }
+
+    /*
+     * Gets the root for the document opened without a workspace, i.e. its directory.
+     */
+    pub fn document_root(uri: &Url) -> Option<PathBuf> {
+        uri.to_file_path().ok()?.parent().map(Path::to_path_buf)
+    }
+
     pub fn handle_request(&self, connection: &Connection, request: lsp_server::Request) -> Result<()> {
-        use lsp_types::request::Request;
+        let _log_context = RequestLogContext::enter(&request.id, &request.method);
 
         let sender = &connection.sender;
+        let id = request.id.clone();
+        if self.cancellations.token(&id).is_cancelled() {
+            info!("request was cancelled before it was handled");
+            self.cancellations.finish(&id);
+            return Self::send_cancelled(sender, id);
+        }
+
+        let result = self.dispatch_request(sender, request);
+        self.cancellations.finish(&id);
+
+        // a request which can't be handled (e.g. goto definition of a keyword) mustn't stop the server
+        if let Err(e) = result {
+            warn!("request failed: {e}");
+            return Self::send_error(sender, id, ErrorCode::RequestFailed, e.to_string());
+        }
+
+        Ok(())
+    }
+
+    fn dispatch_request(&self, sender: &Sender<Message>, request: lsp_server::Request) -> Result<()> {
+        use lsp_types::request::Request;
+
         match request.method.as_str() {
             WorkspaceSymbolRequest::METHOD => self.handle::<WorkspaceSymbolParams>(
                 sender,
                 request.extract::<WorkspaceSymbolParams>(WorkspaceSymbolRequest::METHOD)?,
             ),
 
+            WorkspaceSymbolResolve::METHOD => self.handle::<WorkspaceSymbolResolve>(
+                sender,
+                request.extract::<WorkspaceSymbol>(WorkspaceSymbolResolve::METHOD)?,
+            ),
+
             DocumentSymbolRequest::METHOD => self.handle::<DocumentSymbolRequest>(
                 sender,
                 request.extract::<DocumentSymbolParams>(DocumentSymbolRequest::METHOD)?,
@@ -84,10 +221,107 @@ impl<'a> Server<'a> {
                 self.handle::<GotoDefinition>(sender, request.extract::<GotoDefinitionParams>(GotoDefinition::METHOD)?)
             }
 
-            _ => Err(anyhow!("Method {} is not supported", request.method)),
+            Rename::METHOD => self.handle::<Rename>(sender, request.extract::<RenameParams>(Rename::METHOD)?),
+
+            HoverRequest::METHOD => {
+                self.handle::<HoverRequest>(sender, request.extract::<HoverParams>(HoverRequest::METHOD)?)
+            }
+
+            TypeHierarchyPrepare::METHOD => self.handle::<TypeHierarchyPrepare>(
+                sender,
+                request.extract::<TypeHierarchyPrepareParams>(TypeHierarchyPrepare::METHOD)?,
+            ),
+
+            TypeHierarchySupertypes::METHOD => self.handle::<TypeHierarchySupertypes>(
+                sender,
+                request.extract::<TypeHierarchySupertypesParams>(TypeHierarchySupertypes::METHOD)?,
+            ),
+
+            TypeHierarchySubtypes::METHOD => self.handle::<TypeHierarchySubtypes>(
+                sender,
+                request.extract::<TypeHierarchySubtypesParams>(TypeHierarchySubtypes::METHOD)?,
+            ),
+
+            _ => {
+                let message = format!("Method {} is not supported", request.method);
+                Self::send_error(sender, request.id, ErrorCode::MethodNotFound, message)
+            }
+        }
+    }
+
+    pub fn handle_notification(&self, notification: lsp_server::Notification) -> Result<()> {
+        use lsp_types::notification::Notification;
+
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => self.notify::<DidOpenTextDocument>(
+                notification.extract::<DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)?,
+            ),
+
+            DidChangeTextDocument::METHOD => self.notify::<DidChangeTextDocument>(
+                notification.extract::<DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)?,
+            ),
+
+            DidCloseTextDocument::METHOD => self.notify::<DidCloseTextDocument>(
+                notification.extract::<DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)?,
+            ),
+
+            DidRenameFiles::METHOD => {
+                self.notify::<DidRenameFiles>(notification.extract::<RenameFilesParams>(DidRenameFiles::METHOD)?)
+            }
+
+            _ => {
+                info!("got notification: {notification:?}");
+                Ok(())
+            }
+        }
+    }
+
+    /*
+     * Gets the time until the next changed document has to be reindexed, the main loop waits for messages
+     * no longer than that.
+     */
+    pub fn reindex_timeout(&self) -> Option<Duration> {
+        self.pending_reindex.borrow().timeout(Instant::now())
+    }
+
+    /*
+     * Reindexes the changed documents which haven't changed for the debounce delay.
+     */
+    pub fn reindex_pending(&self) {
+        let files = self.pending_reindex.borrow_mut().take_ready(Instant::now());
+        let documents = self.documents.borrow();
+
+        for file in files {
+            if let Some(document) = documents.get(&file) {
+                info!("reindexing changed document {file:?}");
+                self.finder.publish_file_index(Indexer::index_tree(
+                    file,
+                    &document.tree,
+                    &document.source,
+                    &self.config,
+                ));
+            }
         }
     }
 
+    pub fn shutdown(&self) {
+        let symbols = self.symbols.load();
+
+        info!("Shutting down, {} symbols from {} files were indexed", symbols.len(), symbols.files_len());
+    }
+
+    /*
+     * Finds the symbol previously sent to the client, e.g. in workspace/symbol response.
+     */
+    fn find_symbol_by_location(&self, name: &str, location: &Location) -> Option<Arc<RSymbol>> {
+        self.symbols
+            .load()
+            .iter()
+            .filter(|s| s.name() == name)
+            .find(|s| &Self::convert_to_lsp_sym_info(s).location == location)
+            .cloned()
+    }
+
     fn send_response<T: serde::Serialize>(sender: &Sender<Message>, id: RequestId, response: T) -> Result<()> {
         let result = serde_json::to_value(response).unwrap();
         let resp = Response {
@@ -100,6 +334,20 @@ impl<'a> Server<'a> {
         Ok(())
     }
 
+    fn send_error(sender: &Sender<Message>, id: RequestId, code: ErrorCode, message: String) -> Result<()> {
+        let resp = Response::new_err(id, code as i32, message);
+        sender.send(Message::Response(resp))?;
+
+        Ok(())
+    }
+
+    fn send_cancelled(sender: &Sender<Message>, id: RequestId) -> Result<()> {
+        let resp = Response::new_err(id, ErrorCode::RequestCanceled as i32, "Request was cancelled".to_string());
+        sender.send(Message::Response(resp))?;
+
+        Ok(())
+    }
+
     fn convert_to_lsp_sym_info(rsymbol: impl AsRef<RSymbol>) -> SymbolInformation {
         let rsymbol = rsymbol.as_ref();
         let path = rsymbol.file();
@@ -118,66 +366,445 @@ impl<'a> Server<'a> {
             end: Position::new(line, character + name_len),
         };
 
-        let kind = match rsymbol {
+        #[allow(deprecated)]
+        SymbolInformation {
+            name: name.to_string(),
+            kind: Self::convert_to_lsp_symbol_kind(rsymbol),
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: url,
+                range,
+            },
+            container_name: None,
+        }
+    }
+
+    /*
+     * Maps every symbol to a real kind, some clients drop the symbols of the NULL kind.
+     */
+    fn convert_to_lsp_symbol_kind(rsymbol: &RSymbol) -> SymbolKind {
+        match rsymbol {
             RSymbol::Class(_) => SymbolKind::CLASS,
             RSymbol::Module(_) => SymbolKind::MODULE,
             RSymbol::Method(_) => SymbolKind::METHOD,
             RSymbol::SingletonMethod(_) => SymbolKind::METHOD,
             RSymbol::Constant(_) => SymbolKind::CONSTANT,
-            _ => SymbolKind::NULL,
-        };
+            RSymbol::Variable(_) | RSymbol::GlobalVariable(_) => SymbolKind::VARIABLE,
+            RSymbol::ClassVariable(_) => SymbolKind::FIELD,
+        }
+    }
+
+    /*
+     * Converts the definitions to the links whose range spans the whole definition, e.g. the class with
+     * its body, and whose selection is the name. The trees of the definition files are read once,
+     * preferring the opened documents.
+     */
+    fn convert_to_lsp_location_links(
+        symbols: &[Arc<RSymbol>],
+        documents: &HashMap<PathBuf, Document>,
+        origin_selection_range: Range,
+    ) -> Vec<LocationLink> {
+        let mut trees: HashMap<&Path, Option<Tree>> = HashMap::new();
+
+        symbols
+            .iter()
+            .map(|rsymbol| {
+                let tree = trees.entry(rsymbol.file()).or_insert_with(|| match documents.get(rsymbol.file()) {
+                    Some(document) => Some(document.tree.clone()),
+                    None => read_file_tree(rsymbol.file())
+                        .map_err(|e| warn!("Failed to read {:?}: {e}", rsymbol.file()))
+                        .ok()
+                        .map(|(tree, _)| tree),
+                });
+
+                Self::convert_to_lsp_location_link(rsymbol, tree.as_ref(), origin_selection_range)
+            })
+            .collect()
+    }
+
+    /*
+     * Falls back to the name of the definition when its nodes aren't found, e.g. "baz" of "Foo::Bar::baz".
+     */
+    fn convert_to_lsp_location_link(
+        rsymbol: &Arc<RSymbol>,
+        tree: Option<&Tree>,
+        origin_selection_range: Range,
+    ) -> LocationLink {
+        let location = Self::convert_to_lsp_sym_info(rsymbol).location;
+
+        let ranges = tree.and_then(|tree| get_definition_nodes(tree, *rsymbol.location())).and_then(
+            |(name_node, definition_node)| {
+                Some((Self::convert_to_lsp_range(&definition_node).ok()?, Self::convert_to_lsp_range(&name_node).ok()?))
+            },
+        );
+        let (target_range, target_selection_range) = ranges.unwrap_or_else(|| {
+            let name_len: u32 = rsymbol.full_scope().last().map(|n| n.len()).unwrap_or_default().try_into().unwrap();
+            let name_range = Range {
+                start: location.range.start,
+                end: Position::new(location.range.start.line, location.range.start.character + name_len),
+            };
+            (name_range, name_range)
+        });
+
+        LocationLink {
+            origin_selection_range: Some(origin_selection_range),
+            target_uri: location.uri,
+            target_range,
+            target_selection_range,
+        }
+    }
+
+    /*
+     * Replaces the method name at every location of the rename, the ambiguous ones are left as they are.
+     */
+    fn convert_to_lsp_workspace_edit(rename: &MethodRename, new_name: &str) -> Result<WorkspaceEdit> {
+        let name_len: u32 = rename.name.len().try_into()?;
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+        for (file, point) in &rename.locations {
+            let uri = Url::from_file_path(file).map_err(|_| anyhow!("Failed to convert {file:?} to URL"))?;
+            let start = Position::new(point.row.try_into()?, point.column.try_into()?);
+            let end = Position::new(start.line, start.character + name_len);
+
+            changes.entry(uri).or_default().push(TextEdit::new(Range::new(start, end), new_name.to_string()));
+        }
+
+        Ok(WorkspaceEdit::new(changes))
+    }
+
+    /*
+     * Gets the range of the node the definition was requested for.
+     */
+    fn find_origin_range(tree: &Tree, file: &Path, position: Point) -> Result<Range> {
+        let node = tree
+            .root_node()
+            .descendant_for_point_range(position, position)
+            .ok_or(anyhow!("Failed to find node at {position:?} in {file:?}"))?;
+
+        Self::convert_to_lsp_range(&node)
+    }
+
+    fn convert_to_lsp_range(node: &Node) -> Result<Range> {
+        let convert =
+            |point: Point| -> Result<Position> { Ok(Position::new(point.row.try_into()?, point.column.try_into()?)) };
+
+        Ok(Range {
+            start: convert(node.start_position())?,
+            end: convert(node.end_position())?,
+        })
+    }
+
+    /*
+     * Gets the symbols of the file with its tree. The opened document is indexed as it is since its indexed
+     * symbols can be stale until it's reindexed, and a Ruby file is parsed on demand when it isn't indexed,
+     * e.g. it's outside of the project root. Other files (e.g. JSON) have no symbols.
+     */
+    fn find_file_symbols(
+        indexed: Vec<Arc<RSymbol>>,
+        path: &Path,
+        document: Option<&Document>,
+        config: &Config,
+    ) -> Result<(Vec<Arc<RSymbol>>, Tree)> {
+        if let Some(document) = document {
+            let symbols = Indexer::index_tree(path.to_path_buf(), &document.tree, &document.source, config).symbols;
+            return Ok((symbols, document.tree.clone()));
+        }
+
+        let (tree, source) = read_file_tree(path)?;
+        if !indexed.is_empty() || !config.is_ruby_file(path) {
+            return Ok((indexed, tree));
+        }
+
+        info!("{path:?} isn't indexed, parsing it on demand");
+        let symbols = Indexer::index_tree(path.to_path_buf(), &tree, &source, config).symbols;
+
+        Ok((symbols, tree))
+    }
+
+    /*
+     * Converts the symbols of the file to the outline, symbols are nested in their parents.
+     */
+    fn convert_to_lsp_document_symbols(symbols: &[Arc<RSymbol>], tree: &Tree) -> Result<Vec<DocumentSymbol>> {
+        let is_in_outline = |parent: &Arc<RSymbol>| symbols.iter().any(|s| Arc::ptr_eq(s, parent));
+
+        symbols
+            .iter()
+            .filter(|s| !s.parent().as_ref().map(is_in_outline).unwrap_or(false))
+            .map(|s| Self::convert_to_lsp_document_symbol(s, s.name(), symbols, tree))
+            .collect()
+    }
+
+    /*
+     * The top-level symbols are named in full, while the nested ones are named by the last scope
+     * since their parents already show the rest, e.g. "balance" in "Account".
+     */
+    fn convert_to_lsp_document_symbol(
+        rsymbol: &Arc<RSymbol>,
+        name: &str,
+        symbols: &[Arc<RSymbol>],
+        tree: &Tree,
+    ) -> Result<DocumentSymbol> {
+        let children = symbols
+            .iter()
+            .filter(|s| s.parent().as_ref().map(|p| Arc::ptr_eq(p, rsymbol)).unwrap_or(false))
+            .map(|s| Self::convert_to_lsp_document_symbol(s, s.full_scope().last().unwrap_or(s.name()), symbols, tree))
+            .collect::<Result<Vec<DocumentSymbol>>>()?;
+
+        // the range spans the whole definition, e.g. the class with its body
+        let (name_node, definition_node) =
+            get_definition_nodes(tree, *rsymbol.location()).ok_or(anyhow!("Failed to find node of {rsymbol:?}"))?;
 
         #[allow(deprecated)]
-        SymbolInformation {
+        Ok(DocumentSymbol {
             name: name.to_string(),
-            kind,
+            detail: None,
+            kind: Self::convert_to_lsp_sym_info(rsymbol).kind,
             tags: None,
             deprecated: None,
-            location: Location {
-                uri: url,
-                range,
-            },
+            range: Self::convert_to_lsp_range(&definition_node)?,
+            selection_range: Self::convert_to_lsp_range(&name_node)?,
+            children: (!children.is_empty()).then_some(children),
+        })
+    }
+
+    /*
+     * Container name is left empty, it's populated lazily in workspaceSymbol/resolve.
+     */
+    fn convert_to_lsp_workspace_symbol(rsymbol: impl AsRef<RSymbol>) -> WorkspaceSymbol {
+        let symbol_info = Self::convert_to_lsp_sym_info(rsymbol);
+
+        WorkspaceSymbol {
+            name: symbol_info.name,
+            kind: symbol_info.kind,
+            tags: None,
             container_name: None,
+            location: OneOf::Left(symbol_info.location),
+            data: None,
+        }
+    }
+
+    /*
+     * Formats the container of the symbol according to the configuration, e.g. "Admin" for Admin::User
+     * or its file relative to the root, the files outside of it (e.g. gems) are shown with the full path.
+     */
+    fn convert_to_lsp_container_name(rsymbol: &RSymbol, container: SymbolContainer, root_dir: &Path) -> Option<String> {
+        match container {
+            SymbolContainer::Scope => {
+                let scope = rsymbol.full_scope();
+                let container = scope.prefix(scope.len().saturating_sub(1));
+                (!container.is_empty()).then(|| container.to_string())
+            }
+
+            SymbolContainer::Path => {
+                let file = rsymbol.file();
+                Some(file.strip_prefix(root_dir).unwrap_or(file).to_string_lossy().to_string())
+            }
+
+            SymbolContainer::None => None,
+        }
+    }
+
+    /*
+     * Formats the signatures as the RBS method definition with the overloads, e.g. "def self.build: () -> Invoice".
+     */
+    fn convert_to_rbs_definitions(rsymbol: &RSymbol, signatures: &[String]) -> Option<String> {
+        let name = rsymbol.full_scope().last()?;
+        let prefix = if matches!(rsymbol, RSymbol::SingletonMethod(_)) { "self." } else { "" };
+        let (first, overloads) = signatures.split_first()?;
+
+        let indent = " ".repeat(format!("def {prefix}{name}").len());
+        let overloads = overloads.iter().map(|o| format!("\n{indent}| {o}")).collect::<String>();
+
+        Some(format!("def {prefix}{name}: {first}{overloads}"))
+    }
+
+    fn convert_to_lsp_type_hierarchy_item(rsymbol: impl AsRef<RSymbol>) -> TypeHierarchyItem {
+        let symbol_info = Self::convert_to_lsp_sym_info(rsymbol);
+
+        TypeHierarchyItem {
+            name: symbol_info.name,
+            kind: symbol_info.kind,
+            tags: None,
+            detail: None,
+            uri: symbol_info.location.uri,
+            range: symbol_info.location.range,
+            selection_range: symbol_info.location.range,
+            data: None,
+        }
+    }
+
+    fn find_type_hierarchy_item<F>(&self, item: &TypeHierarchyItem, f: F) -> Vec<TypeHierarchyItem>
+    where
+        F: FnOnce(&RSymbol) -> Vec<Arc<RSymbol>>,
+    {
+        let location = Location {
+            uri: item.uri.clone(),
+            range: item.selection_range,
+        };
+
+        match self.find_symbol_by_location(&item.name, &location) {
+            Some(symbol) => f(&symbol).iter().map(Self::convert_to_lsp_type_hierarchy_item).collect(),
+            None => vec![],
         }
     }
 }
 
-impl<'a> Handler<WorkspaceSymbolParams> for Server<'a> {
-    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, WorkspaceSymbolParams)) -> Result<()> {
-        let (id, params) = request;
+impl NotificationHandler<DidOpenTextDocumentParams> for Server {
+    fn notify<N>(&self, params: DidOpenTextDocumentParams) -> Result<()> {
+        let file = params.text_document.uri.to_file_path().map_err(|_| anyhow!("Unsupported document uri"))?;
 
-        info!("got workspace/symbol request #{id}: {params:?}");
+        let document = if is_erb_file(&file) {
+            Document::new_erb(params.text_document.text)?
+        } else {
+            Document::new(params.text_document.text)?
+        };
+        self.finder.invalidate_definitions(&file);
+        if self.single_file {
+            self.finder.publish_file_index(Indexer::index_tree(
+                file.clone(),
+                &document.tree,
+                &document.source,
+                &self.config,
+            ));
+        }
+        self.documents.borrow_mut().insert(file, document);
+
+        Ok(())
+    }
+}
 
+impl NotificationHandler<DidChangeTextDocumentParams> for Server {
+    fn notify<N>(&self, params: DidChangeTextDocumentParams) -> Result<()> {
         let start = Instant::now();
+        let file = params.text_document.uri.to_file_path().map_err(|_| anyhow!("Unsupported document uri"))?;
 
-        let symbols: Vec<SymbolInformation> =
-            self.finder.fuzzy_find_symbol(&params.query).iter().map(Self::convert_to_lsp_sym_info).collect();
+        let mut documents = self.documents.borrow_mut();
+        let document = documents.get_mut(&file).ok_or(anyhow!("Document {file:?} wasn't opened"))?;
+        for change in params.content_changes {
+            document.apply_change(change)?;
+        }
+        self.finder.invalidate_definitions(&file);
+
+        // goto definition uses the tree of the document right away, only the index waits for a pause in typing
+        if self.config.reindex_debounce_ms == 0 {
+            self.finder.publish_file_index(Indexer::index_tree(file, &document.tree, &document.source, &self.config));
+        } else {
+            self.pending_reindex.borrow_mut().touch(file, Instant::now());
+        }
+
+        info!("textDocument/didChange took {:?}", start.elapsed());
+
+        Ok(())
+    }
+}
 
-        Self::send_response(sender, id, symbols)?;
+impl NotificationHandler<DidCloseTextDocumentParams> for Server {
+    fn notify<N>(&self, params: DidCloseTextDocumentParams) -> Result<()> {
+        let file = params.text_document.uri.to_file_path().map_err(|_| anyhow!("Unsupported document uri"))?;
 
-        let duration = start.elapsed();
+        self.documents.borrow_mut().remove(&file);
+        self.pending_reindex.borrow_mut().remove(&file);
+        self.finder.invalidate_definitions(&file);
 
-        info!("workspace/symbool took {:?}", duration);
+        // unsaved changes are discarded, so the file on disk is the source of truth again
+        if self.single_file {
+            self.finder.remove_file(&file);
+        } else if file.exists() {
+            self.finder.publish_file_index(Indexer::index_file(file, &self.config)?);
+        }
 
         Ok(())
     }
 }
 
-impl<'a> Handler<DocumentSymbolParams> for Server<'a> {
-    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, DocumentSymbolParams)) -> Result<()> {
+impl NotificationHandler<RenameFilesParams> for Server {
+    fn notify<N>(&self, params: RenameFilesParams) -> Result<()> {
+        for rename in params.files {
+            let to_path = |uri: &str| Url::parse(uri).ok().and_then(|u| u.to_file_path().ok());
+            let (Some(old_path), Some(new_path)) = (to_path(&rename.old_uri), to_path(&rename.new_uri)) else {
+                info!("skipping rename of non-file uri: {rename:?}");
+                continue;
+            };
+
+            self.pending_reindex.borrow_mut().remove(&old_path);
+            self.finder.rename_path(&old_path, &new_path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler<WorkspaceSymbolParams> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, WorkspaceSymbolParams)) -> Result<()> {
+        let (id, params) = request;
+
+        info!("got request: {params:?}");
+
         let start = Instant::now();
 
+        let token = self.cancellations.token(&id);
+        let Some(matches) = self.finder.fuzzy_find_symbol(&params.query, &token) else {
+            info!("cancelled after {:?}", start.elapsed());
+            return Self::send_cancelled(sender, id);
+        };
+        let symbols: Vec<WorkspaceSymbol> = matches
+            .iter()
+            .map(|m| {
+                let mut symbol = Self::convert_to_lsp_workspace_symbol(&m.symbol);
+                // positions of the matched characters in the name for clients highlighting them
+                symbol.data = Some(serde_json::json!({ "matchIndices": m.indices }));
+                symbol
+            })
+            .collect();
+
+        Self::send_response(sender, id, WorkspaceSymbolResponse::Nested(symbols))?;
+
+        Ok(())
+    }
+}
+
+impl Handler<WorkspaceSymbol> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, WorkspaceSymbol)) -> Result<()> {
+        let (id, mut workspace_symbol) = request;
+
+        info!("got request: {workspace_symbol:?}");
+
+        let rsymbol = match &workspace_symbol.location {
+            OneOf::Left(location) => self.find_symbol_by_location(&workspace_symbol.name, location),
+            OneOf::Right(_) => None,
+        };
+
+        workspace_symbol.container_name =
+            rsymbol.and_then(|s| Self::convert_to_lsp_container_name(&s, self.config.symbol_container, &self.root_dir));
+
+        Self::send_response(sender, id, workspace_symbol)
+    }
+}
+
+impl Handler<DocumentSymbolParams> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, DocumentSymbolParams)) -> Result<()> {
         let (id, params) = request;
 
-        info!("[#{id}] Got document/symbol request, params = {params:?}");
+        info!("got request: {params:?}");
+
+        let path = params.text_document.uri.to_file_path().map_err(|_| anyhow!("Unsupported document uri"))?;
+        let documents = self.documents.borrow();
+        let document = documents.get(&path);
 
-        let path = params.text_document.uri.to_file_path().unwrap();
-        let symbols: Vec<SymbolInformation> =
-            self.finder.find_by_path(&path).iter().map(Self::convert_to_lsp_sym_info).collect();
+        let symbols = if document.is_none() && !path.is_file() {
+            info!("{path:?} isn't a file, no symbols");
+            vec![]
+        } else {
+            let (symbols, tree) =
+                Self::find_file_symbols(self.finder.find_by_path(&path), &path, document, &self.config)?;
+            let outline: Vec<Arc<RSymbol>> =
+                symbols.into_iter().filter(|s| self.config.is_outline_kind(s.kind())).collect();
 
-        let result = serde_json::to_value(symbols).unwrap();
+            Self::convert_to_lsp_document_symbols(&outline, &tree)?
+        };
 
-        info!("[#{id}] document/symbol took {:?}", start.elapsed());
+        let result = serde_json::to_value(DocumentSymbolResponse::Nested(symbols)).unwrap();
 
         let resp = Response {
             id,
@@ -190,32 +817,47 @@ impl<'a> Handler<DocumentSymbolParams> for Server<'a> {
     }
 }
 
-impl<'a> Handler<GotoDefinitionParams> for Server<'a> {
+impl Handler<GotoDefinitionParams> for Server {
     fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, GotoDefinitionParams)) -> Result<()> {
         let (id, params) = request;
 
-        info!("got textDocument/definition request #{id}: {params:?}");
-
-        let start = Instant::now();
+        info!("got request: {params:?}");
 
-        let file = params.text_document_position_params.text_document.uri.to_file_path().unwrap();
+        let file = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_file_path()
+            .map_err(|_| anyhow!("Unsupported document uri"))?;
         let position = params.text_document_position_params.position;
         let position = Point {
             row: position.line.try_into()?,
             column: position.character.try_into()?,
         };
 
-        let symbols: Vec<Location> = self
-            .finder
-            .find_definition(file.as_path(), position)?
-            .iter()
-            .map(Self::convert_to_lsp_sym_info)
-            .map(|s| s.location)
-            .collect();
+        // the opened document can have changes which aren't saved or reindexed yet
+        let (tree, source) = match self.documents.borrow().get(&file) {
+            Some(document) => (document.tree.clone(), document.source.clone()),
+            None => read_file_tree(&file)?,
+        };
+
+        let symbols = self.finder.find_definition_in_tree(&file, &tree, &source, position)?;
+        let symbols = self.finder.limit_definitions(&file, symbols);
 
         info!("textDocument/definition found {} symbols", symbols.len());
 
-        let result = GotoDefinitionResponse::Array(symbols);
+        let result = if self.definition_link_support {
+            let origin_selection_range = Self::find_origin_range(&tree, &file, position)?;
+            GotoDefinitionResponse::Link(Self::convert_to_lsp_location_links(
+                &symbols,
+                &self.documents.borrow(),
+                origin_selection_range,
+            ))
+        } else {
+            GotoDefinitionResponse::Array(
+                symbols.iter().map(Self::convert_to_lsp_sym_info).map(|s| s.location).collect(),
+            )
+        };
         let result = serde_json::to_value(result).unwrap();
         let resp = Response {
             id,
@@ -224,10 +866,644 @@ impl<'a> Handler<GotoDefinitionParams> for Server<'a> {
         };
         sender.send(Message::Response(resp))?;
 
-        let duration = start.elapsed();
+        Ok(())
+    }
+}
 
-        info!("textDocument/definition took {:?}", duration);
+/*
+ * Shows the RBS signatures of the methods under the cursor, nothing is shown for the other symbols.
+ */
+impl Handler<HoverParams> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, HoverParams)) -> Result<()> {
+        let (id, params) = request;
 
-        Ok(())
+        info!("got request: {params:?}");
+
+        let file = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_file_path()
+            .map_err(|_| anyhow!("Unsupported document uri"))?;
+        let position = params.text_document_position_params.position;
+        let position = Point {
+            row: position.line.try_into()?,
+            column: position.character.try_into()?,
+        };
+
+        let (tree, source) = match self.documents.borrow().get(&file) {
+            Some(document) => (document.tree.clone(), document.source.clone()),
+            None => read_file_tree(&file)?,
+        };
+
+        // nothing is shown when the symbol can't be resolved, it's not worth an error on every mouse move
+        let symbols = self.finder.find_definition_in_tree(&file, &tree, &source, position).unwrap_or_else(|e| {
+            info!("Nothing to show on hover: {e}");
+            vec![]
+        });
+        let signatures: Vec<String> = symbols
+            .iter()
+            .flat_map(|s| Self::convert_to_rbs_definitions(s, self.finder.method_signatures(s)))
+            .collect();
+
+        let hover = (!signatures.is_empty()).then(|| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```rbs\n{}\n```", signatures.join("\n")),
+            }),
+            range: None,
+        });
+
+        Self::send_response(sender, id, hover)
+    }
+}
+
+impl Handler<RenameParams> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, RenameParams)) -> Result<()> {
+        use lsp_types::notification::Notification;
+
+        let (id, params) = request;
+
+        info!("got request: {params:?}");
+
+        if !self.config.experimental_rename {
+            let message = "Rename is experimental, it has to be enabled with experimentalRename".to_string();
+            return Self::send_error(sender, id, ErrorCode::RequestFailed, message);
+        }
+
+        let file = params
+            .text_document_position
+            .text_document
+            .uri
+            .to_file_path()
+            .map_err(|_| anyhow!("Unsupported document uri"))?;
+        let position = params.text_document_position.position;
+        let position = Point {
+            row: position.line.try_into()?,
+            column: position.character.try_into()?,
+        };
+
+        let rename = match self.finder.find_method_rename(&self.documents.borrow(), &file, position) {
+            Ok(rename) => rename,
+            Err(e) => return Self::send_error(sender, id, ErrorCode::RequestFailed, e.to_string()),
+        };
+        let edit = Self::convert_to_lsp_workspace_edit(&rename, &params.new_name)?;
+
+        if !rename.ambiguous.is_empty() {
+            let locations = rename
+                .ambiguous
+                .iter()
+                .map(|(file, point)| {
+                    let file = file.strip_prefix(&self.root_dir).unwrap_or(file);
+                    format!("{}:{}", file.display(), point.row + 1)
+                })
+                .join(", ");
+            let params = ShowMessageParams {
+                typ: MessageType::WARNING,
+                message: format!(
+                    "{} locations of {} couldn't be resolved confidently and weren't renamed: {locations}",
+                    rename.ambiguous.len(),
+                    rename.name
+                ),
+            };
+            let not = lsp_server::Notification::new(ShowMessage::METHOD.to_string(), params);
+            sender.send(Message::Notification(not))?;
+        }
+
+        Self::send_response(sender, id, edit)
+    }
+}
+
+impl Handler<TypeHierarchyPrepareParams> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, TypeHierarchyPrepareParams)) -> Result<()> {
+        let (id, params) = request;
+
+        info!("got request: {params:?}");
+
+        let file = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_file_path()
+            .map_err(|_| anyhow!("Unsupported document uri"))?;
+        let position = params.text_document_position_params.position;
+        let position = Point {
+            row: position.line.try_into()?,
+            column: position.character.try_into()?,
+        };
+
+        let items: Vec<TypeHierarchyItem> = self
+            .finder
+            .find_types(file.as_path(), position)?
+            .iter()
+            .map(Self::convert_to_lsp_type_hierarchy_item)
+            .collect();
+
+        Self::send_response(sender, id, items)
+    }
+}
+
+impl Handler<TypeHierarchySupertypesParams> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, TypeHierarchySupertypesParams)) -> Result<()> {
+        let (id, params) = request;
+
+        info!("got request: {params:?}");
+
+        let items = self.find_type_hierarchy_item(&params.item, |s| self.finder.find_supertypes(s));
+
+        Self::send_response(sender, id, items)
+    }
+}
+
+impl Handler<TypeHierarchySubtypesParams> for Server {
+    fn handle<R>(&self, sender: &Sender<Message>, request: (RequestId, TypeHierarchySubtypesParams)) -> Result<()> {
+        let (id, params) = request;
+
+        info!("got request: {params:?}");
+
+        let items = self.find_type_hierarchy_item(&params.item, |s| self.finder.find_subtypes(s));
+
+        Self::send_response(sender, id, items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use lsp_types::{
+        notification::Notification, request::Request, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+        TextDocumentItem, TextDocumentPositionParams, VersionedTextDocumentIdentifier, WorkspaceFolder,
+    };
+
+    use crate::{
+        finder::tests::{empty_finder, TempDir},
+        parsers::types::Scope,
+        types::RVariable,
+    };
+
+    use super::*;
+
+    #[test]
+    fn workspace_root_test() {
+        let mut params = InitializeParams::default();
+        assert_eq!(None, Server::workspace_root(&params));
+
+        params.workspace_folders = Some(vec![WorkspaceFolder {
+            uri: Url::parse("file:///projects/folder").unwrap(),
+            name: "folder".to_string(),
+        }]);
+        assert_eq!(Some(PathBuf::from("/projects/folder")), Server::workspace_root(&params));
+
+        params.root_uri = Some(Url::parse("file:///projects/root").unwrap());
+        assert_eq!(Some(PathBuf::from("/projects/root")), Server::workspace_root(&params));
+    }
+
+    #[test]
+    fn document_symbols_test() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/aliases/account.rb");
+        let symbols = Indexer::index_file(path.clone(), &Config::default()).unwrap().symbols;
+        let (tree, _) = read_file_tree(&path).unwrap();
+
+        let outline = Server::convert_to_lsp_document_symbols(&symbols, &tree).unwrap();
+
+        assert_eq!(1, outline.len());
+        let class = &outline[0];
+        assert_eq!("Account", class.name);
+        assert_eq!(Range::new(Position::new(0, 0), Position::new(8, 3)), class.range);
+        assert_eq!(Range::new(Position::new(0, 6), Position::new(0, 13)), class.selection_range);
+
+        let children = class.children.as_ref().unwrap();
+        let names: Vec<&str> = children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(vec!["balance", "current_balance", "total", "amount"], names);
+
+        // the multiline method spans all its lines, while the selection is its name
+        let method = &children[0];
+        assert_eq!(Range::new(Position::new(1, 2), Position::new(3, 5)), method.range);
+        assert_eq!(Range::new(Position::new(1, 6), Position::new(1, 13)), method.selection_range);
+    }
+
+    #[test]
+    fn unindexed_document_symbols_test() {
+        let fixture_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/unindexed");
+        let root = TempDir::new("unindexed_document_symbols");
+        let path = root.path().join("deploy.rb");
+        fs::copy(fixture_dir.join("deploy.rb"), &path).unwrap();
+
+        // the file is outside of the project root, so nothing indexed it
+        let finder = empty_finder(&fixture_dir, Config::default());
+        finder.publish_file_index(Indexer::index_file(fixture_dir.join("deploy.rb"), &Config::default()).unwrap());
+        let indexed = finder.find_by_path(&path);
+        assert!(indexed.is_empty());
+
+        let (symbols, tree) = Server::find_file_symbols(indexed, &path, None, &Config::default()).unwrap();
+        let outline = Server::convert_to_lsp_document_symbols(&symbols, &tree).unwrap();
+
+        assert_eq!(vec!["Deploy"], outline.iter().map(|s| s.name.as_str()).collect::<Vec<_>>());
+        let children = outline[0].children.as_ref().unwrap();
+        assert_eq!(vec!["production"], children.iter().map(|c| c.name.as_str()).collect::<Vec<_>>());
+
+        // files of the other languages aren't parsed as Ruby
+        let json = root.path().join("deploy.json");
+        fs::copy(fixture_dir.join("deploy.rb"), &json).unwrap();
+        let (symbols, _) = Server::find_file_symbols(vec![], &json, None, &Config::default()).unwrap();
+        assert!(symbols.is_empty());
+    }
+
+    #[test]
+    fn location_links_test() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/aliases/account.rb");
+        let symbols = Indexer::index_file(path.clone(), &Config::default()).unwrap().symbols;
+        let balance: Vec<Arc<RSymbol>> = symbols.into_iter().filter(|s| s.name() == "Account::balance").collect();
+        let origin = Range::new(Position::new(5, 4), Position::new(5, 11));
+
+        let links = Server::convert_to_lsp_location_links(&balance, &HashMap::new(), origin);
+
+        assert_eq!(1, links.len());
+        assert_eq!(Some(origin), links[0].origin_selection_range);
+        assert_eq!(Range::new(Position::new(1, 2), Position::new(3, 5)), links[0].target_range);
+        assert_eq!(Range::new(Position::new(1, 6), Position::new(1, 13)), links[0].target_selection_range);
+
+        // the opened document is preferred to the file on disk
+        let mut document = Document::new(fs::read_to_string(&path).unwrap()).unwrap();
+        document
+            .apply_change(TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(2, 12), Position::new(2, 12))),
+                range_length: None,
+                text: "\n    nil".to_string(),
+            })
+            .unwrap();
+        let documents = HashMap::from([(path, document)]);
+
+        let links = Server::convert_to_lsp_location_links(&balance, &documents, origin);
+        assert_eq!(Range::new(Position::new(1, 2), Position::new(4, 5)), links[0].target_range);
+    }
+
+    #[test]
+    fn changed_document_symbols_test() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/aliases/account.rb");
+        let indexed = Indexer::index_file(path.clone(), &Config::default()).unwrap().symbols;
+        let mut document = Document::new(fs::read_to_string(&path).unwrap()).unwrap();
+        document
+            .apply_change(TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(0, 6), Position::new(0, 13))),
+                range_length: None,
+                text: "Wallet".to_string(),
+            })
+            .unwrap();
+
+        // the document isn't reindexed yet, but its outline follows the changes
+        let (symbols, tree) = Server::find_file_symbols(indexed, &path, Some(&document), &Config::default()).unwrap();
+        let outline = Server::convert_to_lsp_document_symbols(&symbols, &tree).unwrap();
+
+        assert_eq!(vec!["Wallet"], outline.iter().map(|s| s.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(Range::new(Position::new(0, 6), Position::new(0, 12)), outline[0].selection_range);
+    }
+
+    #[test]
+    fn symbol_kind_test() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/symbol_kinds/kinds.rb");
+        let mut symbols = Indexer::index_file(path.clone(), &Config::default()).unwrap().symbols;
+        for variable in [RSymbol::Variable, RSymbol::ClassVariable] {
+            symbols.push(Arc::new(variable(RVariable {
+                file: path.clone(),
+                name: "count".to_string(),
+                scope: Scope::from("count"),
+                location: Point {
+                    row: 0,
+                    column: 0,
+                },
+                parent: None,
+            })));
+        }
+
+        let kinds: Vec<(&str, SymbolKind)> =
+            symbols.iter().map(|s| (s.kind(), Server::convert_to_lsp_symbol_kind(s))).collect();
+
+        let expected = vec![
+            ("global_variable", SymbolKind::VARIABLE),
+            ("constant", SymbolKind::CONSTANT),
+            ("singleton_method", SymbolKind::METHOD),
+            ("method", SymbolKind::METHOD),
+            ("class", SymbolKind::CLASS),
+            ("module", SymbolKind::MODULE),
+            ("variable", SymbolKind::VARIABLE),
+            ("class_variable", SymbolKind::FIELD),
+        ];
+        assert_eq!(expected, kinds);
+        assert!(kinds.iter().all(|(_, k)| *k != SymbolKind::NULL));
+    }
+
+    #[test]
+    fn workspace_edit_test() {
+        let file = PathBuf::from("/projects/app/invoice.rb");
+        let rename = MethodRename {
+            name: "total".to_string(),
+            locations: vec![(
+                file.clone(),
+                Point {
+                    row: 1,
+                    column: 6,
+                },
+            )],
+            ambiguous: vec![(
+                file,
+                Point {
+                    row: 4,
+                    column: 13,
+                },
+            )],
+        };
+
+        let edit = Server::convert_to_lsp_workspace_edit(&rename, "amount").unwrap();
+
+        let uri = Url::parse("file:///projects/app/invoice.rb").unwrap();
+        let expected = TextEdit::new(Range::new(Position::new(1, 6), Position::new(1, 11)), "amount".to_string());
+        assert_eq!(Some(&vec![expected]), edit.changes.unwrap().get(&uri));
+    }
+
+    #[test]
+    fn document_root_test() {
+        let uri = Url::parse("file:///tmp/scratch/script.rb").unwrap();
+        assert_eq!(Some(PathBuf::from("/tmp/scratch")), Server::document_root(&uri));
+
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        assert_eq!(None, Server::document_root(&uri));
+    }
+
+    #[test]
+    fn container_name_test() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/static_autoload");
+        let symbols = Indexer::index_file(root.join("app/controllers/admin/users_controller.rb"), &Config::default())
+            .unwrap()
+            .symbols;
+        let class = symbols.iter().find(|s| s.name() == "Admin::UsersController").unwrap();
+        let module = symbols.iter().find(|s| s.name() == "Admin").unwrap();
+
+        let container = |s: &RSymbol, c| Server::convert_to_lsp_container_name(s, c, &root);
+        assert_eq!(Some("Admin".to_string()), container(class, SymbolContainer::Scope));
+        assert_eq!(None, container(module, SymbolContainer::Scope));
+        assert_eq!(
+            Some("app/controllers/admin/users_controller.rb".to_string()),
+            container(class, SymbolContainer::Path)
+        );
+        assert_eq!(None, container(class, SymbolContainer::None));
+    }
+
+    fn unresolved_server(connection: &Connection) -> Server {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/unresolved");
+        let config = Config {
+            disable_shell_commands: true,
+            ..Default::default()
+        };
+        Server::new(&root, config, &ClientCapabilities::default(), &connection.sender, Cancellations::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn scope_top_level_methods_test() {
+        let (connection, _client) = Connection::memory();
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/top_level_methods");
+        let config = Config {
+            disable_shell_commands: true,
+            scope_top_level_methods: true,
+            ..Default::default()
+        };
+        let server =
+            Server::new(&root, config, &ClientCapabilities::default(), &connection.sender, Cancellations::default())
+                .unwrap();
+
+        // the indexed files are scoped once when they're published
+        let file = root.join("app/models/admin/user.rb");
+        let names: Vec<String> = server.finder.find_by_path(&file).iter().map(|s| s.full_scope().to_string()).collect();
+        assert_eq!(vec!["Admin::User::helper", "build", "Role::name", "Role"], names);
+        assert_eq!("Admin::User::helper", server.finder.find_by_path(&file)[0].name());
+    }
+
+    #[test]
+    fn reindex_top_level_methods_test() {
+        let (connection, _client) = Connection::memory();
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/top_level_methods");
+        let config = Config {
+            disable_shell_commands: true,
+            scope_top_level_methods: true,
+            reindex_debounce_ms: 0,
+            ..Default::default()
+        };
+        let server =
+            Server::new(&root, config, &ClientCapabilities::default(), &connection.sender, Cancellations::default())
+                .unwrap();
+        let file = root.join("app/models/admin/user.rb");
+        let methods = |server: &Server| -> Vec<String> {
+            let symbols = server.finder.find_by_path(&file);
+            symbols.iter().filter(|s| s.kind() == "method").map(|s| s.name().to_string()).collect()
+        };
+
+        // the changed document is reindexed and scoped again from its own source
+        let uri = Url::from_file_path(&file).unwrap();
+        let text = fs::read_to_string(&file).unwrap();
+        let params = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "ruby".to_string(), 1, text),
+        };
+        server
+            .handle_notification(lsp_server::Notification::new(DidOpenTextDocument::METHOD.to_string(), params))
+            .unwrap();
+        let params = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri, 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(0, 10), Position::new(0, 10))),
+                range_length: None,
+                text: "s".to_string(),
+            }],
+        };
+        server
+            .handle_notification(lsp_server::Notification::new(DidChangeTextDocument::METHOD.to_string(), params))
+            .unwrap();
+        assert_eq!(vec!["Admin::User::helpers", "Role::name"], methods(&server));
+
+        // publishing the already scoped methods doesn't scope them twice
+        let converter = RubyFilenameConverter::with_autoload_paths(&root, vec![PathBuf::from("app/models")]);
+        let file_index = Indexer::index_file(file.clone(), &Config::default()).unwrap();
+        server.finder.publish_file_index(Indexer::scope_top_level_methods(file_index, &converter));
+        assert_eq!(vec!["Admin::User::helper", "Role::name"], methods(&server));
+    }
+
+    #[test]
+    fn single_file_test() {
+        let (connection, _client) = Connection::memory();
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/unresolved");
+        let config = Config {
+            disable_shell_commands: true,
+            ..Default::default()
+        };
+        let server = Server::new_single_file(
+            &root,
+            config,
+            &ClientCapabilities::default(),
+            &connection.sender,
+            Cancellations::default(),
+        )
+        .unwrap();
+        assert!(server.symbols.load().is_empty());
+
+        let file = root.join("script.rb");
+        let uri = Url::from_file_path(&file).unwrap();
+        let params = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "ruby".to_string(), 1, "class Script\nend\n".to_string()),
+        };
+        server
+            .handle_notification(lsp_server::Notification::new(DidOpenTextDocument::METHOD.to_string(), params))
+            .unwrap();
+
+        // the other files of the directory aren't indexed
+        assert_eq!(1, server.symbols.load().files_len());
+        assert_eq!(vec!["Script"], server.finder.find_by_path(&file).iter().map(|s| s.name()).collect::<Vec<_>>());
+
+        let params = DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier::new(uri),
+        };
+        server
+            .handle_notification(lsp_server::Notification::new(DidCloseTextDocument::METHOD.to_string(), params))
+            .unwrap();
+        assert!(server.symbols.load().is_empty());
+    }
+
+    fn goto_definition_request(file: &Path, position: Position) -> lsp_server::Request {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(Url::from_file_path(file).unwrap()),
+                position,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        lsp_server::Request::new(RequestId::from(1), GotoDefinition::METHOD.to_string(), params)
+    }
+
+    fn first_response(client: &Connection) -> Response {
+        client
+            .receiver
+            .try_iter()
+            .find_map(|m| match m {
+                Message::Response(r) => Some(r),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn unsupported_node_request_test() {
+        let (connection, client) = Connection::memory();
+        let server = unresolved_server(&connection);
+
+        // nothing to resolve on the keyword, the result is empty
+        let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/unresolved/report.rb");
+        let request = goto_definition_request(&file, Position::new(0, 1));
+        assert!(server.handle_request(&connection, request).is_ok());
+
+        let response = first_response(&client);
+        assert_eq!(RequestId::from(1), response.id);
+        assert!(response.error.is_none());
+        assert_eq!(Some(serde_json::json!([])), response.result);
+    }
+
+    #[test]
+    fn failed_request_test() {
+        let (connection, client) = Connection::memory();
+        let server = unresolved_server(&connection);
+
+        // the file can't be read, the server responds with an error and keeps running
+        let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/unresolved/missing.rb");
+        let request = goto_definition_request(&file, Position::new(0, 1));
+        assert!(server.handle_request(&connection, request).is_ok());
+
+        let response = first_response(&client);
+        assert_eq!(RequestId::from(1), response.id);
+        assert_eq!(Some(ErrorCode::RequestFailed as i32), response.error.map(|e| e.code));
+    }
+
+    #[test]
+    fn unknown_method_test() {
+        let (connection, client) = Connection::memory();
+        let server = unresolved_server(&connection);
+
+        let request = lsp_server::Request::new(RequestId::from(1), "textDocument/unknown".to_string(), ());
+        assert!(server.handle_request(&connection, request).is_ok());
+
+        let response = first_response(&client);
+        assert_eq!(Some(ErrorCode::MethodNotFound as i32), response.error.map(|e| e.code));
+    }
+
+    #[test]
+    fn hover_signatures_test() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rbs");
+        let (connection, client) = Connection::memory();
+        let config = Config {
+            disable_shell_commands: true,
+            rbs_signatures: true,
+            ..Default::default()
+        };
+        let server =
+            Server::new(&root, config, &ClientCapabilities::default(), &connection.sender, Cancellations::default())
+                .unwrap();
+
+        let hover = |id: i32, line, character| {
+            let params = HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier::new(
+                        Url::from_file_path(root.join("app/services/checkout.rb")).unwrap(),
+                    ),
+                    position: Position::new(line, character),
+                },
+                work_done_progress_params: Default::default(),
+            };
+            let request = lsp_server::Request::new(RequestId::from(id), HoverRequest::METHOD.to_string(), params);
+            server.handle_request(&connection, request).unwrap();
+
+            let response = client
+                .receiver
+                .try_iter()
+                .find_map(|m| match m {
+                    Message::Response(r) => Some(r),
+                    _ => None,
+                })
+                .unwrap();
+            serde_json::from_value::<Option<Hover>>(response.result.unwrap()).unwrap()
+        };
+
+        let Some(Hover {
+            contents: HoverContents::Markup(content),
+            ..
+        }) = hover(1, 3, 26)
+        else {
+            panic!("Expected the signature of total_with_tax");
+        };
+        assert_eq!("```rbs\ndef total_with_tax: (Float rate) -> Float\n```", content.value);
+
+        let Some(Hover {
+            contents: HoverContents::Markup(content),
+            ..
+        }) = hover(2, 3, 12)
+        else {
+            panic!("Expected the signature of build");
+        };
+        assert_eq!("```rbs\ndef self.build: (Integer total) -> Invoice\n```", content.value);
+
+        // the local variable doesn't have a signature
+        assert_eq!(None, hover(3, 3, 18));
+    }
+
+    #[test]
+    fn rbs_definitions_test() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rbs/app/models/invoice.rb");
+        let symbols = Indexer::index_file(path, &Config::default()).unwrap().symbols;
+        let build = symbols.iter().find(|s| s.name() == "Invoice::build").unwrap();
+
+        let signatures = vec!["(Integer total) -> Invoice".to_string(), "() -> Invoice".to_string()];
+        assert_eq!(
+            Some("def self.build: (Integer total) -> Invoice\n              | () -> Invoice".to_string()),
+            Server::convert_to_rbs_definitions(build, &signatures)
+        );
+        assert_eq!(None, Server::convert_to_rbs_definitions(build, &[]));
     }
 }
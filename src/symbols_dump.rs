@@ -0,0 +1,141 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+use itertools::Itertools;
+use log::warn;
+use serde::Serialize;
+use tree_sitter::Point;
+
+use crate::{
+    config::Config,
+    indexer::{FileIndex, Indexer},
+    parsers::general::{get_definition_nodes, read_file_tree},
+    types::RSymbol,
+};
+
+/*
+ * Serializable projection of an indexed symbol, printed by the `--index <dir>` command
+ * to inspect what's indexed outside of an editor.
+ */
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct SymbolDump {
+    pub kind: String,
+    pub name: String,
+    pub scope: String,
+    pub file: String,
+    pub start: Position,
+    pub end: Position,
+}
+
+/*
+ * Zero-based position, the same as the tree-sitter point.
+ */
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<Point> for Position {
+    fn from(point: Point) -> Self {
+        Position {
+            line: point.row,
+            column: point.column,
+        }
+    }
+}
+
+/*
+ * Indexes the Ruby files of the directory, the same files as the project part of the LSP index.
+ */
+pub fn dump_dir(dir: &Path, config: &Config) -> Result<Vec<SymbolDump>> {
+    let file_indexes = Indexer::index_files(dir, config);
+
+    Ok(file_indexes.iter().sorted_by(|a, b| a.file.cmp(&b.file)).flat_map(dump_file_index).collect())
+}
+
+/*
+ * The file is parsed again to get the range of the definitions, symbols only keep the location of their name.
+ * A file that can't be read anymore is skipped, the rest of the directory is still dumped.
+ */
+fn dump_file_index(file_index: &FileIndex) -> Vec<SymbolDump> {
+    if file_index.symbols.is_empty() {
+        return vec![];
+    }
+
+    let tree = match read_file_tree(&file_index.file) {
+        Ok((tree, _)) => tree,
+        Err(e) => {
+            warn!("skipping {:?} in the dump: {e}", file_index.file);
+            return vec![];
+        }
+    };
+    let mut ranges: HashMap<Point, (Point, Point)> = HashMap::new();
+
+    file_index
+        .symbols
+        .iter()
+        .map(|symbol| {
+            let location = *symbol.location();
+            let (start, end) = *ranges.entry(location).or_insert_with(|| {
+                get_definition_nodes(&tree, location)
+                    .map(|(_, definition)| (definition.start_position(), definition.end_position()))
+                    .unwrap_or((location, location))
+            });
+
+            dump_symbol(symbol, start, end)
+        })
+        .collect()
+}
+
+fn dump_symbol(symbol: &RSymbol, start: Point, end: Point) -> SymbolDump {
+    SymbolDump {
+        kind: symbol.kind().to_string(),
+        name: symbol.name().to_string(),
+        scope: symbol.full_scope().to_string(),
+        file: symbol.file().to_string_lossy().to_string(),
+        start: start.into(),
+        end: end.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    #[test]
+    fn dump_dir_test() {
+        let dir = Path::new(FIXTURES_DIR).join("aliases");
+
+        let dumps = dump_dir(&dir, &Config::default()).unwrap();
+        let class = dumps.iter().find(|d| d.kind == "class").unwrap();
+
+        assert_eq!("Account", class.name);
+        assert_eq!("Account", class.scope);
+        assert!(class.file.ends_with("aliases/account.rb"));
+        // the range is the whole definition, not only its name
+        assert_eq!(
+            Position {
+                line: 0,
+                column: 0
+            },
+            class.start
+        );
+        assert!(class.end.line > class.start.line);
+
+        let json = serde_json::to_value(class).unwrap();
+        assert_eq!(serde_json::json!({ "line": 0, "column": 0 }), json["start"]);
+    }
+
+    #[test]
+    fn dump_removed_file_test() {
+        let dir = Path::new(FIXTURES_DIR).join("aliases");
+        let (tree, source) = read_file_tree(&dir.join("account.rb")).unwrap();
+        let file_index = Indexer::index_tree(dir.join("removed.rb"), &tree, &source, &Config::default());
+
+        assert!(!file_index.symbols.is_empty());
+        assert_eq!(Vec::<SymbolDump>::new(), dump_file_index(&file_index));
+    }
+}
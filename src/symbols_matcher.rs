@@ -1,52 +1,368 @@
 use std::cmp::Reverse;
-use std::path::Path;
 use std::sync::Arc;
 
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
+use itertools::Itertools;
 
-use crate::types::RSymbol;
+use crate::{
+    cancellation::CancellationToken,
+    parsers::types::SCOPE_DELIMITER,
+    types::{RSymbol, SourceKind},
+};
 
-pub struct SymbolsMatcher<'a> {
+// number of symbols scored between the cancellation checks
+const CANCELLATION_CHECK_INTERVAL: usize = 4096;
+
+/*
+ * Symbol matched by the query with the positions of the matched characters in its name.
+ */
+pub struct SymbolMatch {
+    pub symbol: Arc<RSymbol>,
+    pub indices: Vec<usize>,
+}
+
+pub struct SymbolsMatcher {
     matcher: SkimMatcherV2,
-    root_path: &'a Path,
 }
 
-impl<'a> SymbolsMatcher<'a> {
-    pub fn new(root_path: &'a Path) -> SymbolsMatcher {
+impl Default for SymbolsMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolsMatcher {
+    pub fn new() -> SymbolsMatcher {
         SymbolsMatcher {
             matcher: SkimMatcherV2::default().smart_case(),
-            root_path,
         }
     }
 
-    pub fn match_rsymbols(&self, query: &str, symbols: &[Arc<RSymbol>]) -> Vec<Arc<RSymbol>> {
-        let mut scores: Vec<(Arc<RSymbol>, [i32; 5])> = symbols
-            .iter()
-            .filter_map(|s| {
+    /*
+     * Matches the symbols along with the source of their files against the query,
+     * returns None if the request was cancelled while matching.
+     */
+    pub fn match_rsymbols<'b>(
+        &self,
+        query: &str,
+        symbols: impl IntoIterator<Item = (&'b Arc<RSymbol>, SourceKind)>,
+        token: &CancellationToken,
+    ) -> Option<Vec<SymbolMatch>> {
+        let query_segments = split_query(query);
+
+        let mut scores: Vec<(SymbolMatch, [i32; 6])> = Vec::new();
+        for chunk in &symbols.into_iter().chunks(CANCELLATION_CHECK_INTERVAL) {
+            if token.is_cancelled() {
+                return None;
+            }
+
+            scores.extend(chunk.filter_map(|(s, source)| {
                 let name = s.name();
+                let unsigiled = strip_variable_sigil(s);
 
-                match self.matcher.fuzzy_indices(name, query) {
-                    None => None,
-                    Some((score, indices)) => {
-                        let start = *indices.first().unwrap_or(&0);
-                        let end = *indices.last().unwrap_or(&0);
-                        let len = name.len();
+                let is_abbreviation = matches_abbreviation(&query_segments, name)
+                    || unsigiled.as_ref().is_some_and(|(n, _, _)| matches_abbreviation(&query_segments, n));
+                let (score, indices) = match self.fuzzy_indices(name, unsigiled.as_ref(), query) {
+                    Some(m) => m,
+                    None if is_abbreviation => (0, vec![]),
+                    None => return None,
+                };
 
-                        let s_path = s.file();
-                        let in_root = if s_path.starts_with(self.root_path) { 1 } else { -1 };
+                let start = *indices.first().unwrap_or(&0);
+                let end = *indices.last().unwrap_or(&0);
+                let len = name.len();
 
-                        let rank = [score as i32, in_root, -(start as i32), -(end as i32), -(len as i32)];
+                // the project first, then gems, then stubs
+                let priority = -(source.priority() as i32);
 
-                        Some((s.clone(), rank))
-                    }
-                }
-            })
-            .map(|m| (m.0, m.1))
-            .collect();
+                let rank =
+                    [is_abbreviation as i32, score as i32, priority, -(start as i32), -(end as i32), -(len as i32)];
+
+                let symbol_match = SymbolMatch {
+                    symbol: s.clone(),
+                    indices,
+                };
+
+                Some((symbol_match, rank))
+            }));
+        }
 
         scores.sort_by_key(|m| Reverse(m.1));
 
-        scores.iter().map(|m| m.0.clone()).collect()
+        Some(scores.into_iter().map(|m| m.0).collect())
+    }
+
+    /*
+     * Matches the name and the name without the sigil if it's a variable, whichever scores higher.
+     * Indices of the match without the sigil are mapped back to the full name.
+     */
+    fn fuzzy_indices(&self, name: &str, unsigiled: Option<&UnsigiledName>, query: &str) -> Option<(i64, Vec<usize>)> {
+        let full_match = self.matcher.fuzzy_indices(name, query);
+
+        let unsigiled_match = unsigiled.and_then(|(unsigiled_name, start, sigil_len)| {
+            let (score, indices) = self.matcher.fuzzy_indices(unsigiled_name, query)?;
+            let indices = indices.into_iter().map(|i| if i >= *start { i + sigil_len } else { i }).collect();
+            Some((score, indices))
+        });
+
+        [full_match, unsigiled_match].into_iter().flatten().max_by_key(|(score, _)| *score)
+    }
+}
+
+/*
+ * Variable name without the sigil, the position of the sigil and its length in characters.
+ */
+type UnsigiledName = (String, usize, usize);
+
+/*
+ * Strips the sigil of the variable name, e.g. "@count" to "count" or "Foo::@@count" to "Foo::count".
+ */
+fn strip_variable_sigil(symbol: &RSymbol) -> Option<UnsigiledName> {
+    if !matches!(symbol, RSymbol::Variable(_) | RSymbol::GlobalVariable(_) | RSymbol::ClassVariable(_)) {
+        return None;
+    }
+
+    let name = symbol.name();
+    let start = name.rfind(SCOPE_DELIMITER).map(|i| i + SCOPE_DELIMITER.len()).unwrap_or(0);
+    let sigil_len = name[start..].chars().take_while(|c| matches!(c, '@' | '$')).count();
+    if sigil_len == 0 {
+        return None;
+    }
+
+    let unsigiled_name = format!("{}{}", &name[..start], &name[start + sigil_len..]);
+    Some((unsigiled_name, name[..start].chars().count(), sigil_len))
+}
+
+/*
+ * Splits the query into scope segments, both "::" and "." are accepted as delimiters (e.g. "a.c.user").
+ */
+fn split_query(query: &str) -> Vec<&str> {
+    query.split([':', '.']).filter(|s| !s.is_empty()).collect()
+}
+
+/*
+ * Checks if each query segment abbreviates the corresponding trailing segment of the name,
+ * e.g. "ar::base" abbreviates "ActiveRecord::Base" and "a.c.user" abbreviates "App::Controllers::User".
+ */
+fn matches_abbreviation(query_segments: &[&str], name: &str) -> bool {
+    let name_segments: Vec<&str> = name.split(SCOPE_DELIMITER).collect();
+    if query_segments.is_empty() || query_segments.len() > name_segments.len() {
+        return false;
+    }
+
+    let trailing_segments = &name_segments[name_segments.len() - query_segments.len()..];
+    query_segments.iter().zip(trailing_segments).all(|(q, n)| {
+        let query: Vec<char> = q.to_lowercase().chars().collect();
+        abbreviates(&query, &split_words(n))
+    })
+}
+
+/*
+ * Checks if the query is made of prefixes of the words in order, starting with the first word,
+ * e.g. "ar", "actrec" and "active" abbreviate ["active", "record"].
+ * `matched[w][q]` tells if the query from `q` is made of prefixes of the words from `w`, the first one included,
+ * it's filled from the last word so that each state is computed once instead of backtracking.
+ */
+fn abbreviates(query: &[char], words: &[Vec<char>]) -> bool {
+    if query.is_empty() {
+        return true;
+    }
+
+    // whether the query from `q` matches prefixes of any words after the current one
+    let mut matched_after = vec![false; query.len() + 1];
+    matched_after[query.len()] = true;
+    let mut matched_first = false;
+
+    for word in words.iter().rev() {
+        let mut matched = vec![false; query.len() + 1];
+        matched[query.len()] = true;
+        for (q, m) in matched.iter_mut().enumerate().take(query.len()) {
+            let common = query[q..].iter().zip(word).take_while(|(q, w)| q == w).count();
+            *m = (1..=common).any(|taken| matched_after[q + taken]);
+        }
+
+        matched_first = matched[0];
+        for (after, m) in matched_after.iter_mut().zip(matched) {
+            *after |= m;
+        }
+    }
+
+    matched_first
+}
+
+/*
+ * Splits a camel case or snake case name into lowercase words, e.g. "ActiveRecord" into ["active", "record"].
+ */
+fn split_words(name: &str) -> Vec<Vec<char>> {
+    let mut words: Vec<Vec<char>> = Vec::new();
+    let mut previous: Option<char> = None;
+
+    for c in name.chars() {
+        let is_boundary = match previous {
+            None => true,
+            Some(p) => p == '_' || (c.is_uppercase() && !p.is_uppercase()),
+        };
+
+        if c != '_' {
+            if is_boundary || words.is_empty() {
+                words.push(Vec::new());
+            }
+            words.last_mut().unwrap().extend(c.to_lowercase());
+        }
+
+        previous = Some(c);
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use tree_sitter::{Parser, Point};
+
+    use crate::{
+        config::Config,
+        parsers::{general::parse, types::Scope},
+        types::RVariable,
+    };
+
+    use super::*;
+
+    fn parse_source(source: &str) -> Vec<Arc<RSymbol>> {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ruby::language()).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+
+        root.named_children(&mut cursor)
+            .flat_map(|n| parse(Path::new("/foo.rb"), source.as_bytes(), n, None, Config::default().max_nesting_depth))
+            .collect()
+    }
+
+    fn in_project(symbols: &[Arc<RSymbol>]) -> impl Iterator<Item = (&Arc<RSymbol>, SourceKind)> {
+        symbols.iter().map(|s| (s, SourceKind::Project))
+    }
+
+    fn match_names(query: &str, symbols: &[Arc<RSymbol>]) -> Vec<String> {
+        let matcher = SymbolsMatcher::new();
+        let token = CancellationToken::default();
+        matcher
+            .match_rsymbols(query, in_project(symbols), &token)
+            .unwrap()
+            .iter()
+            .map(|m| m.symbol.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn abbreviation_test() {
+        let source = r#"
+module Arel
+  class Nodes; end
+  class DatabaseBase; end
+end
+
+class Barbase; end
+
+module ActiveRecord
+  class Base; end
+end
+
+module App
+  module Controllers
+    class User; end
+    class UsersController; end
+  end
+end
+"#;
+        let symbols = parse_source(source);
+
+        assert_eq!(Some("ActiveRecord::Base"), match_names("ar::base", &symbols).first().map(String::as_str));
+        assert_eq!(Some("ActiveRecord::Base"), match_names("AR::Base", &symbols).first().map(String::as_str));
+        assert_eq!(
+            vec!["App::Controllers::User", "App::Controllers::UsersController"],
+            match_names("a.c.user", &symbols)
+        );
+    }
+
+    #[test]
+    fn match_indices_test() {
+        let symbols = parse_source("module Foo; class Bar; end; end");
+        let matcher = SymbolsMatcher::new();
+        let matches = matcher.match_rsymbols("fbar", in_project(&symbols), &CancellationToken::default()).unwrap();
+
+        assert_eq!(1, matches.len());
+        assert_eq!("Foo::Bar", matches[0].symbol.name());
+        assert_eq!(vec![0, 5, 6, 7], matches[0].indices);
+    }
+
+    fn variable(kind: fn(RVariable) -> RSymbol, name: &str) -> Arc<RSymbol> {
+        Arc::new(kind(RVariable {
+            file: PathBuf::from("/foo.rb"),
+            name: name.to_string(),
+            scope: Scope::from(name),
+            location: Point::default(),
+            parent: None,
+        }))
+    }
+
+    #[test]
+    fn variable_sigil_test() {
+        let symbols = vec![
+            variable(RSymbol::Variable, "@discount"),
+            variable(RSymbol::ClassVariable, "Stats::@@count"),
+            variable(RSymbol::Variable, "@count"),
+            variable(RSymbol::GlobalVariable, "$stdout"),
+        ];
+        let matcher = SymbolsMatcher::new();
+        let matches = matcher.match_rsymbols("count", in_project(&symbols), &CancellationToken::default()).unwrap();
+
+        let names: Vec<&str> = matches.iter().map(|m| m.symbol.name()).collect();
+        assert_eq!(vec!["@count", "Stats::@@count", "@discount"], names);
+        assert_eq!(vec![1, 2, 3, 4, 5], matches[0].indices);
+        assert_eq!(vec![9, 10, 11, 12, 13], matches[1].indices);
+    }
+
+    #[test]
+    fn cancelled_test() {
+        let symbols = parse_source("class Foo; end");
+        let matcher = SymbolsMatcher::new();
+        let token = CancellationToken::default();
+        token.cancel();
+
+        assert!(matcher.match_rsymbols("foo", in_project(&symbols), &token).is_none());
+    }
+
+    #[test]
+    fn matches_abbreviation_test() {
+        assert!(matches_abbreviation(&["ar", "base"], "ActiveRecord::Base"));
+        assert!(matches_abbreviation(&["actrec"], "ActiveRecord"));
+        assert!(matches_abbreviation(&["base"], "ActiveRecord::Base"));
+        assert!(matches_abbreviation(&["uc"], "App::UsersController"));
+        assert!(matches_abbreviation(&["fb"], "foo_bar"));
+
+        assert!(!matches_abbreviation(&["rb"], "ActiveRecord::Base"));
+        assert!(!matches_abbreviation(&["ar", "base", "x"], "ActiveRecord::Base"));
+        assert!(!matches_abbreviation(&["ab"], "ActiveRecord"));
+        // words can be skipped after the first one
+        assert!(matches_abbreviation(&["auc"], "AdminUsersController"));
+        assert!(matches_abbreviation(&["ac"], "AdminUsersController"));
+        assert!(!matches_abbreviation(&["uc"], "AdminUsersController"));
+    }
+
+    #[test]
+    fn long_abbreviation_test() {
+        let start = std::time::Instant::now();
+        let name = vec!["a"; 40].join("_");
+        let query = "a".repeat(39) + "b";
+
+        // every split of the query into the words is tried once at most
+        assert!(!matches_abbreviation(&[&query], &name));
+        assert!(matches_abbreviation(&[&query[..39]], &name));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1), "Matching took {:?}", start.elapsed());
     }
 }
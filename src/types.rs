@@ -1,13 +1,104 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
+use arc_swap::ArcSwap;
 use tree_sitter::Point;
 
 use crate::parsers::types::Scope;
 
-#[allow(dead_code)]
+/*
+ * Symbols shared between the server and the finder. `ArcSwap` lets a reindex atomically publish
+ * a new set of symbols while requests keep reading the set they've already loaded.
+ */
+pub type RSymbols = Arc<ArcSwap<RSymbolIndex>>;
+
+/*
+ * Symbols of the indexed files by the file path. Reindexing a file replaces only its own symbols,
+ * the published copy shares the symbols of the other files with the previous one.
+ */
+#[derive(Default, Clone)]
+pub struct RSymbolIndex {
+    files: BTreeMap<PathBuf, Arc<RFileSymbols>>,
+}
+
+/*
+ * Symbols and references of a file and where the file comes from, it's known when the file is indexed.
+ */
+pub struct RFileSymbols {
+    pub source: SourceKind,
+    pub symbols: Vec<Arc<RSymbol>>,
+    pub references: RReferences,
+}
+
+impl RSymbolIndex {
+    pub fn insert(&mut self, file: PathBuf, source: SourceKind, symbols: Vec<Arc<RSymbol>>, references: RReferences) {
+        self.files.insert(
+            file,
+            Arc::new(RFileSymbols {
+                source,
+                symbols,
+                references,
+            }),
+        );
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&Path) -> bool) {
+        self.files.retain(|file, _| f(file));
+    }
+
+    pub fn get(&self, file: &Path) -> &[Arc<RSymbol>] {
+        self.files.get(file).map(|f| f.symbols.as_slice()).unwrap_or_default()
+    }
+
+    pub fn source(&self, file: &Path) -> Option<SourceKind> {
+        self.files.get(file).map(|f| f.source)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<RSymbol>> + Clone {
+        self.files.values().flat_map(|f| f.symbols.iter())
+    }
+
+    /*
+     * Iterates the symbols along with the source of their files.
+     */
+    pub fn iter_with_source(&self) -> impl Iterator<Item = (&Arc<RSymbol>, SourceKind)> {
+        self.files.values().flat_map(|f| f.symbols.iter().map(|s| (s, f.source)))
+    }
+
+    /*
+     * Iterates the positions of the references with the name in all files.
+     */
+    pub fn references<'b>(&'b self, name: &'b str) -> impl Iterator<Item = (&'b Path, Point)> {
+        self.files.iter().flat_map(move |(file, f)| {
+            f.references.get(name).into_iter().flatten().map(move |point| (file.as_path(), *point))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.values().map(|f| f.symbols.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.values().all(|f| f.symbols.is_empty())
+    }
+
+    pub fn files_len(&self) -> usize {
+        self.files.len()
+    }
+}
+
+/*
+ * Positions of the references in a file by the referenced name.
+ */
+pub type RReferences = HashMap<String, Vec<Point>>;
+
+/*
+ * Symbols outlive the trees they were parsed from, so they only keep owned data (file and location),
+ * the node itself is located again in a freshly parsed tree when needed.
+ */
 #[derive(PartialEq, Eq)]
 pub enum RSymbol {
     Class(RClass),
@@ -114,6 +205,31 @@ impl std::fmt::Debug for RSymbol {
     }
 }
 
+/*
+ * Where the symbol comes from, e.g. test files are excluded from the search if configured.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Project,
+    Test,
+    Gem,
+    Stub,
+}
+
+impl SourceKind {
+    /*
+     * Order in which the definitions with the same name are preferred, so that a monkey-patch
+     * in the project comes before the gem and the core class stub it reopens.
+     */
+    pub fn priority(&self) -> u8 {
+        match self {
+            SourceKind::Project | SourceKind::Test => 0,
+            SourceKind::Gem => 1,
+            SourceKind::Stub => 2,
+        }
+    }
+}
+
 #[derive(PartialEq, Eq)]
 pub struct RClass {
     pub file: PathBuf,
@@ -121,6 +237,8 @@ pub struct RClass {
     pub scope: Scope,
     pub location: Point,
     pub superclass_scopes: Scope,
+    pub include_scopes: Vec<Scope>,
+    pub prepend_scopes: Vec<Scope>,
     pub parent: Option<Arc<RSymbol>>,
 }
 
@@ -131,9 +249,19 @@ pub struct RMethod {
     pub scope: Scope,
     pub location: Point,
     pub parameters: Vec<RMethodParam>,
+    pub visibility: Visibility,
     pub parent: Option<Arc<RSymbol>>,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Visibility {
+    #[default]
+    Public,
+    Protected,
+    Private,
+}
+
 #[derive(PartialEq, Eq)]
 pub enum RMethodParam {
     Regular(MethodParam),
@@ -0,0 +1,3 @@
+def root_node
+  Node.new
+end
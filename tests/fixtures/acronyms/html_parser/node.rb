@@ -0,0 +1,2 @@
+class HTMLParser::Node
+end
@@ -0,0 +1,2 @@
+class XMLParser::Node
+end
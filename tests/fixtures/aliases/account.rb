@@ -0,0 +1,9 @@
+class Account
+  def balance
+    @balance
+  end
+
+  alias_method :current_balance, :balance
+  alias total balance
+  alias_method "amount", "balance"
+end
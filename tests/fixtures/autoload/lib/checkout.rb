@@ -0,0 +1,5 @@
+class Checkout
+  def widget
+    Shop::Widget.new
+  end
+end
@@ -0,0 +1,3 @@
+module Shop
+  autoload :Widget, "shop/widget"
+end
@@ -0,0 +1,15 @@
+class Document
+  def reload
+    @content = nil
+  end
+
+  def refresh
+    reload
+    content = load
+    content
+  end
+
+  def content
+    @content
+  end
+end
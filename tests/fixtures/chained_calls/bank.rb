@@ -0,0 +1,37 @@
+class Balance
+  def amount; end
+end
+
+class Account
+  def initialize
+    @balance = Balance.new
+  end
+
+  def balance
+    @balance
+  end
+end
+
+class User
+  def account
+    Account.new
+  end
+
+  def total
+    account.balance.amount
+  end
+end
+
+class Ledger
+  def amount; end
+
+  def balance; end
+end
+
+class Report
+  def render(user)
+    current = User.new
+    current.account.balance.amount
+    user.account.balance
+  end
+end
@@ -0,0 +1,25 @@
+class Profile
+  def name; end
+end
+
+class Current
+  def self.profile
+    Profile.new
+  end
+
+  def self.session; end
+end
+
+class Greeting
+  def text
+    Current.profile.name
+  end
+
+  def expiry
+    Current.session.expires_at
+  end
+end
+
+class Session
+  def expires_at; end
+end
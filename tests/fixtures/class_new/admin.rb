@@ -0,0 +1,5 @@
+Admin = Class.new(ApplicationRecord) do
+  def promote
+    save
+  end
+end
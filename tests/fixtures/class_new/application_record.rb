@@ -0,0 +1,3 @@
+class ApplicationRecord
+  def save; end
+end
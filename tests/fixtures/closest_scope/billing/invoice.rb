@@ -0,0 +1,7 @@
+module Billing
+  class Invoice
+    def config
+      Config.new("usd")
+    end
+  end
+end
@@ -0,0 +1,5 @@
+module Billing
+  module Settings
+    Config = Struct.new(:currency)
+  end
+end
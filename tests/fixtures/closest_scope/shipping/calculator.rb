@@ -0,0 +1,7 @@
+module Shipping
+  class Calculator
+    def config
+      Config.new("ups")
+    end
+  end
+end
@@ -0,0 +1,5 @@
+module Shipping
+  module Settings
+    Config = Struct.new(:carrier)
+  end
+end
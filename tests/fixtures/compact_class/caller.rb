@@ -0,0 +1,5 @@
+class Caller
+  def call
+    Foo::Bar.qux
+  end
+end
@@ -0,0 +1,5 @@
+class Foo::Bar
+  def baz; end
+
+  def self.qux; end
+end
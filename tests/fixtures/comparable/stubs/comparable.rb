@@ -0,0 +1,7 @@
+module Comparable
+  def <(other); end
+
+  def >(other); end
+
+  def between?(min, max); end
+end
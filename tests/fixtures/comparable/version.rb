@@ -0,0 +1,20 @@
+class Version
+  include Comparable
+
+  def <=>(other); end
+
+  def newer?(other)
+    self > other
+  end
+end
+
+class Score
+  def <(other); end
+end
+
+def compare
+  a = Version.new
+  b = Version.new
+  a < b
+  a.between?(b, b)
+end
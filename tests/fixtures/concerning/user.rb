@@ -0,0 +1,16 @@
+class User
+  concerning :Trackable do
+    def track(event); end
+
+    class_methods do
+      def tracked_events; end
+    end
+  end
+
+  concerning(:Naming) { def full_name; end }
+
+  def save
+    track(:saved)
+    full_name
+  end
+end
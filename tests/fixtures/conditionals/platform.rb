@@ -0,0 +1,17 @@
+WINDOWS = true if Gem.win_platform?
+
+class Platform
+  if WINDOWS
+    def separator
+      "\\"
+    end
+  else
+    def separator
+      "/"
+    end
+  end
+
+  def join(a, b)
+    [a, b].join(separator())
+  end
+end
@@ -0,0 +1,18 @@
+Point = Data.define(:x, :y) do
+  def distance
+    Math.sqrt(x**2 + y**2)
+  end
+end
+
+Coordinates = Data.define("lat", "lng")
+
+Marker = Data.define
+
+origin = Point.new(x: 0, y: 0)
+origin.y
+
+Span = Data.define(:from, :to) do
+  def initialize(from:, to: from)
+    super
+  end
+end
@@ -0,0 +1,24 @@
+BEGIN {
+  STARTED_AT = Time.now
+}
+
+class Script
+  def run
+    DATA.each_line { |line| puts line }
+  end
+end
+
+END {
+  def report; end
+
+  report
+}
+
+Script.new.run
+
+__END__
+class FakeClass
+  def fake_method; end
+end
+
+FAKE_CONSTANT = 1
@@ -0,0 +1,12 @@
+class Client
+  DEFAULT_TIMEOUT = 5
+
+  def initialize(timeout = DEFAULT_TIMEOUT, retries: Retry::COUNT)
+    @timeout = timeout
+    @retries = retries
+  end
+end
+
+module Retry
+  COUNT = 3
+end
@@ -0,0 +1,5 @@
+class Invoice
+  def initialize(total:)
+    @total = total
+  end
+end
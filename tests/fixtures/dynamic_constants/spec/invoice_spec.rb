@@ -0,0 +1,9 @@
+RSpec.describe Invoice do
+  subject(:invoice) { described_class.new(total: 10) }
+
+  it "resolves the class by name" do
+    name = "Invoice"
+    klass = Object.const_get(name)
+    expect(klass.const_get(name).new).to be_a(described_class)
+  end
+end
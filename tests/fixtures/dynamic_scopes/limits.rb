@@ -0,0 +1,11 @@
+class Limits
+  LIMIT = 10
+
+  def limit
+    self.class::LIMIT
+  end
+
+  def fetch
+    build(1)::LIMIT
+  end
+end
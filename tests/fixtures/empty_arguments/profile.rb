@@ -0,0 +1,17 @@
+class Profile
+  attr_accessor
+  attr_reader()
+
+  define_method
+  define_method() { |value| value }
+  alias_method()
+  autoload
+
+  refine do
+    def shout; end
+  end
+
+  private
+
+  def name; end
+end
@@ -0,0 +1,7 @@
+class UserPresenter
+  def initialize(user)
+    @user = user
+  end
+
+  def display_name; end
+end
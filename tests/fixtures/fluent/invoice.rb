@@ -0,0 +1,25 @@
+class Invoice
+  def self.draft
+    self
+  end
+
+  def self.build
+    new
+  end
+
+  def self.create
+    draft.build.total
+  end
+
+  def self.copy
+    self.build.total
+  end
+
+  def total; end
+end
+
+class Billing
+  def run
+    Invoice.draft.build.total
+  end
+end
@@ -0,0 +1,19 @@
+class QueryBuilder
+  def where(condition)
+    @conditions = condition
+    self
+  end
+
+  def order(column)
+    @order = column
+    self
+  end
+
+  def to_sql; end
+end
+
+class Report
+  def run
+    QueryBuilder.new.where("a").order("b").to_sql
+  end
+end
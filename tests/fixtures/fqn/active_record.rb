@@ -0,0 +1,11 @@
+module ActiveRecord
+  class Base
+    def save; end
+
+    def self.create; end
+  end
+
+  module Persistence
+    def save; end
+  end
+end
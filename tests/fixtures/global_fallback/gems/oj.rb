@@ -0,0 +1,4 @@
+module Oj
+  class Parser
+  end
+end
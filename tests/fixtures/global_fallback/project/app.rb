@@ -0,0 +1,6 @@
+class App
+  def run
+    Settings.load
+    Oj::Parser.new
+  end
+end
@@ -0,0 +1,3 @@
+class Settings
+  def self.load; end
+end
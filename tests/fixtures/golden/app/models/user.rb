@@ -0,0 +1,16 @@
+module Models
+  class User < ApplicationRecord
+    include Comparable
+
+    ROLES = %w[admin member].freeze
+    ADMIN, MEMBER = ROLES
+
+    define_method(:admin?) { ROLES.first == role }
+
+    def self.find_by_email(email)
+    end
+
+    def name(format = :full, locale:)
+    end
+  end
+end
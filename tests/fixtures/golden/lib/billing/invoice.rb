@@ -0,0 +1,16 @@
+$billing_logger = nil
+
+module Billing
+  SETTINGS = OpenStruct.new(currency: "EUR")
+
+  class Invoice::Line
+    def total
+      @totals = OpenStruct.new(net: 0)
+    end
+  end
+end
+
+class Billing::Invoice
+  def <=>(other)
+  end
+end
@@ -0,0 +1,11 @@
+class Report
+  QUERY = <<~SQL
+    class Fake; end
+    FAKE_CONSTANT = 1
+    SELECT * FROM users WHERE id = #{Formatter::ID}
+  SQL
+
+  def title
+    "#{Formatter.bold(name)} report"
+  end
+end
@@ -0,0 +1,5 @@
+module Concerns
+  module Trackable
+    def track; end
+  end
+end
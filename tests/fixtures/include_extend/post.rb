@@ -0,0 +1,6 @@
+class Post
+  include Concerns::Trackable
+  extend Searchable
+  include ::Concerns::Trackable
+  extend ::Searchable
+end
@@ -0,0 +1,3 @@
+module Searchable
+  def search; end
+end
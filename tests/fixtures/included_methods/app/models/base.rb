@@ -0,0 +1,4 @@
+class Base
+  def persist
+  end
+end
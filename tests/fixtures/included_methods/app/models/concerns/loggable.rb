@@ -0,0 +1,10 @@
+module Loggable
+  # cycle back to Trackable
+  include Trackable
+
+  def log(message)
+  end
+
+  def persist
+  end
+end
@@ -0,0 +1,7 @@
+module Trackable
+  include Loggable
+
+  def track(event)
+    log(event)
+  end
+end
@@ -0,0 +1,10 @@
+class User < Base
+  include Trackable
+  include Loggable
+
+  def save
+    track(:save)
+    log("saved")
+    self.persist
+  end
+end
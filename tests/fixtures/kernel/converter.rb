@@ -0,0 +1,9 @@
+class Converter
+  def to_list(value)
+    Array(value)
+  end
+
+  def build
+    Array.new
+  end
+end
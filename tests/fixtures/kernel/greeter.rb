@@ -0,0 +1,12 @@
+class Greeter
+  def greet
+    puts "hi"
+    freeze
+  end
+
+  def self.wait
+    sleep 1
+  end
+end
+
+puts "done"
@@ -0,0 +1,3 @@
+class Array
+  def compact; end
+end
@@ -0,0 +1,15 @@
+module Kernel
+  def puts(*args); end
+
+  def raise(*args); end
+
+  def sleep(duration = nil); end
+
+  def Array(arg); end
+end
+
+class Object
+  include Kernel
+
+  def freeze; end
+end
@@ -0,0 +1,11 @@
+class Importer
+  def run
+    log_duration("import") { import }
+  end
+
+  def import
+    log_duration "rows" do
+      rows.each { |row| log_duration(row) { save(row) } }
+    end
+  end
+end
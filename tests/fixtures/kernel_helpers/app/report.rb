@@ -0,0 +1,13 @@
+class Report
+  def self.build
+    log_duration("report") { new.render }
+  end
+
+  def render
+    log_duration("render") { "report" }
+  end
+
+  def log_duration(label)
+    yield
+  end
+end
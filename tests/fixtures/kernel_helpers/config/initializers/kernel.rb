@@ -0,0 +1,8 @@
+module Kernel
+  def log_duration(label)
+    started = Time.now
+    yield
+  ensure
+    puts "#{label}: #{Time.now - started}"
+  end
+end
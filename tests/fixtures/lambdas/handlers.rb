@@ -0,0 +1,10 @@
+class Handlers
+  def build
+    adder = ->(a, b = 1) { a + b }
+    printer = lambda { |c| puts c }
+    logger = proc do |d|
+      d.upcase
+    end
+    [adder, printer, logger]
+  end
+end
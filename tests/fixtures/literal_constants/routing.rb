@@ -0,0 +1,7 @@
+class FooHandler; end
+class BarHandler; end
+
+module Routing
+  ALLOWED = [FooHandler, BarHandler]
+  HANDLERS = { foo: FooHandler, "bar" => BarHandler }
+end
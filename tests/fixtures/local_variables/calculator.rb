@@ -0,0 +1,7 @@
+class Calculator
+  def sum(a, b = 0, c: 1)
+    total = a + b
+    total = total + c
+    total
+  end
+end
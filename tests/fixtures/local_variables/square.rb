@@ -0,0 +1 @@
+class Square; def area(side); side * side; end; end
@@ -0,0 +1,6 @@
+class Tracer
+  def trace
+    name = __method__
+    puts name, __dir__, __FILE__
+  end
+end
@@ -0,0 +1,20 @@
+class Catalog
+  def initialize(items)
+    @items = items
+  end
+
+  def find(id, = nil)
+  end
+
+  def count
+    @items.size
+  end
+end
+
+module ::
+end
+
+class Catalog::Item <
+end
+
+VERSION = "1.0"
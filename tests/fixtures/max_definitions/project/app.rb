@@ -0,0 +1,7 @@
+class App
+  def call(env); end
+
+  def run(server)
+    server.handler.call
+  end
+end
@@ -0,0 +1,3 @@
+class Job
+  def call; end
+end
@@ -0,0 +1,18 @@
+module Settings
+  def self.defaults
+    { timeout: 5 }
+  end
+
+  def self.load!
+    @values = defaults
+  end
+
+  load!
+  timeout = defaults[:timeout]
+  TIMEOUT = timeout
+
+  def timeout
+    timeout = 10
+    timeout
+  end
+end
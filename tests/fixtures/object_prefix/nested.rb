@@ -0,0 +1,9 @@
+module A
+  class Foo
+  end
+
+  module B
+    X = Object::Foo
+    Y = ::Object::Foo
+  end
+end
@@ -0,0 +1,15 @@
+CONFIG = OpenStruct.new(host: "localhost", "port" => 3000, :ssl => false, **overrides)
+
+class Client
+  DEFAULTS = ::OpenStruct.new(timeout: 5)
+
+  def initialize
+    @options = OpenStruct.new(verbose: true)
+  end
+
+  def call
+    CONFIG.host
+    DEFAULTS.timeout
+    @options.verbose
+  end
+end
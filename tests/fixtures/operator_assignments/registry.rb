@@ -0,0 +1,5 @@
+module Registry
+  HANDLERS ||= {}
+  VERSION += 1
+  handlers ||= []
+end
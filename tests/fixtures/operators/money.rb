@@ -0,0 +1,24 @@
+class Money
+  def self.[](amount)
+    new(amount)
+  end
+
+  def +(other)
+  end
+
+  def [](index)
+  end
+
+  def []=(index, value)
+  end
+
+  def <=>(other)
+  end
+
+  def -@
+  end
+
+  def double
+    self + self
+  end
+end
@@ -0,0 +1,7 @@
+a = Money[10]
+b = a + a
+a[0]
+a[0] = 1
+a <=> b
+-a
+a && b
@@ -0,0 +1,4 @@
+class Vector
+  def +(other)
+  end
+end
@@ -0,0 +1,28 @@
+if RUBY_PLATFORM =~ /java/
+  require "java"
+
+  class NativeParser
+    def parse(input); end
+  end
+elsif RUBY_PLATFORM =~ /mswin|mingw/
+  class NativeParser
+    def parse(input); end
+  end
+else
+  module Native
+    class Parser; end
+  end
+end
+
+unless defined?(JRUBY_VERSION)
+  FAST_PATH = true
+end
+
+case RbConfig::CONFIG["host_os"]
+when /darwin/
+  class MacClipboard; end
+when /linux/
+  class LinuxClipboard; end
+else
+  class NullClipboard; end
+end
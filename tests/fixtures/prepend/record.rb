@@ -0,0 +1,20 @@
+module Auditing
+  def save
+    super
+  end
+end
+
+class Record
+  prepend Auditing
+
+  def save; end
+
+  def publish
+    save
+  end
+end
+
+def publish_record
+  record = Record.new
+  record.save
+end
@@ -0,0 +1,11 @@
+module Billing
+  class Charge
+    def call(amount)
+      raise Errors::PaymentFailed, "amount must be positive" if amount <= 0
+
+      gateway.charge(amount)
+    rescue Errors::GatewayTimeout => e
+      raise Errors::PaymentFailed, e.message
+    end
+  end
+end
@@ -0,0 +1,9 @@
+module Billing
+  module Errors
+    class PaymentFailed < StandardError
+    end
+
+    class GatewayTimeout < StandardError
+    end
+  end
+end
@@ -0,0 +1,5 @@
+class Billing::Refund
+  def call
+    raise Errors::PaymentFailed.new("already refunded")
+  end
+end
@@ -0,0 +1,13 @@
+class Invoice
+  def self.build(total)
+    new(total)
+  end
+
+  def initialize(total)
+    @total = total
+  end
+
+  def total_with_tax(rate)
+    @total * (1 + rate)
+  end
+end
@@ -0,0 +1,6 @@
+class Checkout
+  def call
+    invoice = Invoice.new(100)
+    Invoice.build(invoice.total_with_tax(0.2))
+  end
+end
@@ -0,0 +1,17 @@
+module StringExt
+  refine String do
+    def shout
+      upcase + "!"
+    end
+  end
+
+  def self.helper; end
+end
+
+class String
+  def whisper; end
+end
+
+using StringExt
+
+"hi".shout
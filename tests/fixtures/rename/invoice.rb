@@ -0,0 +1,9 @@
+class Invoice
+  def total(currency)
+    currency
+  end
+
+  def summary
+    "Total: #{self.total(:usd)}"
+  end
+end
@@ -0,0 +1,5 @@
+class Order
+  def total(currency)
+    currency
+  end
+end
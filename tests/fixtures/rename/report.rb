@@ -0,0 +1,7 @@
+class Report
+  def call(document)
+    Invoice.new.total(:eur)
+    Order.new.total(:eur)
+    document.total(:eur)
+  end
+end
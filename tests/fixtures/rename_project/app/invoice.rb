@@ -0,0 +1,5 @@
+class Invoice
+  def total
+    Ledger.new.balance
+  end
+end
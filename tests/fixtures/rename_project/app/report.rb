@@ -0,0 +1,5 @@
+class Report
+  def call
+    Invoice.new.total
+  end
+end
@@ -0,0 +1,9 @@
+class Foo
+  def self.bar; end
+end
+
+class Caller
+  def call
+    Foo.bar
+  end
+end
@@ -0,0 +1,3 @@
+module Foo
+  BAR = 1
+end
@@ -0,0 +1,9 @@
+module Foo
+  MAX = 10
+
+  class Limits
+    def max
+      MAX + BAR
+    end
+  end
+end
@@ -0,0 +1,3 @@
+module Reports
+  FOO_VERSION = 1
+end
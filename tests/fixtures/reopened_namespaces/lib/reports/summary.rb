@@ -0,0 +1,7 @@
+module Reports
+  class Summary
+    def total
+      Foo::BAR + Foo::MAX
+    end
+  end
+end
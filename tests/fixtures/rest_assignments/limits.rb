@@ -0,0 +1,6 @@
+module Limits
+  MIN, *REST = [1, 2, 3]
+  *Defaults::SIZES, MAX = [1, 2, 3]
+  Defaults::LOW, HIGH = 1, 2
+  low, *rest = [1, 2]
+end
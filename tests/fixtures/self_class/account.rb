@@ -0,0 +1,3 @@
+class Account
+  def self.create(attrs); end
+end
@@ -0,0 +1,7 @@
+class User
+  def self.create(attrs); end
+
+  def copy
+    self.class.create(name)
+  end
+end
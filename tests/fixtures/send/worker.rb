@@ -0,0 +1,11 @@
+class Worker
+  def run
+    self.send(:process)
+    public_send(:cleanup, 1)
+    send(:process, :cleanup)
+  end
+
+  def process; end
+
+  def cleanup(force); end
+end
@@ -0,0 +1,5 @@
+class Formatter
+  def format(value)
+    String.new(value).strip
+  end
+end
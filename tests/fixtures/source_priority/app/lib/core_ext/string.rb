@@ -0,0 +1,6 @@
+class String
+  # strips non-breaking spaces as well
+  def strip
+    super.tr(" ", " ").strip
+  end
+end
@@ -0,0 +1,7 @@
+class String
+  def strip
+  end
+
+  def upcase
+  end
+end
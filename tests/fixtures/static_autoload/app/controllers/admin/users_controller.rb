@@ -0,0 +1,4 @@
+module Admin
+  class UsersController
+  end
+end
@@ -0,0 +1,14 @@
+$verbose = false
+
+module Reports
+  FORMATS = %i[csv pdf].freeze
+
+  class Exporter
+    def self.build
+      new
+    end
+
+    def export
+    end
+  end
+end
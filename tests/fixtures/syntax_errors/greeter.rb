@@ -0,0 +1,16 @@
+class Greeter
+  GREETING = "hi".freeze
+
+  def greet(name)
+    message = GREETING + name
+    if message.
+  end
+
+  def farewell
+    Farewell::MESSAGE +
+  end
+end
+
+module Farewell
+  MESSAGE = "bye"
+end
@@ -0,0 +1,3 @@
+describe do
+  FakeUser.new
+end
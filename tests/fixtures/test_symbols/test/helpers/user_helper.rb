@@ -0,0 +1,2 @@
+module UserHelper
+end
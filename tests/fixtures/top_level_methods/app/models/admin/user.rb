@@ -0,0 +1,7 @@
+def helper; end
+
+def self.build; end
+
+class Role
+  def name; end
+end
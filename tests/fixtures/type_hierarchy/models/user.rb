@@ -0,0 +1,8 @@
+module Models
+  class User < Base
+    include Auditable
+  end
+
+  class Admin < User
+  end
+end
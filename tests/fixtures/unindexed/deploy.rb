@@ -0,0 +1,3 @@
+class Deploy
+  def production; end
+end
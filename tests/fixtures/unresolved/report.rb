@@ -0,0 +1,12 @@
+class Report
+  unknown_helper
+  columns = [:id]
+
+  module Formatting
+    missing_format
+  end
+
+  def render
+    columns.map(&:to_s)
+  end
+end
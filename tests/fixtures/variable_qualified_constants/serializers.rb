@@ -0,0 +1,21 @@
+class BaseSerializer
+  VERSION = 1
+end
+
+module Serializers
+  class User < BaseSerializer
+    FIELDS = [:id, :name].freeze
+
+    class Options; end
+  end
+
+  FIELDS = [].freeze
+end
+
+def serialize(format)
+  klass = Serializers::User
+  klass::FIELDS
+  klass::VERSION
+  klass::Options
+  format::FIELDS
+end
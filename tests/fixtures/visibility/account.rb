@@ -0,0 +1,19 @@
+class Account
+  def balance
+    @balance
+  end
+
+  private def secret
+    "pin"
+  end
+
+  protected def audit
+    @log = OpenStruct.new(level: :debug)
+  end
+
+  public def deposit(amount)
+    @balance += amount
+  end
+
+  private :balance
+end
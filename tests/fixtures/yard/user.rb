@@ -0,0 +1,4 @@
+module Models
+  class User
+  end
+end
@@ -0,0 +1,7 @@
+module Models
+  class UsersRepository
+    # @return [Array<Models::User>] all users
+    def all
+    end
+  end
+end